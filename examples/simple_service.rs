@@ -13,6 +13,7 @@ use datafusion_odata::{
     context::{CollectionContext, OnUnsupported, ServiceContext},
     error::{CollectionNotFound, ODataError},
     handlers::{MEDIA_TYPE_ATOM, MEDIA_TYPE_XML},
+    negotiation::FormatQueryParam,
 };
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -27,9 +28,11 @@ const DEFAULT_MAX_ROWS: usize = 100;
 pub async fn odata_service_handler(
     axum::extract::State(query_ctx): axum::extract::State<SessionContext>,
     host: TypedHeader<Host>,
+    query: axum::extract::Query<FormatQueryParam>,
+    headers: axum::http::HeaderMap,
 ) -> Result<Response<String>, ODataError> {
     let ctx = Arc::new(ODataContext::new_service(query_ctx, host));
-    datafusion_odata::handlers::odata_service_handler(axum::Extension(ctx)).await
+    datafusion_odata::handlers::odata_service_handler(axum::Extension(ctx), query, headers).await
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -37,9 +40,16 @@ pub async fn odata_service_handler(
 pub async fn odata_metadata_handler(
     axum::extract::State(query_ctx): axum::extract::State<SessionContext>,
     host: TypedHeader<Host>,
+    query: axum::extract::Query<FormatQueryParam>,
+    headers: axum::http::HeaderMap,
 ) -> Result<Response<String>, ODataError> {
     let ctx = ODataContext::new_service(query_ctx, host);
-    datafusion_odata::handlers::odata_metadata_handler(axum::Extension(Arc::new(ctx))).await
+    datafusion_odata::handlers::odata_metadata_handler(
+        axum::Extension(Arc::new(ctx)),
+        query,
+        headers,
+    )
+    .await
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -50,7 +60,7 @@ pub async fn odata_collection_handler(
     axum::extract::Path(collection_path_element): axum::extract::Path<String>,
     query: axum::extract::Query<QueryParamsRaw>,
     headers: axum::http::HeaderMap,
-) -> Result<Response<String>, ODataError> {
+) -> Result<Response<axum::body::Body>, ODataError> {
     let Some(addr) = CollectionAddr::decode(&collection_path_element) else {
         Err(CollectionNotFound::new(collection_path_element))?
     };
@@ -127,6 +137,9 @@ impl ServiceContext for ODataContext {
                 addr: Some(CollectionAddr {
                     name: table_name,
                     key: None,
+                    property: None,
+                    value: false,
+                    count: false,
                 }),
             }));
         }
@@ -159,6 +172,10 @@ impl CollectionContext for ODataContext {
         Ok(self.addr()?.name.clone())
     }
 
+    fn key_column(&self) -> Result<String, ODataError> {
+        Ok("offset".to_string())
+    }
+
     async fn last_updated_time(&self) -> DateTime<Utc> {
         Utc::now()
     }
@@ -193,7 +210,7 @@ impl CollectionContext for ODataContext {
             .apply(
                 df,
                 self.addr()?,
-                "offset",
+                &["offset"],
                 &self.key_column_alias(),
                 DEFAULT_MAX_ROWS,
                 usize::MAX,
@@ -201,6 +218,10 @@ impl CollectionContext for ODataContext {
             .map_err(ODataError::internal)
     }
 
+    fn max_page_size(&self) -> Option<usize> {
+        Some(DEFAULT_MAX_ROWS)
+    }
+
     fn on_unsupported_feature(&self) -> OnUnsupported {
         OnUnsupported::Error
     }
@@ -283,13 +304,16 @@ async fn main() {
             axum::routing::get(mock_odata_metadata_handler),
         )
         .route(
-            "/mock/:collection",
+            "/mock/{collection}",
             axum::routing::get(mock_odata_collection_handler),
         )
         // Real
         .route("/", axum::routing::get(odata_service_handler))
         .route("/$metadata", axum::routing::get(odata_metadata_handler))
-        .route("/:collection", axum::routing::get(odata_collection_handler))
+        .route(
+            "/{*collection}",
+            axum::routing::get(odata_collection_handler),
+        )
         .layer(tower_http::trace::TraceLayer::new_for_http())
         .layer(
             tower_http::cors::CorsLayer::new()
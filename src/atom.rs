@@ -1,68 +1,23 @@
-use std::sync::Arc;
+use std::{io::Write, sync::Arc};
 
+use base64::Engine as _;
 use chrono::{DateTime, Utc};
 use datafusion::arrow::{
-    array::{Array, AsArray, PrimitiveArray, RecordBatch},
+    array::{
+        Array, ArrayRef, AsArray, PrimitiveArray, downcast_dictionary_array, downcast_run_array,
+    },
     datatypes::{DataType, *},
 };
 use quick_xml::events::*;
 
 use crate::{
-    context::{CollectionContext, OnUnsupported},
-    error::{ODataError, UnsupportedDataType, UnsupportedNetProtocol},
-    metadata::to_edm_type,
+    context::Relationship,
+    encoder::{CollectionInfo, EdmField, ResponseEncoder},
+    error::{ODataError, UnsupportedDataType},
+    geo::{Coord, Geometry, SpatialType},
+    handlers::MEDIA_TYPE_ATOM,
 };
 
-// TODO: Replace with an interface similar to Encoder
-// See: https://github.com/kamu-data/kamu-cli/blob/385bbf56036d4485efdf54bf458a95bfba048b2b/src/utils/data-utils/src/data/format/traits.rs#L69
-struct Edm {
-    typ: String,
-    tag: String,
-}
-
-impl Edm {
-    fn from_field(field: &Arc<Field>) -> Result<Self, UnsupportedDataType> {
-        // TODO: Escape field name
-        let tag = format!("d:{}", field.name());
-        let typ = to_edm_type(field.data_type())?.to_string();
-        Ok(Self { typ, tag })
-    }
-}
-
-fn to_edms(
-    schema: &Schema,
-    key_column: &str,
-    on_unsupported: OnUnsupported,
-) -> Result<(Vec<(Edm, usize)>, usize), UnsupportedDataType> {
-    let mut edms = Vec::new();
-    let mut key_edm_index = usize::MAX;
-
-    for (index, field) in schema.fields().iter().enumerate() {
-        if field.name() == key_column {
-            key_edm_index = index;
-            continue;
-        }
-        let edm = match Edm::from_field(field) {
-            Ok(typ) => typ,
-            Err(err) => match on_unsupported {
-                OnUnsupported::Error => return Err(err),
-                OnUnsupported::Warn => {
-                    tracing::warn!(
-                        field = field.name(),
-                        error = %err,
-                        error_dbg = ?err,
-                        "Unsupported field type - skipping",
-                    );
-                    continue;
-                }
-            },
-        };
-
-        edms.push((edm, index));
-    }
-    Ok((edms, key_edm_index))
-}
-
 ///////////////////////////////////////////////////////////////////////////////
 
 // https://www.odata.org/documentation/odata-version-3-0/atom-format/
@@ -98,364 +53,493 @@ fn to_edms(
 //     </content>
 //   </entry>
 //   <entry>
-//     <id>http://a5d4b8ec90d5144a08efb47e789d49d5-1706314482.us-west-2.elb.amazonaws.com/tickers_spy(1)</id>
-//     <category term="ODataDemo.tickers_spy" scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" />
-//     <link rel="edit" title="tickers_spy" href="tickers_spy(1)" />
-//     <title />
-//     <updated>2024-03-10T00:36:45Z</updated>
-//     <author>
-//       <name />
-//     </author>
-//     <content type="application/xml">
-//       <m:properties>
-//         <d:offset m:type="Edm.Int64">1</d:offset>
-//         <d:from_symbol m:type="Edm.String">spy</d:from_symbol>
-//         <d:to_symbol m:type="Edm.String">usd</d:to_symbol>
-//         <d:close m:type="Edm.Double">136.5622</d:close>
-//       </m:properties>
-//     </content>
+//     ...
 //   </entry>
 // </feed>
 //
-// TODO: Use erased dyn Writer type
-// TODO: Extract `CollectionInfo` type to avoid propagating
-//       a bunch of individual parameters
-pub fn write_atom_feed_from_records<W>(
-    schema: &Schema,
-    record_batches: Vec<RecordBatch>,
-    ctx: &dyn CollectionContext,
-    updated_time: DateTime<Utc>,
-    writer: &mut quick_xml::Writer<W>,
-) -> Result<(), ODataError>
-where
-    W: std::io::Write,
-{
-    let mut service_base_url = ctx.service_base_url()?;
-    let mut collection_base_url = ctx.collection_base_url()?;
-    let collection_name = ctx.collection_name()?;
-    let type_name = ctx.collection_name()?;
-    let type_namespace = ctx.collection_namespace()?;
+// When a single entry is requested the <entry> element becomes the root and
+// carries the `xml:base` and namespace attributes instead of the <feed>.
+#[derive(Debug, Default)]
+pub struct AtomEncoder {
+    // Nesting level of the currently open feed / entry elements
+    depth: usize,
+    // Whether <content> of the current entry was started, which is deferred
+    // until after the navigation links
+    content_started: bool,
+    // Whether the currently open navigation links are expanded
+    navigation: Vec<bool>,
+}
 
-    if !service_base_url.starts_with("http") {
-        return Err(UnsupportedNetProtocol::new(service_base_url).into());
-    }
-    if !collection_base_url.starts_with("http") {
-        return Err(UnsupportedNetProtocol::new(collection_base_url).into());
+impl AtomEncoder {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    if !service_base_url.ends_with('/') {
-        service_base_url.push('/');
-    }
-    if collection_base_url.ends_with('/') {
-        collection_base_url.pop();
-    }
+    fn start_root(
+        &self,
+        writer: &mut quick_xml::Writer<&mut dyn Write>,
+        tag: &str,
+        info: &CollectionInfo,
+    ) -> Result<(), ODataError> {
+        writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))?;
+
+        let mut start = BytesStart::new(tag);
+        start.push_attribute(("xml:base", info.service_base_url.as_str()));
+        start.push_attribute(("xmlns", "http://www.w3.org/2005/Atom"));
+        start.push_attribute((
+            "xmlns:d",
+            "http://schemas.microsoft.com/ado/2007/08/dataservices",
+        ));
+        start.push_attribute((
+            "xmlns:m",
+            "http://schemas.microsoft.com/ado/2007/08/dataservices/metadata",
+        ));
 
-    let fq_type = format!("{type_namespace}.{type_name}");
+        writer.write_event(Event::Start(start))?;
+        Ok(())
+    }
 
-    let (edms, key_edm_index) = to_edms(
-        schema,
-        &ctx.key_column_alias(),
-        ctx.on_unsupported_feature(),
-    )?;
+    // <content type="application/xml">
+    //   <m:properties>
+    fn start_content(&mut self, writer: &mut dyn Write) -> Result<(), ODataError> {
+        if self.content_started {
+            return Ok(());
+        }
+        self.content_started = true;
+
+        let mut writer = quick_xml::Writer::new(writer);
+        writer.write_event(Event::Start(
+            BytesStart::new("content").with_attributes([("type", "application/xml")]),
+        ))?;
+        writer.write_event(Event::Start(BytesStart::new("m:properties")))?;
+        Ok(())
+    }
+}
 
-    writer.write_event(quick_xml::events::Event::Decl(BytesDecl::new(
-        "1.0",
-        Some("utf-8"),
-        None,
-    )))?;
+impl ResponseEncoder for AtomEncoder {
+    fn media_type(&self) -> &str {
+        MEDIA_TYPE_ATOM
+    }
 
-    let mut feed = BytesStart::new("feed");
-    feed.push_attribute(("xml:base", service_base_url.as_str()));
-    feed.push_attribute(("xmlns", "http://www.w3.org/2005/Atom"));
-    feed.push_attribute((
-        "xmlns:d",
-        "http://schemas.microsoft.com/ado/2007/08/dataservices",
-    ));
-    feed.push_attribute((
-        "xmlns:m",
-        "http://schemas.microsoft.com/ado/2007/08/dataservices/metadata",
-    ));
+    fn start_feed(
+        &mut self,
+        writer: &mut dyn Write,
+        info: &CollectionInfo,
+        count: Option<u64>,
+    ) -> Result<(), ODataError> {
+        let mut writer = quick_xml::Writer::new(writer);
 
-    writer.write_event(Event::Start(feed))?;
-
-    // <id>http://a5d4b8ec90d5144a08efb47e789d49d5-1706314482.us-west-2.elb.amazonaws.com/tickers_spy/</id>
-    // <title type="text">tickers_spy</title>
-    // <updated>2024-03-10T00:36:45Z</updated>
-    // <link rel="self" title="tickers_spy" href="tickers_spy" />
-    writer
-        .create_element("id")
-        .write_text_content(BytesText::from_escaped(&collection_base_url))?;
-    writer
-        .create_element("title")
-        .with_attribute(("type", "text"))
-        .write_text_content(BytesText::from_escaped(&collection_name))?;
-    writer
-        .create_element("updated")
-        .write_text_content(encode_date_time(&updated_time))?;
-    writer
-        .create_element("link")
-        .with_attributes([
-            ("rel", "self"),
-            ("title", collection_name.as_str()),
-            ("href", collection_name.as_str()),
-        ])
-        .write_empty()?;
-
-    for batch in record_batches {
-        for row in 0..batch.num_rows() {
-            writer.write_event(Event::Start(BytesStart::new("entry")))?;
+        if self.depth == 0 {
+            self.start_root(&mut writer, "feed", info)?;
+        } else {
+            writer.write_event(Event::Start(BytesStart::new("feed")))?;
+        }
+        self.depth += 1;
+
+        // <id>http://a5d4b8ec90d5144a08efb47e789d49d5-1706314482.us-west-2.elb.amazonaws.com/tickers_spy/</id>
+        // <title type="text">tickers_spy</title>
+        // <updated>2024-03-10T00:36:45Z</updated>
+        // <link rel="self" title="tickers_spy" href="tickers_spy" />
+        writer
+            .create_element("id")
+            .write_text_content(BytesText::from_escaped(&info.collection_base_url))?;
+        writer
+            .create_element("title")
+            .with_attribute(("type", "text"))
+            .write_text_content(BytesText::from_escaped(&info.collection_name))?;
+        writer
+            .create_element("updated")
+            .write_text_content(encode_date_time(&info.updated_time))?;
+        writer
+            .create_element("link")
+            .with_attributes([
+                ("rel", "self"),
+                ("title", info.collection_name.as_str()),
+                ("href", info.collection_name.as_str()),
+            ])
+            .write_empty()?;
 
-            // <id>http://a5d4b8ec90d5144a08efb47e789d49d5-1706314482.us-west-2.elb.amazonaws.com/tickers_spy(1)</id>
-            // <category term="ODataDemo.tickers_spy" scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" />
-            // <link rel="edit" title="tickers_spy" href="tickers_spy(1)" />
-            // <title />
-            // <updated>2024-03-10T00:36:45Z</updated>
-            // <author>
-            //   <name />
-            // </author>
+        // <m:count>2</m:count>
+        if let Some(count) = count {
+            writer
+                .create_element("m:count")
+                .write_text_content(BytesText::new(&count.to_string()))?;
+        }
 
-            let id = encode_primitive_dyn(batch.column(key_edm_index), row)?.decode()?;
+        Ok(())
+    }
 
-            let entry_url_rel = format!("{collection_name}({id})");
-            let entry_url_full = format!("{collection_base_url}({id})");
+    fn start_entry(
+        &mut self,
+        writer: &mut dyn Write,
+        info: &CollectionInfo,
+        key: Option<&str>,
+    ) -> Result<(), ODataError> {
+        let mut writer = quick_xml::Writer::new(writer);
 
+        if self.depth == 0 {
+            self.start_root(&mut writer, "entry", info)?;
+        } else {
+            writer.write_event(Event::Start(BytesStart::new("entry")))?;
+        }
+        self.depth += 1;
+
+        // <id>http://a5d4b8ec90d5144a08efb47e789d49d5-1706314482.us-west-2.elb.amazonaws.com/tickers_spy(1)</id>
+        // <category term="ODataDemo.tickers_spy" scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" />
+        // <link rel="edit" title="tickers_spy" href="tickers_spy(1)" />
+        // <title />
+        // <updated>2024-03-10T00:36:45Z</updated>
+        // <author>
+        //   <name />
+        // </author>
+        // Entries of records with null keys have no id and edit link
+        if let Some(key) = key {
             writer
                 .create_element("id")
-                .write_text_content(BytesText::from_escaped(entry_url_full))?;
-            writer
-                .create_element("category")
-                .with_attributes([
-                    (
-                        "scheme",
-                        "http://schemas.microsoft.com/ado/2007/08/dataservices/scheme",
-                    ),
-                    ("term", &fq_type),
-                ])
-                .write_empty()?;
+                .write_text_content(BytesText::from_escaped(format!(
+                    "{}({key})",
+                    info.collection_base_url
+                )))?;
+        }
+        writer
+            .create_element("category")
+            .with_attributes([
+                (
+                    "scheme",
+                    "http://schemas.microsoft.com/ado/2007/08/dataservices/scheme",
+                ),
+                ("term", &info.fq_type()),
+            ])
+            .write_empty()?;
+        if let Some(key) = key {
             writer
                 .create_element("link")
                 .with_attributes([
                     ("rel", "edit"),
-                    ("title", &collection_name),
-                    ("href", &entry_url_rel),
+                    ("title", info.collection_name.as_str()),
+                    ("href", &format!("{}({key})", info.collection_name)),
                 ])
                 .write_empty()?;
-            writer.create_element("title").write_empty()?;
-            writer
-                .create_element("updated")
-                .write_text_content(encode_date_time(&updated_time))?;
-            writer.write_event(Event::Start(BytesStart::new("author")))?;
-            writer.create_element("name").write_empty()?;
-            writer.write_event(Event::End(BytesEnd::new("author")))?;
-
-            // <content type="application/xml">
-            //   <m:properties>
-            //     <d:offset m:type="Edm.Int64">1</d:offset>
-            //     <d:from_symbol m:type="Edm.String">spy</d:from_symbol>
-            //     <d:to_symbol m:type="Edm.String">usd</d:to_symbol>
-            //     <d:close m:type="Edm.Double">136.5622</d:close>
-            //   </m:properties>
-            // </content>
-            writer.write_event(Event::Start(
-                BytesStart::new("content").with_attributes([("type", "application/xml")]),
-            ))?;
-            writer.write_event(Event::Start(BytesStart::new("m:properties")))?;
-
-            for (edm, index) in &edms {
-                let col = batch.column(*index);
-
-                let mut start = BytesStart::new(&edm.tag);
-                start.push_attribute(("m:type", edm.typ.as_str()));
-                writer.write_event(Event::Start(start))?;
-                writer.write_event(Event::Text(encode_primitive_dyn(col, row)?))?;
-                writer.write_event(Event::End(BytesEnd::new(&edm.tag)))?;
-            }
-
-            writer.write_event(Event::End(BytesEnd::new("m:properties")))?;
-            writer.write_event(Event::End(BytesEnd::new("content")))?;
-            writer.write_event(Event::End(BytesEnd::new("entry")))?;
         }
+        writer.create_element("title").write_empty()?;
+        writer
+            .create_element("updated")
+            .write_text_content(encode_date_time(&info.updated_time))?;
+        writer.write_event(Event::Start(BytesStart::new("author")))?;
+        writer.create_element("name").write_empty()?;
+        writer.write_event(Event::End(BytesEnd::new("author")))?;
+
+        self.content_started = false;
+        Ok(())
     }
 
-    writer.write_event(Event::End(BytesEnd::new("feed")))?;
+    // <link
+    //   rel="http://schemas.microsoft.com/ado/2007/08/dataservices/related/Orders"
+    //   type="application/atom+xml;type=feed"
+    //   title="Orders"
+    //   href="Customers(1)/Orders">
+    //   <m:inline>
+    //     <feed>...</feed>
+    //   </m:inline>
+    // </link>
+    fn start_navigation(
+        &mut self,
+        writer: &mut dyn Write,
+        info: &CollectionInfo,
+        key: &str,
+        relationship: &Relationship,
+        expanded: bool,
+    ) -> Result<(), ODataError> {
+        let mut writer = quick_xml::Writer::new(writer);
+
+        let rel = format!(
+            "http://schemas.microsoft.com/ado/2007/08/dataservices/related/{}",
+            relationship.name
+        );
+        let typ = if relationship.to_many() {
+            "application/atom+xml;type=feed"
+        } else {
+            "application/atom+xml;type=entry"
+        };
+        let href = format!("{}({key})/{}", info.collection_name, relationship.name);
+
+        let start = BytesStart::new("link").with_attributes([
+            ("rel", rel.as_str()),
+            ("type", typ),
+            ("title", relationship.name.as_str()),
+            ("href", href.as_str()),
+        ]);
+
+        if expanded {
+            writer.write_event(Event::Start(start))?;
+            writer.write_event(Event::Start(BytesStart::new("m:inline")))?;
+        } else {
+            writer.write_event(Event::Empty(start))?;
+        }
+        self.navigation.push(expanded);
 
-    Ok(())
-}
+        Ok(())
+    }
 
-///////////////////////////////////////////////////////////////////////////////
+    fn end_navigation(&mut self, writer: &mut dyn Write) -> Result<(), ODataError> {
+        let mut writer = quick_xml::Writer::new(writer);
 
-// https://www.odata.org/documentation/odata-version-3-0/atom-format/
-//
-// <?xml version="1.0" encoding="utf-8"?>
-// <entry
-//   xml:base="http://a5d4b8ec90d5144a08efb47e789d49d5-1706314482.us-west-2.elb.amazonaws.com/"
-//   xmlns="http://www.w3.org/2005/Atom"
-//   xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
-//   xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata">
-//   <id>http://a5d4b8ec90d5144a08efb47e789d49d5-1706314482.us-west-2.elb.amazonaws.com/tickers_spy(0)</id>
-//   <category term="ODataDemo.tickers_spy" scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" />
-//   <link rel="edit" title="tickers_spy" href="tickers_spy(0)" />
-//   <title />
-//   <updated>2024-03-10T00:36:45Z</updated>
-//   <author>
-//     <name />
-//   </author>
-//   <content type="application/xml">
-//     <m:properties>
-//       <d:offset m:type="Edm.Int64">0</d:offset>
-//       <d:from_symbol m:type="Edm.String">spy</d:from_symbol>
-//       <d:to_symbol m:type="Edm.String">usd</d:to_symbol>
-//       <d:close m:type="Edm.Double">135.5625</d:close>
-//     </m:properties>
-//   </content>
-// </entry>
-// TODO: Use erased dyn Writer type
-// TODO: Extract `CollectionInfo` type to avoid propagating
-//       a bunch of individual parameters
-pub fn write_atom_entry_from_record<W>(
-    schema: &Schema,
-    batch: RecordBatch,
-    ctx: &dyn CollectionContext,
-    updated_time: DateTime<Utc>,
-    writer: &mut quick_xml::Writer<W>,
-) -> Result<(), ODataError>
-where
-    W: std::io::Write,
-{
-    let mut service_base_url = ctx.service_base_url()?;
-    let mut collection_base_url = ctx.collection_base_url()?;
-    let collection_name = ctx.collection_name()?;
-    let type_name = ctx.collection_name()?;
-    let type_namespace = ctx.collection_namespace()?;
+        if self.navigation.pop() == Some(true) {
+            writer.write_event(Event::End(BytesEnd::new("m:inline")))?;
+            writer.write_event(Event::End(BytesEnd::new("link")))?;
+        }
+        // Nested entries have been closed by now
+        self.content_started = false;
 
-    if !service_base_url.starts_with("http") {
-        return Err(UnsupportedNetProtocol::new(service_base_url).into());
-    }
-    if !collection_base_url.starts_with("http") {
-        return Err(UnsupportedNetProtocol::new(collection_base_url).into());
+        Ok(())
     }
 
-    if !service_base_url.ends_with('/') {
-        service_base_url.push('/');
+    // <d:offset m:type="Edm.Int64">1</d:offset>
+    // <d:close m:type="Edm.Double" m:null="true"/>
+    fn property(
+        &mut self,
+        writer: &mut dyn Write,
+        field: &EdmField,
+        col: &ArrayRef,
+        row: usize,
+    ) -> Result<(), ODataError> {
+        self.start_content(writer)?;
+        let mut writer = quick_xml::Writer::new(writer);
+
+        // TODO: Escape field name
+        let start = BytesStart::new(format!("d:{}", field.name));
+        write_property(&mut writer, start, field, col, row)
     }
-    if collection_base_url.ends_with('/') {
-        collection_base_url.pop();
+
+    fn end_entry(&mut self, writer: &mut dyn Write) -> Result<(), ODataError> {
+        self.start_content(writer)?;
+        let mut writer = quick_xml::Writer::new(writer);
+
+        writer.write_event(Event::End(BytesEnd::new("m:properties")))?;
+        writer.write_event(Event::End(BytesEnd::new("content")))?;
+        writer.write_event(Event::End(BytesEnd::new("entry")))?;
+        self.depth -= 1;
+        self.content_started = false;
+
+        Ok(())
     }
 
-    let fq_type = format!("{type_namespace}.{type_name}");
+    // <link rel="next" href="http://example.com/odata/tickers_spy?$skiptoken=..." />
+    fn end_feed(
+        &mut self,
+        writer: &mut dyn Write,
+        next_link: Option<&str>,
+    ) -> Result<(), ODataError> {
+        let mut writer = quick_xml::Writer::new(writer);
+
+        if let Some(next_link) = next_link {
+            writer
+                .create_element("link")
+                .with_attributes([("rel", "next"), ("href", next_link)])
+                .write_empty()?;
+        }
+        writer.write_event(Event::End(BytesEnd::new("feed")))?;
+        self.depth -= 1;
+
+        Ok(())
+    }
+}
 
-    let (edms, key_edm_index) = to_edms(
-        schema,
-        &ctx.key_column_alias(),
-        ctx.on_unsupported_feature(),
-    )?;
+///////////////////////////////////////////////////////////////////////////////
 
-    writer.write_event(quick_xml::events::Event::Decl(BytesDecl::new(
-        "1.0",
-        Some("utf-8"),
-        None,
-    )))?;
+// https://www.odata.org/documentation/odata-version-3-0/atom-format/#primitiveandcomplexproperties
+//
+// <?xml version="1.0" encoding="utf-8"?>
+// <d:close
+//   xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+//   xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata"
+//   m:type="Edm.Double">134.5937</d:close>
+pub fn write_property_xml(
+    writer: &mut dyn Write,
+    field: &EdmField,
+    col: &ArrayRef,
+    row: usize,
+) -> Result<(), ODataError> {
+    let mut writer = quick_xml::Writer::new(writer);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))?;
 
-    let mut entry = BytesStart::new("entry");
-    entry.push_attribute(("xml:base", service_base_url.as_str()));
-    entry.push_attribute(("xmlns", "http://www.w3.org/2005/Atom"));
-    entry.push_attribute((
+    let mut start = BytesStart::new(format!("d:{}", field.name));
+    start.push_attribute((
         "xmlns:d",
         "http://schemas.microsoft.com/ado/2007/08/dataservices",
     ));
-    entry.push_attribute((
+    start.push_attribute((
         "xmlns:m",
         "http://schemas.microsoft.com/ado/2007/08/dataservices/metadata",
     ));
+    write_property(&mut writer, start, field, col, row)
+}
 
-    writer.write_event(Event::Start(entry))?;
-
-    // <id>http://a5d4b8ec90d5144a08efb47e789d49d5-1706314482.us-west-2.elb.amazonaws.com/tickers_spy(1)</id>
-    // <category term="ODataDemo.tickers_spy" scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" />
-    // <link rel="edit" title="tickers_spy" href="tickers_spy(1)" />
-    // <title />
-    // <updated>2024-03-10T00:36:45Z</updated>
-    // <author>
-    //   <name />
-    // </author>
-
-    let row = 0;
-    let id = encode_primitive_dyn(batch.column(key_edm_index), row)?.decode()?;
-
-    let entry_url_rel = format!("{collection_name}({id})");
-    let entry_url_full = format!("{collection_base_url}({id})");
-
-    writer
-        .create_element("id")
-        .write_text_content(BytesText::from_escaped(entry_url_full))?;
-    writer
-        .create_element("category")
-        .with_attributes([
-            (
-                "scheme",
-                "http://schemas.microsoft.com/ado/2007/08/dataservices/scheme",
-            ),
-            ("term", &fq_type),
-        ])
-        .write_empty()?;
-    writer
-        .create_element("link")
-        .with_attributes([
-            ("rel", "edit"),
-            ("title", &collection_name),
-            ("href", &entry_url_rel),
-        ])
-        .write_empty()?;
-    writer.create_element("title").write_empty()?;
-    writer
-        .create_element("updated")
-        .write_text_content(encode_date_time(&updated_time))?;
-    writer.write_event(Event::Start(BytesStart::new("author")))?;
-    writer.create_element("name").write_empty()?;
-    writer.write_event(Event::End(BytesEnd::new("author")))?;
+// <d:address m:type="default.instruments_address">
+//   <d:city m:type="Edm.String">Vancouver</d:city>
+//   <d:zip m:type="Edm.String" m:null="true"/>
+// </d:address>
+//
+// <d:tags m:type="Collection(Edm.String)">
+//   <d:element m:type="Edm.String">etf</d:element>
+// </d:tags>
+fn write_property<W: Write>(
+    writer: &mut quick_xml::Writer<W>,
+    mut start: BytesStart<'_>,
+    field: &EdmField,
+    col: &ArrayRef,
+    row: usize,
+) -> Result<(), ODataError> {
+    start.push_attribute(("m:type", field.edm_type.as_str()));
+
+    let (col, row) = decode_encoded(col, row);
+    let col = &col;
+    if col.is_null(row) {
+        start.push_attribute(("m:null", "true"));
+        writer.write_event(Event::Empty(start))?;
+        return Ok(());
+    }
 
-    // <content type="application/xml">
-    //   <m:properties>
-    //     <d:offset m:type="Edm.Int64">1</d:offset>
-    //     <d:from_symbol m:type="Edm.String">spy</d:from_symbol>
-    //     <d:to_symbol m:type="Edm.String">usd</d:to_symbol>
-    //     <d:close m:type="Edm.Double">136.5622</d:close>
-    //   </m:properties>
-    // </content>
-    writer.write_event(Event::Start(
-        BytesStart::new("content").with_attributes([("type", "application/xml")]),
-    ))?;
-    writer.write_event(Event::Start(BytesStart::new("m:properties")))?;
-
-    for (edm, index) in &edms {
-        let col = batch.column(*index);
-
-        let mut start = BytesStart::new(&edm.tag);
-
-        start.push_attribute(("m:type", edm.typ.as_str()));
-        if col.is_null(row) {
-            start.push_attribute(("m:null", true.to_string().as_str()));
-            writer.write_event(Event::Empty(start))?;
-            continue;
+    let end = start.to_end().into_owned();
+    writer.write_event(Event::Start(start))?;
+    match col.as_struct_opt() {
+        Some(members) if field.is_complex() => {
+            for (member, member_col) in field.fields.iter().zip(members.columns()) {
+                let start = BytesStart::new(format!("d:{}", member.name));
+                write_property(writer, start, member, member_col, row)?;
+            }
         }
-        writer.write_event(Event::Start(start))?;
-        writer.write_event(Event::Text(encode_primitive_dyn(col, row)?))?;
-        writer.write_event(Event::End(BytesEnd::new(&edm.tag)))?;
+        _ => match &field.item {
+            Some(item) => {
+                let items = decode_list(col, row)?;
+                for i in 0..items.len() {
+                    write_property(writer, BytesStart::new("d:element"), item, &items, i)?;
+                }
+            }
+            None => match SpatialType::from_edm_type(&field.edm_type) {
+                Some(spatial_type) => {
+                    let geometry = Geometry::decode(col, row).map_err(ODataError::internal)?;
+                    write_gml(writer, &geometry, spatial_type, true)?;
+                }
+                None => writer.write_event(Event::Text(encode_primitive_dyn(col, row)?))?,
+            },
+        },
+    }
+    writer.write_event(Event::End(end))?;
+
+    Ok(())
+}
+
+// https://www.odata.org/documentation/odata-version-3-0/atom-format/#primitivetypes
+//
+// <d:location m:type="Edm.GeographyPoint">
+//   <gml:Point xmlns:gml="http://www.opengis.net/gml" gml:srsName="http://www.opengis.net/def/crs/EPSG/0/4326">
+//     <gml:pos>49.25 -123.1</gml:pos>
+//   </gml:Point>
+// </d:location>
+//
+// Positions of geography values are written as latitude followed by longitude
+fn write_gml<W: Write>(
+    writer: &mut quick_xml::Writer<W>,
+    geometry: &Geometry,
+    spatial_type: SpatialType,
+    root: bool,
+) -> Result<(), ODataError> {
+    let tag = match geometry {
+        Geometry::Point(_) => "gml:Point",
+        Geometry::LineString(_) => "gml:LineString",
+        Geometry::Polygon(_) => "gml:Polygon",
+        Geometry::MultiPoint(_) => "gml:MultiPoint",
+        Geometry::MultiLineString(_) => "gml:MultiCurve",
+        Geometry::MultiPolygon(_) => "gml:MultiSurface",
+        Geometry::GeometryCollection(_) => "gml:MultiGeometry",
+    };
+    let mut start = BytesStart::new(tag);
+    if root {
+        let srs_name = format!(
+            "http://www.opengis.net/def/crs/EPSG/0/{}",
+            spatial_type.srid()
+        );
+        start.push_attribute(("xmlns:gml", "http://www.opengis.net/gml"));
+        start.push_attribute(("gml:srsName", srs_name.as_str()));
+    }
+    if geometry == &Geometry::Point(None) {
+        writer.write_event(Event::Empty(start))?;
+        return Ok(());
     }
 
-    writer.write_event(Event::End(BytesEnd::new("m:properties")))?;
-    writer.write_event(Event::End(BytesEnd::new("content")))?;
-    writer.write_event(Event::End(BytesEnd::new("entry")))?;
+    writer.write_event(Event::Start(start))?;
+    match geometry {
+        Geometry::Point(coord) => write_gml_positions(writer, coord.as_slice(), spatial_type)?,
+        Geometry::LineString(coords) => write_gml_positions(writer, coords, spatial_type)?,
+        Geometry::Polygon(rings) => {
+            for (i, ring) in rings.iter().enumerate() {
+                let boundary = if i == 0 {
+                    "gml:exterior"
+                } else {
+                    "gml:interior"
+                };
+                writer.write_event(Event::Start(BytesStart::new(boundary)))?;
+                writer.write_event(Event::Start(BytesStart::new("gml:LinearRing")))?;
+                write_gml_positions(writer, ring, spatial_type)?;
+                writer.write_event(Event::End(BytesEnd::new("gml:LinearRing")))?;
+                writer.write_event(Event::End(BytesEnd::new(boundary)))?;
+            }
+        }
+        _ => {
+            let (members_tag, members): (_, Vec<_>) = match geometry {
+                Geometry::MultiPoint(coords) => (
+                    "gml:pointMembers",
+                    coords.iter().map(|c| Geometry::Point(Some(*c))).collect(),
+                ),
+                Geometry::MultiLineString(lines) => (
+                    "gml:curveMembers",
+                    lines.iter().cloned().map(Geometry::LineString).collect(),
+                ),
+                Geometry::MultiPolygon(polygons) => (
+                    "gml:surfaceMembers",
+                    polygons.iter().cloned().map(Geometry::Polygon).collect(),
+                ),
+                Geometry::GeometryCollection(members) => ("gml:geometryMembers", members.clone()),
+                _ => unreachable!(),
+            };
+            writer.write_event(Event::Start(BytesStart::new(members_tag)))?;
+            for member in &members {
+                write_gml(writer, member, spatial_type, false)?;
+            }
+            writer.write_event(Event::End(BytesEnd::new(members_tag)))?;
+        }
+    }
+    writer.write_event(Event::End(BytesEnd::new(tag)))?;
 
     Ok(())
 }
 
+fn write_gml_positions<W: Write>(
+    writer: &mut quick_xml::Writer<W>,
+    coords: &[Coord],
+    spatial_type: SpatialType,
+) -> Result<(), ODataError> {
+    for [x, y] in coords {
+        let pos = match spatial_type.is_geography() {
+            true => format!("{y} {x}"),
+            false => format!("{x} {y}"),
+        };
+        writer
+            .create_element("gml:pos")
+            .write_text_content(BytesText::new(&pos))?;
+    }
+    Ok(())
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
-fn encode_primitive_dyn(
+pub(crate) fn encode_primitive_dyn(
     col: &Arc<dyn Array>,
     row: usize,
-) -> Result<BytesText<'_>, UnsupportedDataType> {
+) -> Result<BytesText<'static>, UnsupportedDataType> {
+    let (col, row) = decode_encoded(col, row);
+    let col = &col;
     let col_type = col.data_type().clone();
 
     match col_type {
@@ -472,49 +556,50 @@ fn encode_primitive_dyn(
         DataType::UInt16 => Ok(encode_primitive::<UInt16Type>(col, row)),
         DataType::UInt32 => Ok(encode_primitive::<UInt32Type>(col, row)),
         DataType::UInt64 => Ok(encode_primitive::<UInt64Type>(col, row)),
-        DataType::Float16 => Ok(encode_primitive::<Float16Type>(col, row)),
-        DataType::Float32 => Ok(encode_primitive::<Float32Type>(col, row)),
-        DataType::Float64 => Ok(encode_primitive::<Float64Type>(col, row)),
-        DataType::Timestamp(unit, tz) => encode_timestamp(col, row, unit, tz),
-        DataType::Date32 => {
-            let arr = col.as_primitive::<Date32Type>();
-            let days_since_epoch = chrono::Duration::days(arr.value(row).into());
-            let epoch = chrono::DateTime::UNIX_EPOCH.date_naive();
-            let date = epoch + days_since_epoch;
-            Ok(encode_date(&date))
+        DataType::Float16 | DataType::Float32 | DataType::Float64 => {
+            Ok(BytesText::from_escaped(decode_float(col, row)?))
         }
-        DataType::Date64 => {
-            let arr = col.as_primitive::<Date64Type>();
-            let ticks = arr.value(row);
-            let ts = chrono::DateTime::from_timestamp_millis(ticks)
-                .ok_or(UnsupportedDataType::new(col_type))?;
-
-            Ok(encode_date(&ts.date_naive()))
+        DataType::Timestamp(unit, tz) => encode_timestamp(col, row, unit, tz),
+        DataType::Date32 | DataType::Date64 => Ok(encode_date(&decode_date(col, row)?)),
+        DataType::Decimal32(_, _)
+        | DataType::Decimal64(_, _)
+        | DataType::Decimal128(_, _)
+        | DataType::Decimal256(_, _) => Ok(BytesText::from_escaped(decode_decimal(col, row)?)),
+        DataType::Binary
+        | DataType::LargeBinary
+        | DataType::BinaryView
+        | DataType::FixedSizeBinary(_) => Ok(BytesText::from_escaped(
+            base64::engine::general_purpose::STANDARD.encode(decode_binary(col, row)?),
+        )),
+        // Edm.Time is represented as xsd:duration since midnight
+        DataType::Time32(_) | DataType::Time64(_) => Ok(BytesText::from_escaped(
+            encode_xsd_duration(0, 0, decode_time(col, row)?.into()),
+        )),
+        DataType::Duration(_) | DataType::Interval(_) => {
+            Ok(BytesText::from_escaped(decode_duration(col, row)?))
         }
         DataType::Null | DataType::Utf8 => {
             let arr = col.as_string::<i32>();
             let val = arr.value(row);
-            Ok(BytesText::from_escaped(quick_xml::escape::escape(val)))
+            Ok(BytesText::from_escaped(
+                quick_xml::escape::escape(val).into_owned(),
+            ))
         }
         DataType::Utf8View => {
             let arr = col.as_string_view();
             let val = arr.value(row);
-            Ok(BytesText::from_escaped(quick_xml::escape::escape(val)))
+            Ok(BytesText::from_escaped(
+                quick_xml::escape::escape(val).into_owned(),
+            ))
         }
         DataType::LargeUtf8 => {
             let arr = col.as_string::<i64>();
             let val = arr.value(row);
-            Ok(BytesText::from_escaped(quick_xml::escape::escape(val)))
-        }
-        DataType::Time32(_)
-        | DataType::Time64(_)
-        | DataType::Duration(_)
-        | DataType::Interval(_)
-        | DataType::Binary
-        | DataType::FixedSizeBinary(_)
-        | DataType::LargeBinary
-        | DataType::BinaryView
-        | DataType::List(_)
+            Ok(BytesText::from_escaped(
+                quick_xml::escape::escape(val).into_owned(),
+            ))
+        }
+        DataType::List(_)
         | DataType::FixedSizeList(_, _)
         | DataType::LargeList(_)
         | DataType::ListView(_)
@@ -522,10 +607,6 @@ fn encode_primitive_dyn(
         | DataType::Struct(_)
         | DataType::Union(_, _)
         | DataType::Dictionary(_, _)
-        | DataType::Decimal32(_, _)
-        | DataType::Decimal64(_, _)
-        | DataType::Decimal128(_, _)
-        | DataType::Decimal256(_, _)
         | DataType::Map(_, _)
         | DataType::RunEndEncoded(_, _) => Err(UnsupportedDataType::new(col_type)),
     }
@@ -533,7 +614,7 @@ fn encode_primitive_dyn(
 
 ///////////////////////////////////////////////////////////////////////////////
 
-fn encode_primitive<T>(arr: &Arc<dyn Array>, row: usize) -> BytesText<'_>
+fn encode_primitive<T>(arr: &Arc<dyn Array>, row: usize) -> BytesText<'static>
 where
     T: ArrowPrimitiveType,
     <T as ArrowPrimitiveType>::Native: std::fmt::Display,
@@ -551,6 +632,22 @@ fn encode_timestamp(
     unit: TimeUnit,
     tz: Option<Arc<str>>,
 ) -> Result<BytesText<'static>, UnsupportedDataType> {
+    let d = decode_timestamp(col, index, unit)?;
+
+    Ok(if tz.is_some() {
+        encode_date_time(&d)
+    } else {
+        encode_date_time_naive(&d.naive_utc())
+    })
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+pub(crate) fn decode_timestamp(
+    col: &Arc<dyn Array>,
+    index: usize,
+    unit: TimeUnit,
+) -> Result<DateTime<Utc>, UnsupportedDataType> {
     let dt = match unit {
         TimeUnit::Microsecond => {
             let value = cast_primitive::<TimestampMicrosecondType>(col, index)?;
@@ -570,14 +667,285 @@ fn encode_timestamp(
         }
     };
 
-    match dt {
-        Some(d) => Ok(if tz.is_some() {
-            encode_date_time(&d)
+    dt.ok_or_else(|| UnsupportedDataType::new(col.data_type().clone()))
+}
+
+pub(crate) fn decode_date(
+    col: &Arc<dyn Array>,
+    row: usize,
+) -> Result<chrono::NaiveDate, UnsupportedDataType> {
+    match col.data_type() {
+        DataType::Date32 => {
+            let arr = col.as_primitive::<Date32Type>();
+            let days_since_epoch = chrono::Duration::days(arr.value(row).into());
+            let epoch = chrono::DateTime::UNIX_EPOCH.date_naive();
+            Ok(epoch + days_since_epoch)
+        }
+        DataType::Date64 => {
+            let arr = col.as_primitive::<Date64Type>();
+            let ticks = arr.value(row);
+            let ts = chrono::DateTime::from_timestamp_millis(ticks)
+                .ok_or_else(|| UnsupportedDataType::new(col.data_type().clone()))?;
+            Ok(ts.date_naive())
+        }
+        typ => Err(UnsupportedDataType::new(typ.clone())),
+    }
+}
+
+/// Formats a decimal from its scaled integer representation, so that the value
+/// is rendered exactly, e.g. `12345` with scale `2` as `123.45`
+pub(crate) fn decode_decimal(
+    col: &Arc<dyn Array>,
+    row: usize,
+) -> Result<String, UnsupportedDataType> {
+    let (unscaled, scale) = match col.data_type() {
+        DataType::Decimal32(_, scale) => (
+            col.as_primitive::<Decimal32Type>().value(row).to_string(),
+            *scale,
+        ),
+        DataType::Decimal64(_, scale) => (
+            col.as_primitive::<Decimal64Type>().value(row).to_string(),
+            *scale,
+        ),
+        DataType::Decimal128(_, scale) => (
+            col.as_primitive::<Decimal128Type>().value(row).to_string(),
+            *scale,
+        ),
+        DataType::Decimal256(_, scale) => (
+            col.as_primitive::<Decimal256Type>().value(row).to_string(),
+            *scale,
+        ),
+        typ => return Err(UnsupportedDataType::new(typ.clone())),
+    };
+
+    let (sign, digits) = match unscaled.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", unscaled.as_str()),
+    };
+
+    // Negative scale multiplies the value by a power of ten
+    if scale <= 0 {
+        let zeros = if digits == "0" {
+            0
         } else {
-            encode_date_time_naive(&d.naive_utc())
-        }),
-        None => Err(UnsupportedDataType::new(DataType::Timestamp(unit, tz))),
+            scale.unsigned_abs() as usize
+        };
+        return Ok(format!("{sign}{digits}{}", "0".repeat(zeros)));
+    }
+
+    let scale = scale as usize;
+    let digits = format!("{digits:0>width$}", width = scale + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - scale);
+    Ok(format!("{sign}{whole}.{fraction}"))
+}
+
+/// Formats a float in the lexical form of `xsd:double` that OData also uses for
+/// special values in JSON (`NaN`, `INF`, `-INF`). Finite values are rendered with
+/// the shortest digits that read back as the same value of the column type.
+pub(crate) fn decode_float(
+    col: &Arc<dyn Array>,
+    row: usize,
+) -> Result<String, UnsupportedDataType> {
+    match col.data_type() {
+        DataType::Float16 => Ok(format_float(
+            col.as_primitive::<Float16Type>().value(row).to_f32(),
+        )),
+        DataType::Float32 => Ok(format_float(col.as_primitive::<Float32Type>().value(row))),
+        DataType::Float64 => Ok(format_float(col.as_primitive::<Float64Type>().value(row))),
+        typ => Err(UnsupportedDataType::new(typ.clone())),
+    }
+}
+
+fn format_float<T>(value: T) -> String
+where
+    T: Copy + Into<f64> + std::fmt::Display + std::fmt::LowerExp,
+{
+    let float: f64 = value.into();
+    if float.is_nan() {
+        "NaN".to_string()
+    } else if float.is_infinite() {
+        if float > 0.0 { "INF" } else { "-INF" }.to_string()
+    } else if float != 0.0 && !(1e-7..1e21).contains(&float.abs()) {
+        // Avoid spelling out hundreds of zeros of very large and small magnitudes
+        format!("{value:e}")
+    } else {
+        value.to_string()
+    }
+}
+
+pub(crate) fn decode_binary(
+    col: &Arc<dyn Array>,
+    row: usize,
+) -> Result<&[u8], UnsupportedDataType> {
+    match col.data_type() {
+        DataType::Binary => Ok(col.as_binary::<i32>().value(row)),
+        DataType::LargeBinary => Ok(col.as_binary::<i64>().value(row)),
+        DataType::BinaryView => Ok(col.as_binary_view().value(row)),
+        DataType::FixedSizeBinary(_) => Ok(col.as_fixed_size_binary().value(row)),
+        typ => Err(UnsupportedDataType::new(typ.clone())),
+    }
+}
+
+/// Resolves a value of a dictionary or run-end encoded array into the array of
+/// values and the index of the value in it. Other arrays are returned as is.
+pub(crate) fn decode_encoded(col: &ArrayRef, row: usize) -> (ArrayRef, usize) {
+    let (mut col, mut row) = (col.clone(), row);
+    loop {
+        let array = col.as_ref();
+        (col, row) = downcast_dictionary_array!(
+            array => match array.key(row) {
+                Some(key) => (array.values().clone(), key),
+                // Null key has no value
+                None => return (col, row),
+            },
+            DataType::RunEndEncoded(_, _) => downcast_run_array!(
+                array => (array.values().clone(), array.get_physical_index(row)),
+                _ => unreachable!(),
+            ),
+            _ => return (col, row),
+        );
+    }
+}
+
+/// Decodes items of a list value or entries of a map value
+pub(crate) fn decode_list(
+    col: &Arc<dyn Array>,
+    row: usize,
+) -> Result<ArrayRef, UnsupportedDataType> {
+    match col.data_type() {
+        DataType::List(_) => Ok(col.as_list::<i32>().value(row)),
+        DataType::LargeList(_) => Ok(col.as_list::<i64>().value(row)),
+        DataType::FixedSizeList(_, _) => Ok(col.as_fixed_size_list().value(row)),
+        DataType::ListView(_) => Ok(col.as_list_view::<i32>().value(row)),
+        DataType::LargeListView(_) => Ok(col.as_list_view::<i64>().value(row)),
+        DataType::Map(_, _) => Ok(Arc::new(col.as_map().value(row))),
+        typ => Err(UnsupportedDataType::new(typ.clone())),
+    }
+}
+
+/// Decodes time of day as nanoseconds since midnight
+pub(crate) fn decode_time(col: &Arc<dyn Array>, row: usize) -> Result<i64, UnsupportedDataType> {
+    match col.data_type() {
+        DataType::Time32(TimeUnit::Second) => {
+            let value = col.as_primitive::<Time32SecondType>().value(row);
+            Ok(i64::from(value) * 1_000_000_000)
+        }
+        DataType::Time32(TimeUnit::Millisecond) => {
+            let value = col.as_primitive::<Time32MillisecondType>().value(row);
+            Ok(i64::from(value) * 1_000_000)
+        }
+        DataType::Time64(TimeUnit::Microsecond) => {
+            let value = col.as_primitive::<Time64MicrosecondType>().value(row);
+            Ok(value * 1_000)
+        }
+        DataType::Time64(TimeUnit::Nanosecond) => {
+            Ok(col.as_primitive::<Time64NanosecondType>().value(row))
+        }
+        typ => Err(UnsupportedDataType::new(typ.clone())),
+    }
+}
+
+/// Formats a duration or an interval as xsd:duration, e.g. `P1DT2H30M` or `-PT0.5S`
+pub(crate) fn decode_duration(
+    col: &Arc<dyn Array>,
+    row: usize,
+) -> Result<String, UnsupportedDataType> {
+    let (months, days, nanos) = match col.data_type() {
+        DataType::Duration(unit) => {
+            let (value, nanos_per_unit) = match unit {
+                TimeUnit::Second => (
+                    col.as_primitive::<DurationSecondType>().value(row),
+                    1_000_000_000,
+                ),
+                TimeUnit::Millisecond => (
+                    col.as_primitive::<DurationMillisecondType>().value(row),
+                    1_000_000,
+                ),
+                TimeUnit::Microsecond => (
+                    col.as_primitive::<DurationMicrosecondType>().value(row),
+                    1_000,
+                ),
+                TimeUnit::Nanosecond => {
+                    (col.as_primitive::<DurationNanosecondType>().value(row), 1)
+                }
+            };
+            (0, 0, i128::from(value) * nanos_per_unit)
+        }
+        DataType::Interval(IntervalUnit::YearMonth) => {
+            let months = col.as_primitive::<IntervalYearMonthType>().value(row);
+            (i64::from(months), 0, 0)
+        }
+        DataType::Interval(IntervalUnit::DayTime) => {
+            let value = col.as_primitive::<IntervalDayTimeType>().value(row);
+            (
+                0,
+                i64::from(value.days),
+                i128::from(value.milliseconds) * 1_000_000,
+            )
+        }
+        DataType::Interval(IntervalUnit::MonthDayNano) => {
+            let value = col.as_primitive::<IntervalMonthDayNanoType>().value(row);
+            (
+                i64::from(value.months),
+                i64::from(value.days),
+                i128::from(value.nanoseconds),
+            )
+        }
+        typ => return Err(UnsupportedDataType::new(typ.clone())),
+    };
+
+    // Components of opposite signs cannot be expressed as a single duration
+    let signs = [months.signum(), days.signum(), nanos.signum() as i64];
+    if signs.contains(&1) && signs.contains(&-1) {
+        return Err(UnsupportedDataType::new(col.data_type().clone()));
+    }
+
+    Ok(encode_xsd_duration(months, days, nanos))
+}
+
+/// Formats the components of the same sign as xsd:duration
+pub(crate) fn encode_xsd_duration(months: i64, days: i64, nanos: i128) -> String {
+    const NANOS_PER_SECOND: u128 = 1_000_000_000;
+    const NANOS_PER_DAY: u128 = 86_400 * NANOS_PER_SECOND;
+
+    let negative = months < 0 || days < 0 || nanos < 0;
+    let months = months.unsigned_abs();
+    let nanos = nanos.unsigned_abs();
+    let days = u128::from(days.unsigned_abs()) + nanos / NANOS_PER_DAY;
+    let nanos = nanos % NANOS_PER_DAY;
+
+    let mut s = String::from(if negative { "-P" } else { "P" });
+    if months >= 12 {
+        s.push_str(&format!("{}Y", months / 12));
+    }
+    if !months.is_multiple_of(12) {
+        s.push_str(&format!("{}M", months % 12));
     }
+    if days != 0 {
+        s.push_str(&format!("{days}D"));
+    }
+
+    // Zero duration still needs at least one component
+    if nanos != 0 || s.ends_with('P') {
+        let seconds = nanos / NANOS_PER_SECOND;
+        let fraction = nanos % NANOS_PER_SECOND;
+        let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+        s.push('T');
+        if hours != 0 {
+            s.push_str(&format!("{hours}H"));
+        }
+        if minutes != 0 {
+            s.push_str(&format!("{minutes}M"));
+        }
+        if fraction != 0 {
+            let fraction = format!("{fraction:09}");
+            s.push_str(&format!("{seconds}.{}S", fraction.trim_end_matches('0')));
+        } else if seconds != 0 || s.ends_with('T') {
+            s.push_str(&format!("{seconds}S"));
+        }
+    }
+    s
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -619,10 +987,13 @@ mod tests {
 
     use datafusion::arrow::{
         array::{
-            Array, Date32Array, Date64Array, Int64Array, TimestampMicrosecondArray,
+            Array, Date32Array, Date64Array, Decimal32Array, Decimal128Array, Decimal256Array,
+            DictionaryArray, DurationMillisecondArray, Float32Array, Float64Array, Int16Array,
+            Int64Array, IntervalMonthDayNanoArray, IntervalYearMonthArray, RunArray,
+            Time32SecondArray, Time64NanosecondArray, TimestampMicrosecondArray,
             TimestampMillisecondArray, TimestampSecondArray,
         },
-        datatypes::{ArrowPrimitiveType, Date32Type, Date64Type},
+        datatypes::{ArrowPrimitiveType, Date32Type, Date64Type, IntervalMonthDayNano, i256},
     };
 
     #[test]
@@ -732,4 +1103,156 @@ mod tests {
         let result = encode_primitive_dyn(&values, 0).unwrap();
         assert_eq!(result, BytesText::new("1"));
     }
+
+    #[test]
+    fn test_encode_decimal() {
+        let assert_serializes_as = |arr: Arc<dyn Array>, expected: &[&'static str]| {
+            let actual: Vec<_> = (0..arr.len())
+                .map(|i| encode_primitive_dyn(&arr, i).unwrap())
+                .collect();
+            let expected: Vec<_> = expected.iter().map(|s| BytesText::new(s)).collect();
+            assert_eq!(actual, expected);
+        };
+
+        let values = Decimal128Array::from(vec![12345, -5, 0, 100])
+            .with_precision_and_scale(10, 2)
+            .unwrap();
+        assert_serializes_as(Arc::new(values), &["123.45", "-0.05", "0.00", "1.00"]);
+
+        let values = Decimal32Array::from(vec![123, -4])
+            .with_precision_and_scale(5, -2)
+            .unwrap();
+        assert_serializes_as(Arc::new(values), &["12300", "-400"]);
+
+        // Beyond the range of f64 mantissa
+        let values = Decimal128Array::from(vec![i128::MAX])
+            .with_precision_and_scale(38, 10)
+            .unwrap();
+        assert_serializes_as(
+            Arc::new(values),
+            &["17014118346046923173168730371.5884105727"],
+        );
+
+        let values = Decimal256Array::from(vec![i256::from_i128(-123456789)])
+            .with_precision_and_scale(76, 4)
+            .unwrap();
+        assert_serializes_as(Arc::new(values), &["-12345.6789"]);
+    }
+
+    #[test]
+    fn test_encode_float() {
+        let values = Arc::new(Float64Array::from(vec![
+            135.5625,
+            -0.0,
+            0.1 + 0.2,
+            1e300,
+            -2.5e-10,
+            f64::NAN,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ])) as Arc<dyn Array>;
+        let encoded: Vec<_> = (0..values.len())
+            .map(|row| {
+                encode_primitive_dyn(&values, row)
+                    .unwrap()
+                    .decode()
+                    .unwrap()
+                    .into_owned()
+            })
+            .collect();
+        assert_eq!(
+            encoded,
+            [
+                "135.5625",
+                "-0",
+                "0.30000000000000004",
+                "1e300",
+                "-2.5e-10",
+                "NaN",
+                "INF",
+                "-INF",
+            ]
+        );
+
+        // Shortest digits of the single precision value rather than of its widened form
+        let values = Arc::new(Float32Array::from(vec![0.1, f32::MAX, f32::NAN])) as Arc<dyn Array>;
+        assert_eq!(decode_float(&values, 0).unwrap(), "0.1");
+        assert_eq!(decode_float(&values, 1).unwrap(), "3.4028235e38");
+        assert_eq!(decode_float(&values, 2).unwrap(), "NaN");
+    }
+
+    #[test]
+    fn test_encode_time_and_duration() {
+        let assert_serializes_as = |arr: Arc<dyn Array>, expected: &[&'static str]| {
+            let actual: Vec<_> = (0..arr.len())
+                .map(|i| encode_primitive_dyn(&arr, i).unwrap())
+                .collect();
+            let expected: Vec<_> = expected.iter().map(|s| BytesText::new(s)).collect();
+            assert_eq!(actual, expected);
+        };
+
+        // 13:20, 00:00, 23:59:59
+        let values = Time32SecondArray::from(vec![48_000, 0, 86_399]);
+        assert_serializes_as(Arc::new(values), &["PT13H20M", "PT0S", "PT23H59M59S"]);
+
+        // 00:00:00.5, 01:00:00.000000001
+        let values = Time64NanosecondArray::from(vec![500_000_000, 3_600_000_000_001]);
+        assert_serializes_as(Arc::new(values), &["PT0.5S", "PT1H0.000000001S"]);
+
+        let values = DurationMillisecondArray::from(vec![93_784_005, -1_500, 0]);
+        assert_serializes_as(Arc::new(values), &["P1DT2H3M4.005S", "-PT1.5S", "PT0S"]);
+
+        let values = IntervalYearMonthArray::from(vec![14, -3]);
+        assert_serializes_as(Arc::new(values), &["P1Y2M", "-P3M"]);
+
+        let values = IntervalMonthDayNanoArray::from(vec![
+            IntervalMonthDayNano::new(1, 2, 3_000_000_000),
+            IntervalMonthDayNano::new(1, -2, 0),
+        ]);
+        assert_eq!(
+            encode_primitive_dyn(&(Arc::new(values.clone()) as Arc<dyn Array>), 0).unwrap(),
+            BytesText::new("P1M2DT3S")
+        );
+        assert!(encode_primitive_dyn(&(Arc::new(values) as Arc<dyn Array>), 1).is_err());
+    }
+
+    #[test]
+    fn test_encode_encoded() {
+        let values: Arc<dyn Array> = Arc::new(DictionaryArray::<Int8Type>::from_iter([
+            Some("b"),
+            None,
+            Some("a&b"),
+            Some("b"),
+        ]));
+        let actual: Vec<_> = (0..values.len())
+            .map(|i| {
+                let (col, row) = decode_encoded(&values, i);
+                (col.is_null(row), encode_primitive_dyn(&values, i).ok())
+            })
+            .collect();
+        assert_eq!(
+            actual,
+            vec![
+                (false, Some(BytesText::new("b"))),
+                (true, None),
+                (false, Some(BytesText::from_escaped("a&amp;b"))),
+                (false, Some(BytesText::new("b"))),
+            ]
+        );
+
+        let values: Arc<dyn Array> = Arc::new(
+            RunArray::try_new(
+                &Int16Array::from(vec![2, 3]),
+                &Int64Array::from(vec![Some(7), None]),
+            )
+            .unwrap(),
+        );
+        assert!(!values.is_null(2));
+        let (col, row) = decode_encoded(&values, 2);
+        assert!(col.is_null(row));
+        assert_eq!(
+            encode_primitive_dyn(&values, 1).unwrap(),
+            BytesText::new("7")
+        );
+    }
 }
@@ -1,10 +1,29 @@
-use datafusion::prelude::*;
+use base64::Engine as _;
+use datafusion::{
+    arrow::{
+        array::{Array, RecordBatch},
+        datatypes::{DataType, Schema},
+        error::ArrowError,
+        util::display::array_value_to_string,
+    },
+    common::{DFSchema, plan_err},
+    functions::core::expr_fn::{get_field, named_struct},
+    functions_aggregate::count::count_all,
+    functions_window::expr_fn::row_number,
+    logical_expr::ExprFunctionExt,
+    prelude::*,
+    scalar::ScalarValue,
+};
 
-use crate::{error::ODataError, filter::ODataFilter};
+use crate::{
+    context::EnumColumn,
+    error::ODataError,
+    filter::{ODataFilter, parse_key_value},
+};
 
 ///////////////////////////////////////////////////////////////////////////////
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Deserialize)]
 pub struct QueryParamsRaw {
     #[serde(rename = "$select")]
     pub select: Option<String>,
@@ -16,12 +35,26 @@ pub struct QueryParamsRaw {
     pub top: Option<u64>,
     #[serde(rename = "$filter")]
     pub filter: Option<ODataFilter>,
+    #[serde(rename = "$format")]
+    pub format: Option<String>,
+    #[serde(rename = "$skiptoken")]
+    pub skip_token: Option<String>,
+    #[serde(rename = "$inlinecount")]
+    pub inline_count: Option<String>,
+    #[serde(rename = "$expand")]
+    pub expand: Option<String>,
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
 impl QueryParamsRaw {
-    pub fn decode(self) -> Result<QueryParams, ODataError> {
+    /// Validates query options, resolving the filter against the collection
+    /// schema and its enum columns
+    pub fn decode(
+        self,
+        schema: &Schema,
+        enum_columns: &[EnumColumn],
+    ) -> Result<QueryParams, ODataError> {
         let select = self.select.unwrap_or_default();
         let mut select: Vec<_> = select.split(',').map(|s| s.to_string()).collect();
         select.retain(|i| !i.is_empty());
@@ -42,22 +75,85 @@ impl QueryParamsRaw {
             order_by.push((cname.to_string(), asc));
         }
 
+        let expand = self.expand.unwrap_or_default();
+        let mut expand: Vec<_> = expand.split(',').map(|s| s.trim().to_string()).collect();
+        expand.retain(|i| !i.is_empty());
+
         let skip = self.skip.map(|v| v as usize);
         let top = self.top.map(|v| v as usize);
 
+        // Token carries values of all ordering columns plus the key columns
+        let skip_token: Option<SkipToken> =
+            self.skip_token.as_deref().map(str::parse).transpose()?;
+        if let Some(skip_token) = &skip_token
+            && skip_token.values.len() <= order_by.len()
+        {
+            return Err(ODataError::bad_request("Invalid $skiptoken"));
+        }
+
+        // https://www.odata.org/documentation/odata-version-3-0/odata-version-3-0-core-protocol/#inlinecountsystemqueryoption
+        let inline_count = match self.inline_count.as_deref() {
+            None | Some("none") => false,
+            Some("allpages") => true,
+            Some(value) => {
+                return Err(ODataError::bad_request(format!(
+                    "Invalid $inlinecount value: {value}"
+                )));
+            }
+        };
+
         Ok(QueryParams {
             select,
             order_by,
             skip,
             top,
-            filter: self.filter.map(Into::into),
+            filter: self
+                .filter
+                .map(|f| f.to_expr(schema, enum_columns))
+                .transpose()?,
+            skip_token,
+            page_size: None,
+            inline_count,
+            count: false,
+            expand,
+            limit_per_value: None,
         })
     }
+
+    /// Query string of the next page link in server-driven paging. The `$skip`
+    /// option is omitted as the token already points past the skipped records.
+    pub fn next_page_query(&self, top: Option<u64>, skip_token: &SkipToken) -> String {
+        let options = [
+            ("$select", self.select.clone()),
+            (
+                "$filter",
+                self.filter.as_ref().map(|f| f.as_str().to_string()),
+            ),
+            ("$orderby", self.order_by.clone()),
+            ("$top", top.map(|t| t.to_string())),
+            ("$format", self.format.clone()),
+            ("$inlinecount", self.inline_count.clone()),
+            ("$expand", self.expand.clone()),
+            ("$skiptoken", Some(skip_token.encode())),
+        ];
+
+        options
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let value: String = form_urlencoded::byte_serialize(value?.as_bytes()).collect();
+                Some(format!("{name}={value}"))
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
-#[derive(Debug)]
+/// Synthetic column numbering records sharing a value for `limit_per_value`
+const ROW_NUMBER_COLUMN: &str = "__row_number__";
+
+#[derive(Debug, Clone, Default)]
 pub struct QueryParams {
     /// Column names
     pub select: Vec<String>,
@@ -69,35 +165,148 @@ pub struct QueryParams {
     pub top: Option<usize>,
     /// Filter a collection of resources   
     pub filter: Option<Expr>,
+    /// Continuation of server-driven paging
+    pub skip_token: Option<SkipToken>,
+    /// Maximum number of records in a page of server-driven paging. When set
+    /// one extra record is fetched to tell whether the next page exists.
+    pub page_size: Option<usize>,
+    /// Whether the feed should include the total number of matching records
+    pub inline_count: bool,
+    /// Produce a single `count` column with the number of matching records
+    /// instead of the records themselves, ignoring the ordering and paging
+    pub count: bool,
+    /// Navigation properties whose related entries are included inline
+    pub expand: Vec<String>,
+    /// Tuple (column_name, limit) bounding the number of records sharing the
+    /// same value of the column, used to bound the expanded entries related
+    /// to each source entry. Records beyond the limit are dropped first in the
+    /// specified order.
+    pub limit_per_value: Option<(String, usize)>,
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+// Members of complex properties are selected with paths like `address/city`.
+// Paths into the same column are merged into a single struct pruned to the
+// selected members, e.g. `address/city,address/geo/lat` selects
+// `{city, geo: {lat}}` as `address`.
+fn select_exprs(select: &[String], schema: &DFSchema) -> datafusion::error::Result<Vec<Expr>> {
+    let mut columns: Vec<(&str, Vec<Vec<&str>>)> = Vec::new();
+    for path in select {
+        let mut members = path.split('/');
+        let name = members.next().unwrap_or_default();
+        let members = members.collect();
+        match columns.iter_mut().find(|(c, _)| *c == name) {
+            Some((_, paths)) => paths.push(members),
+            None => columns.push((name, vec![members])),
+        }
+    }
+
+    columns
+        .into_iter()
+        .map(|(name, paths)| {
+            let field = schema.field_with_unqualified_name(name)?;
+            let expr = Expr::Column(Column::new_unqualified(name));
+            Ok(prune_struct(expr, field.data_type(), &paths, name)?.alias(name))
+        })
+        .collect()
+}
+
+fn prune_struct(
+    expr: Expr,
+    data_type: &DataType,
+    paths: &[Vec<&str>],
+    path: &str,
+) -> datafusion::error::Result<Expr> {
+    // Whole value is selected
+    if paths.iter().any(Vec::is_empty) {
+        return Ok(expr);
+    }
+
+    let DataType::Struct(fields) = data_type else {
+        return plan_err!("Property {path} has no members");
+    };
+
+    for member in paths.iter().map(|p| p[0]) {
+        if fields.find(member).is_none() {
+            return plan_err!("Property {path} has no member {member}");
+        }
+    }
+
+    let mut args = Vec::new();
+    for field in fields {
+        let name = field.name().as_str();
+        let member_paths: Vec<_> = paths
+            .iter()
+            .filter(|p| p[0] == name)
+            .map(|p| p[1..].to_vec())
+            .collect();
+        if member_paths.is_empty() {
+            continue;
+        }
+        let member = get_field(expr.clone(), name);
+        let member_path = format!("{path}/{name}");
+        args.push(lit(name));
+        args.push(prune_struct(
+            member,
+            field.data_type(),
+            &member_paths,
+            &member_path,
+        )?);
+    }
+
+    // Null value stays null instead of becoming a struct of nulls
+    when(expr.is_not_null(), named_struct(args)).end()
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
 impl QueryParams {
+    /// Applies the query to the collection. Key columns are propagated to the
+    /// results as synthetic columns named by [`key_column_aliases`].
     pub fn apply(
         self,
         df: DataFrame,
         addr: &CollectionAddr,
-        key_column: &str,
+        key_columns: &[&str],
         key_column_alias: &str,
         default_rows: usize,
         max_rows: usize,
     ) -> datafusion::error::Result<DataFrame> {
-        // Add key column as alias
-        let df = df.with_column(key_column_alias, col(key_column))?;
+        let key_aliases = key_column_aliases(key_column_alias, key_columns);
+
+        // Add key columns as aliases
+        let mut df = df;
+        for (key_column, key_alias) in key_columns.iter().zip(&key_aliases) {
+            df = df.with_column(key_alias, col(*key_column))?;
+        }
 
         // Select desired columns
-        let df = if self.select.is_empty() {
+        let df = if self.select.is_empty() || self.count {
             df
         } else {
-            let mut select: Vec<_> = self.select.iter().map(String::as_str).collect();
-            select.push(key_column_alias);
-            df.select_columns(&select)?
+            let mut select = select_exprs(&self.select, df.schema())?;
+            select.extend(key_aliases.iter().map(col));
+            df.select(select)?
         };
 
         // If queried by key - ignore the rest
         if let Some(key) = &addr.key {
-            return df.filter(col(key_column_alias).eq(lit(key.clone())));
+            let values = key
+                .values(key_columns)
+                .map_err(|e| datafusion::error::DataFusionError::External(e.into()))?;
+
+            let mut df = df;
+            for (key_alias, value) in key_aliases.iter().zip(values) {
+                let data_type = df
+                    .schema()
+                    .field_with_unqualified_name(key_alias)?
+                    .data_type();
+                let value = parse_key_value(value, data_type)
+                    .map_err(|e| datafusion::error::DataFusionError::External(e.into()))?;
+                df = df.filter(col(key_alias).eq(lit(value)))?;
+            }
+            return Ok(df);
         }
 
         let df = match self.filter {
@@ -105,12 +314,50 @@ impl QueryParams {
             None => df,
         };
 
+        if self.count {
+            return df.aggregate(vec![], vec![count_all().alias("count")]);
+        }
+
+        // Paging requires a total order, so key is used as a tie-breaker
+        let mut order_by = self.order_by;
+        if self.page_size.is_some() || self.skip_token.is_some() {
+            order_by.extend(key_aliases.into_iter().map(|c| (c, true)));
+        }
+
+        // Continue after the last record of the previous page
+        let df = match self.skip_token {
+            Some(skip_token) => {
+                let filter = skip_token.to_filter(&order_by, df.schema())?;
+                df.filter(filter)?
+            }
+            None => df,
+        };
+
+        let df = match self.limit_per_value {
+            Some((column, limit)) => {
+                let row_number = row_number()
+                    .partition_by(vec![col(column)])
+                    .order_by(
+                        order_by
+                            .iter()
+                            .map(|(c, asc)| col(c).sort(*asc, true))
+                            .collect(),
+                    )
+                    .build()?
+                    .alias(ROW_NUMBER_COLUMN);
+                df.window(vec![row_number])?
+                    .filter(col(ROW_NUMBER_COLUMN).lt_eq(lit(limit as u64)))?
+                    .drop_columns(&[ROW_NUMBER_COLUMN])?
+            }
+            None => df,
+        };
+
         // Order by
-        let df = if self.order_by.is_empty() {
+        let df = if order_by.is_empty() {
             df
         } else {
             df.sort(
-                self.order_by
+                order_by
                     .into_iter()
                     .map(|(c, asc)| col(c).sort(asc, true))
                     .collect(),
@@ -118,10 +365,120 @@ impl QueryParams {
         };
 
         // Skip / limit
-        df.limit(
-            self.skip.unwrap_or(0),
-            Some(std::cmp::min(self.top.unwrap_or(default_rows), max_rows)),
-        )
+        let limit = std::cmp::min(self.top.unwrap_or(default_rows), max_rows);
+        let limit = match self.page_size {
+            Some(page_size) if page_size < limit => page_size + 1,
+            _ => limit,
+        };
+
+        // Unbounded limit is not representable in the plan
+        let limit = (limit != usize::MAX).then_some(limit);
+
+        df.limit(self.skip.unwrap_or(0), limit)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Key-based continuation of server-driven paging: values of the ordering
+/// columns and the key column of the last record of the previous page.
+/// Values are kept in their string form and typed against the schema when
+/// the token is applied. The token is opaque to clients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkipToken {
+    pub values: Vec<Option<String>>,
+}
+
+impl SkipToken {
+    pub fn new(values: Vec<Option<String>>) -> Self {
+        Self { values }
+    }
+
+    /// Token continuing after the row, `columns` are indices of the ordering
+    /// columns followed by the key columns in the batch
+    pub fn from_row(
+        batch: &RecordBatch,
+        columns: &[usize],
+        row: usize,
+    ) -> Result<Self, ArrowError> {
+        let values = columns
+            .iter()
+            .map(|i| {
+                let col = batch.column(*i);
+                if col.is_null(row) {
+                    Ok(None)
+                } else {
+                    array_value_to_string(col, row).map(Some)
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { values })
+    }
+
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(&self.values).unwrap();
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json)
+    }
+
+    /// Builds a predicate selecting records that follow the token in the
+    /// specified order, e.g. `(a, b) > (1, 2)` becomes `a > 1 OR (a = 1 AND b > 2)`.
+    /// Nulls are sorted first in both directions.
+    fn to_filter(
+        &self,
+        order_by: &[(String, bool)],
+        schema: &datafusion::common::DFSchema,
+    ) -> datafusion::error::Result<Expr> {
+        let mut filter: Option<Expr> = None;
+
+        for ((name, asc), value) in order_by.iter().zip(&self.values).rev() {
+            let data_type = schema.field_with_unqualified_name(name)?.data_type();
+            let value = match value {
+                Some(value) => ScalarValue::try_from_string(value.clone(), data_type)?,
+                None => ScalarValue::try_from(data_type)?,
+            };
+
+            let (after, equal) = if value.is_null() {
+                (col(name).is_not_null(), col(name).is_null())
+            } else if *asc {
+                (col(name).gt(lit(value.clone())), col(name).eq(lit(value)))
+            } else {
+                (col(name).lt(lit(value.clone())), col(name).eq(lit(value)))
+            };
+
+            filter = Some(match filter {
+                None => after,
+                Some(rest) => after.or(equal.and(rest)),
+            });
+        }
+
+        filter.ok_or_else(|| datafusion::error::DataFusionError::Plan("Empty $skiptoken".into()))
+    }
+}
+
+impl std::str::FromStr for SkipToken {
+    type Err = ODataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let json = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(s)
+            .map_err(|_| ODataError::bad_request("Invalid $skiptoken"))?;
+        let values = serde_json::from_slice(&json)
+            .map_err(|_| ODataError::bad_request("Invalid $skiptoken"))?;
+        Ok(Self { values })
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Names of the synthetic columns carrying the values of the key columns.
+/// A single (or unspecified) key column is propagated as the alias itself.
+pub fn key_column_aliases(key_column_alias: &str, key_columns: &[&str]) -> Vec<String> {
+    match key_columns {
+        [] | [_] => vec![key_column_alias.to_string()],
+        _ => key_columns
+            .iter()
+            .map(|c| format!("{key_column_alias}{c}"))
+            .collect(),
     }
 }
 
@@ -130,24 +487,167 @@ impl QueryParams {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CollectionAddr {
     pub name: String,
-    pub key: Option<String>,
+    pub key: Option<KeyPredicate>,
+    /// Property of the addressed entry, i.e. `collection(key)/property`, which
+    /// is either a navigation property or a column
+    pub property: Option<String>,
+    /// Addresses the raw value of the property, i.e. `collection(key)/property/$value`
+    pub value: bool,
+    /// Addresses the number of records, i.e. `collection/$count`
+    pub count: bool,
 }
 
 impl CollectionAddr {
     pub fn decode(collection_path_element: &str) -> Option<Self> {
-        let re = regex::Regex::new(r#"^(?<name>[A-Za-z0-9._-]+)(\((?<key>[^)]+)\))?$"#).unwrap();
+        let re = regex::Regex::new(
+            r#"^(?<name>[A-Za-z0-9._-]+)(\((?<key>([^)']|'([^']|'')*')+)\))?(/(?<property>[A-Za-z0-9._-]+))?(?<value>/\$value)?(?<count>/\$count)?$"#,
+        )
+        .unwrap();
         let c = re.captures(collection_path_element)?;
 
         let name = c.name("name")?.as_str().to_string();
-        let key = c.name("key").map(|m| m.as_str().to_string());
+        let key = match c.name("key") {
+            Some(m) => Some(KeyPredicate::decode(m.as_str())?),
+            None => None,
+        };
+        let property = c.name("property").map(|m| m.as_str().to_string());
+        let value = c.name("value").is_some();
+        let count = c.name("count").is_some();
 
-        Some(Self { name, key })
+        // Properties belong to a single entry
+        if property.is_some() && key.is_none() {
+            return None;
+        }
+
+        // Raw values exist only for properties and cannot be counted
+        if value && (property.is_none() || count) {
+            return None;
+        }
+
+        // Only collections can be counted
+        if key.is_some() && property.is_none() && count {
+            return None;
+        }
+
+        Some(Self {
+            name,
+            key,
+            property,
+            value,
+            count,
+        })
+    }
+}
+
+/// Key predicate addressing a single entry, e.g. `(1)` or `(order_id=1,line=2)`.
+/// Values are kept in their literal form.
+///
+/// See: https://www.odata.org/documentation/odata-version-3-0/url-conventions/#url4.3.1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyPredicate {
+    /// Value of the only key column
+    Single(String),
+    /// Values of the key columns by their names
+    Named(Vec<(String, String)>),
+}
+
+impl KeyPredicate {
+    pub fn decode(s: &str) -> Option<Self> {
+        let parts = split_unquoted(s, ',');
+
+        if let [part] = parts.as_slice()
+            && split_unquoted(part, '=').len() == 1
+        {
+            return Some(Self::Single(part.to_string()));
+        }
+
+        let name_re = regex::Regex::new(r#"^[A-Za-z_][A-Za-z0-9_]*$"#).unwrap();
+        let mut values = Vec::new();
+        for part in parts {
+            let [name, value] = split_unquoted(part, '=')[..] else {
+                return None;
+            };
+            if !name_re.is_match(name) || value.is_empty() {
+                return None;
+            }
+            values.push((name.to_string(), value.to_string()));
+        }
+        Some(Self::Named(values))
+    }
+
+    /// Values of the key columns in their order
+    pub fn values(&self, key_columns: &[&str]) -> Result<Vec<&str>, ODataError> {
+        match self {
+            Self::Single(value) if key_columns.len() == 1 => Ok(vec![value.as_str()]),
+            Self::Single(_) => Err(ODataError::bad_request(format!(
+                "Key predicate must specify values of all key properties: {}",
+                key_columns.join(", ")
+            ))),
+            Self::Named(values) => {
+                if values.len() != key_columns.len() {
+                    return Err(ODataError::bad_request(format!(
+                        "Key predicate must specify values of all key properties: {}",
+                        key_columns.join(", ")
+                    )));
+                }
+                key_columns
+                    .iter()
+                    .map(|c| {
+                        values
+                            .iter()
+                            .find(|(name, _)| name == c)
+                            .map(|(_, value)| value.as_str())
+                            .ok_or_else(|| {
+                                ODataError::bad_request(format!(
+                                    "Key predicate is missing key property {c}"
+                                ))
+                            })
+                    })
+                    .collect()
+            }
+        }
     }
 }
 
+impl std::fmt::Display for KeyPredicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Single(value) => write!(f, "{value}"),
+            Self::Named(values) => {
+                for (i, (name, value)) in values.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{name}={value}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Splits the string by a separator that is not enclosed in single quotes
+fn split_unquoted(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if c == '\'' {
+            quoted = !quoted;
+        } else if c == sep && !quoted {
+            parts.push(&s[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::collection::CollectionAddr;
+    use datafusion::arrow::datatypes::Schema;
+
+    use crate::collection::{CollectionAddr, KeyPredicate, QueryParamsRaw, SkipToken};
 
     #[test]
     fn test_collection_addr_decode() {
@@ -156,6 +656,9 @@ mod tests {
             Some(CollectionAddr {
                 name: "coll".to_string(),
                 key: None,
+                property: None,
+                value: false,
+                count: false,
             })
         );
 
@@ -164,6 +667,9 @@ mod tests {
             Some(CollectionAddr {
                 name: "Coll123".to_string(),
                 key: None,
+                property: None,
+                value: false,
+                count: false,
             })
         );
 
@@ -172,6 +678,9 @@ mod tests {
             Some(CollectionAddr {
                 name: "Coll.x_12-3".to_string(),
                 key: None,
+                property: None,
+                value: false,
+                count: false,
             })
         );
 
@@ -179,7 +688,10 @@ mod tests {
             CollectionAddr::decode("Coll(123)"),
             Some(CollectionAddr {
                 name: "Coll".to_string(),
-                key: Some("123".to_string()),
+                key: Some(KeyPredicate::Single("123".to_string())),
+                property: None,
+                value: false,
+                count: false,
             })
         );
 
@@ -187,8 +699,132 @@ mod tests {
             CollectionAddr::decode("Coll('key')"),
             Some(CollectionAddr {
                 name: "Coll".to_string(),
-                key: Some("'key'".to_string()),
+                key: Some(KeyPredicate::Single("'key'".to_string())),
+                property: None,
+                value: false,
+                count: false,
+            })
+        );
+
+        assert_eq!(
+            CollectionAddr::decode("Coll/$count"),
+            Some(CollectionAddr {
+                name: "Coll".to_string(),
+                key: None,
+                property: None,
+                value: false,
+                count: true,
             })
         );
+
+        assert_eq!(
+            CollectionAddr::decode("Coll('key')/Nav"),
+            Some(CollectionAddr {
+                name: "Coll".to_string(),
+                key: Some(KeyPredicate::Single("'key'".to_string())),
+                property: Some("Nav".to_string()),
+                value: false,
+                count: false,
+            })
+        );
+
+        assert_eq!(
+            CollectionAddr::decode("Coll(1)/Nav/$count"),
+            Some(CollectionAddr {
+                name: "Coll".to_string(),
+                key: Some(KeyPredicate::Single("1".to_string())),
+                property: Some("Nav".to_string()),
+                value: false,
+                count: true,
+            })
+        );
+
+        assert_eq!(
+            CollectionAddr::decode("Coll(symbol='a,b=c',line=2)"),
+            Some(CollectionAddr {
+                name: "Coll".to_string(),
+                key: Some(KeyPredicate::Named(vec![
+                    ("symbol".to_string(), "'a,b=c'".to_string()),
+                    ("line".to_string(), "2".to_string()),
+                ])),
+                property: None,
+                value: false,
+                count: false,
+            })
+        );
+
+        assert_eq!(
+            CollectionAddr::decode("Coll('it''s (1)')"),
+            Some(CollectionAddr {
+                name: "Coll".to_string(),
+                key: Some(KeyPredicate::Single("'it''s (1)'".to_string())),
+                property: None,
+                value: false,
+                count: false,
+            })
+        );
+
+        assert_eq!(
+            CollectionAddr::decode("Coll(1)/Prop/$value"),
+            Some(CollectionAddr {
+                name: "Coll".to_string(),
+                key: Some(KeyPredicate::Single("1".to_string())),
+                property: Some("Prop".to_string()),
+                value: true,
+                count: false,
+            })
+        );
+
+        assert_eq!(CollectionAddr::decode("Coll(1)/$value"), None);
+        assert_eq!(CollectionAddr::decode("Coll(1)/Prop/$value/$count"), None);
+        assert_eq!(CollectionAddr::decode("Coll(1)/$count"), None);
+        assert_eq!(CollectionAddr::decode("Coll(1,2)"), None);
+        assert_eq!(CollectionAddr::decode("Coll(a=1,2)"), None);
+        assert_eq!(CollectionAddr::decode("Coll(a=)"), None);
+        assert_eq!(CollectionAddr::decode("Coll/$value"), None);
+        assert_eq!(CollectionAddr::decode("Coll/Nav"), None);
+        assert_eq!(CollectionAddr::decode("Coll(1)/Nav/Nav"), None);
+    }
+
+    #[test]
+    fn test_key_predicate_values() {
+        let key = KeyPredicate::decode("line=2,order_id=10").unwrap();
+        assert_eq!(key.to_string(), "line=2,order_id=10");
+        assert_eq!(key.values(&["order_id", "line"]).unwrap(), vec!["10", "2"]);
+        assert!(key.values(&["order_id"]).is_err());
+        assert!(key.values(&["order_id", "item"]).is_err());
+
+        let key = KeyPredicate::decode("10").unwrap();
+        assert_eq!(key.values(&["offset"]).unwrap(), vec!["10"]);
+        assert!(key.values(&["order_id", "line"]).is_err());
+    }
+
+    #[test]
+    fn test_skip_token() {
+        let token = SkipToken::new(vec![Some("it's".to_string()), None, Some("1".to_string())]);
+        assert_eq!(token.encode().parse::<SkipToken>().unwrap(), token);
+        assert!("not a token".parse::<SkipToken>().is_err());
+
+        let raw = QueryParamsRaw {
+            order_by: Some("a asc,b desc".to_string()),
+            skip: Some(10),
+            top: Some(20),
+            ..Default::default()
+        };
+        assert_eq!(
+            raw.next_page_query(Some(5), &token),
+            format!(
+                "$orderby=a+asc%2Cb+desc&$top=5&$skiptoken={}",
+                token.encode()
+            )
+        );
+
+        // Token must carry values of the key columns besides the ordering ones
+        let raw = QueryParamsRaw {
+            order_by: Some("a asc,b desc,c asc".to_string()),
+            skip_token: Some(token.encode()),
+            ..Default::default()
+        };
+        assert!(raw.decode(&Schema::empty(), &[]).is_err());
     }
 }
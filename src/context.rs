@@ -8,7 +8,9 @@ use datafusion::{
 
 use crate::{
     collection::{CollectionAddr, QueryParams},
-    error::{KeyColumnNotAssigned, ODataError},
+    encoder::{ResponseEncoderFactory, default_response_encoders},
+    error::{CollectionNotFound, KeyColumnNotAssigned, ODataError},
+    geo::SpatialType,
 };
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -23,6 +25,12 @@ pub trait ServiceContext: Send + Sync {
 
     async fn list_collections(&self) -> Result<Vec<Arc<dyn CollectionContext>>, ODataError>;
 
+    /// Relationships between the collections published as navigation
+    /// properties in `$metadata`
+    fn relationships(&self) -> Vec<Relationship> {
+        Vec::new()
+    }
+
     fn on_unsupported_feature(&self) -> OnUnsupported;
 }
 
@@ -51,6 +59,11 @@ pub trait CollectionContext: Send + Sync {
         Err(KeyColumnNotAssigned)?
     }
 
+    /// Columns forming the entity key, override for composite keys
+    fn key_columns(&self) -> Result<Vec<String>, ODataError> {
+        Ok(vec![self.key_column()?])
+    }
+
     async fn last_updated_time(&self) -> DateTime<Utc>;
 
     async fn schema(&self) -> Result<SchemaRef, ODataError>;
@@ -63,13 +76,118 @@ pub trait CollectionContext: Send + Sync {
     async fn validate(&self, _record_batches: &[RecordBatch]) -> Result<(), ODataError> {
         Ok(())
     }
+
+    /// Maximum number of entries returned in one feed response. When more
+    /// entries are available the feed ends with a next link carrying a
+    /// `$skiptoken` to continue from. Paging is disabled by default.
+    fn max_page_size(&self) -> Option<usize> {
+        None
+    }
+
+    /// Relationships originating from this collection, must be consistent
+    /// with [`ServiceContext::relationships`]
+    fn relationships(&self) -> Vec<Relationship> {
+        Vec::new()
+    }
+
+    /// Context of another collection of the service, used to resolve the
+    /// targets of navigation properties
+    async fn related_collection(
+        &self,
+        collection_name: &str,
+    ) -> Result<Arc<dyn CollectionContext>, ODataError> {
+        Err(CollectionNotFound::new(collection_name))?
+    }
+
+    /// String columns published as enum types instead of `Edm.String`, e.g.
+    /// low-cardinality dictionary-encoded status codes or categories. Values
+    /// of the columns are the names of the declared members.
+    fn enum_columns(&self) -> Vec<EnumColumn> {
+        Vec::new()
+    }
+
+    /// Binary columns holding WKB or string columns holding WKT values that
+    /// are published as geography or geometry properties, encoded as GML in
+    /// Atom and as GeoJSON in JSON payloads
+    fn spatial_columns(&self) -> Vec<SpatialColumn> {
+        Vec::new()
+    }
+
+    /// Encoders available for the collection resources. The first one is used
+    /// when the client does not express a preference. Override to register
+    /// custom formats, e.g. CSV or Arrow IPC.
+    fn response_encoders(&self) -> Vec<Arc<dyn ResponseEncoderFactory>> {
+        default_response_encoders()
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OnUnsupported {
     /// Return an error or crash
     Error,
     /// Log error and recover as gracefully as possible
     Warn,
 }
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Directed relationship between two collections, where entries of the target
+/// collection are related to a source entry when their `to_column` equals the
+/// `from_column` of the source entry (e.g. a foreign key)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relationship {
+    /// Name of the navigation property of the source entity type
+    pub name: String,
+    pub from_collection: String,
+    pub from_column: String,
+    pub to_collection: String,
+    pub to_column: String,
+    /// Number of target entries related to a single source entry
+    pub multiplicity: Multiplicity,
+}
+
+impl Relationship {
+    pub fn to_many(&self) -> bool {
+        self.multiplicity == Multiplicity::Many
+    }
+}
+
+/// Column published as an enum type, see [`CollectionContext::enum_columns`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumColumn {
+    pub column: String,
+    /// Name of the enum type in the collection namespace, e.g. `Status`
+    pub type_name: String,
+    /// Names of the members in the order of their values, which must be CSDL
+    /// simple identifiers, e.g. `Active`
+    pub members: Vec<String>,
+}
+
+/// Column published as a spatial type, see [`CollectionContext::spatial_columns`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpatialColumn {
+    pub column: String,
+    pub spatial_type: SpatialType,
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// See: https://www.odata.org/documentation/odata-version-3-0/common-schema-definition-language-csdl/#csdl7.2.3
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multiplicity {
+    ZeroOrOne,
+    One,
+    Many,
+}
+
+impl Multiplicity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ZeroOrOne => "0..1",
+            Self::One => "1",
+            Self::Many => "*",
+        }
+    }
+}
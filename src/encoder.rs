@@ -0,0 +1,658 @@
+use std::{io::Write, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use datafusion::arrow::{
+    array::{ArrayRef, RecordBatch},
+    datatypes::{DataType, Field, Schema},
+};
+
+use crate::{
+    atom::{AtomEncoder, decode_binary, decode_encoded, encode_primitive_dyn},
+    collection::key_column_aliases,
+    context::{CollectionContext, EnumColumn, OnUnsupported, Relationship, SpatialColumn},
+    error::{ODataError, UnsupportedDataType, UnsupportedNetProtocol},
+    json::{JsonEncoder, JsonFormat, JsonMetadata},
+    metadata::{
+        collection_item, complex_type_name, to_edm_type, to_enum_property, to_spatial_property,
+    },
+    navigation::RelatedEntries,
+    negotiation::{MediaRange, ResponseFormat},
+};
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Writes feeds and entries of a collection in a specific format.
+///
+/// Encoders are stateful and are created per response. A feed is written by
+/// calling [`ResponseEncoder::start_feed`], then [`ResponseEncoder::start_entry`],
+/// [`ResponseEncoder::property`] and [`ResponseEncoder::end_entry`] for every
+/// row, and finally [`ResponseEncoder::end_feed`]. A single entry response is
+/// written the same way but without the enclosing feed calls.
+///
+/// Navigation properties of an entry are written after
+/// [`ResponseEncoder::start_entry`] and before its properties. Expanded ones
+/// enclose a nested feed or entry of the related collection. Entries of
+/// records with null key values are not addressable and have no navigation
+/// properties.
+///
+/// The writer is passed into every call so that the output can be flushed by
+/// the caller at any point between the calls.
+pub trait ResponseEncoder: Send {
+    /// Value of the `Content-Type` response header
+    fn media_type(&self) -> &str;
+
+    /// Additional response headers, e.g. protocol version
+    fn headers(&self) -> Vec<(&'static str, &'static str)> {
+        Vec::new()
+    }
+
+    /// Starts a feed, `count` is the total number of records matching the
+    /// query when it was requested via `$inlinecount`
+    fn start_feed(
+        &mut self,
+        writer: &mut dyn Write,
+        info: &CollectionInfo,
+        count: Option<u64>,
+    ) -> Result<(), ODataError>;
+
+    /// Starts an entry identified by the `key` predicate, e.g. `1` in `coll(1)`,
+    /// which is absent when the record has null key values
+    fn start_entry(
+        &mut self,
+        writer: &mut dyn Write,
+        info: &CollectionInfo,
+        key: Option<&str>,
+    ) -> Result<(), ODataError>;
+
+    /// Writes value of the property at the specified row (which may be null)
+    fn property(
+        &mut self,
+        writer: &mut dyn Write,
+        field: &EdmField,
+        col: &ArrayRef,
+        row: usize,
+    ) -> Result<(), ODataError>;
+
+    /// Starts a navigation property of the entry identified by `key`. When
+    /// `expanded` the related entries follow as a nested feed, or as a single
+    /// entry (if any) for to-one relationships, otherwise a link is written.
+    fn start_navigation(
+        &mut self,
+        _writer: &mut dyn Write,
+        _info: &CollectionInfo,
+        _key: &str,
+        _relationship: &Relationship,
+        _expanded: bool,
+    ) -> Result<(), ODataError> {
+        Ok(())
+    }
+
+    fn end_navigation(&mut self, _writer: &mut dyn Write) -> Result<(), ODataError> {
+        Ok(())
+    }
+
+    fn end_entry(&mut self, writer: &mut dyn Write) -> Result<(), ODataError>;
+
+    /// Ends the feed, `next_link` is set when server-driven paging truncated it
+    fn end_feed(
+        &mut self,
+        writer: &mut dyn Write,
+        next_link: Option<&str>,
+    ) -> Result<(), ODataError>;
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Creates encoders for the media ranges requested by the client. Custom
+/// factories can be registered via [`CollectionContext::response_encoders`].
+pub trait ResponseEncoderFactory: Send + Sync {
+    /// Returns a new encoder if this factory can satisfy the media range
+    fn resolve(&self, range: &MediaRange) -> Option<Box<dyn ResponseEncoder>>;
+}
+
+impl ResponseEncoderFactory for ResponseFormat {
+    fn resolve(&self, range: &MediaRange) -> Option<Box<dyn ResponseEncoder>> {
+        match ResponseFormat::resolve(self, range)? {
+            ResponseFormat::Atom => Some(Box::new(AtomEncoder::new())),
+            ResponseFormat::Json(format) => Some(Box::new(JsonEncoder::new(format))),
+            ResponseFormat::Xml => None,
+        }
+    }
+}
+
+/// Encoders supported by the collection resources out of the box, with Atom
+/// being the default
+pub fn default_response_encoders() -> Vec<Arc<dyn ResponseEncoderFactory>> {
+    vec![
+        Arc::new(ResponseFormat::Atom),
+        Arc::new(ResponseFormat::Json(JsonFormat::V4(JsonMetadata::Minimal))),
+        Arc::new(ResponseFormat::Json(JsonFormat::Verbose)),
+    ]
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Collection-level information needed by the encoders
+#[derive(Debug, Clone)]
+pub struct CollectionInfo {
+    /// Service URL with a trailing slash
+    pub service_base_url: String,
+    /// Collection URL without a trailing slash
+    pub collection_base_url: String,
+    pub collection_name: String,
+    pub type_namespace: String,
+    pub type_name: String,
+    pub key_column_alias: String,
+    /// Names of the key columns, empty when the context does not specify them
+    /// and the collection is keyed by a single column
+    pub key_columns: Vec<String>,
+    pub updated_time: DateTime<Utc>,
+    pub on_unsupported: OnUnsupported,
+    pub enum_columns: Vec<EnumColumn>,
+    pub spatial_columns: Vec<SpatialColumn>,
+}
+
+impl CollectionInfo {
+    pub async fn new(ctx: &dyn CollectionContext) -> Result<Self, ODataError> {
+        let mut service_base_url = ctx.service_base_url()?;
+        let mut collection_base_url = ctx.collection_base_url()?;
+
+        if !service_base_url.starts_with("http") {
+            return Err(UnsupportedNetProtocol::new(service_base_url).into());
+        }
+        if !collection_base_url.starts_with("http") {
+            return Err(UnsupportedNetProtocol::new(collection_base_url).into());
+        }
+
+        if !service_base_url.ends_with('/') {
+            service_base_url.push('/');
+        }
+        if collection_base_url.ends_with('/') {
+            collection_base_url.pop();
+        }
+
+        Ok(Self {
+            service_base_url,
+            collection_base_url,
+            collection_name: ctx.collection_name()?,
+            type_namespace: ctx.collection_namespace()?,
+            type_name: ctx.collection_name()?,
+            key_column_alias: ctx.key_column_alias(),
+            key_columns: match ctx.key_columns() {
+                Ok(key_columns) => key_columns,
+                Err(ODataError::KeyColumnNotAssigned(_)) => Vec::new(),
+                Err(err) => return Err(err),
+            },
+            updated_time: ctx.last_updated_time().await,
+            on_unsupported: ctx.on_unsupported_feature(),
+            enum_columns: ctx.enum_columns(),
+            spatial_columns: ctx.spatial_columns(),
+        })
+    }
+
+    /// Fully qualified name of the entity type
+    pub fn fq_type(&self) -> String {
+        format!("{}.{}", self.type_namespace, self.type_name)
+    }
+
+    /// EDM type of a column of the collection taking enum and spatial columns
+    /// declared by the context into account
+    pub fn edm_field(&self, field: &Field) -> Result<EdmField, UnsupportedDataType> {
+        let name = field.name();
+        if let Some(enum_column) = self.enum_columns.iter().find(|e| e.column == *name) {
+            to_enum_property(field, &self.type_namespace, &enum_column.type_name)
+                .map(|property| EdmField::primitive(name, property.typ))
+        } else if let Some(spatial) = self.spatial_columns.iter().find(|s| s.column == *name) {
+            to_spatial_property(field, spatial.spatial_type)
+                .map(|property| EdmField::primitive(name, property.typ))
+        } else {
+            EdmField::new(field, &self.type_name, &self.type_namespace)
+        }
+    }
+
+    /// Names of the synthetic columns carrying the key values, see [`key_column_aliases`]
+    pub fn key_column_aliases(&self) -> Vec<String> {
+        let key_columns: Vec<_> = self.key_columns.iter().map(String::as_str).collect();
+        key_column_aliases(&self.key_column_alias, &key_columns)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Column of a record batch along with its EDM type
+#[derive(Debug, Clone)]
+pub struct EdmField {
+    pub name: String,
+    pub edm_type: String,
+    /// Members of a complex type in the order of the struct fields, empty for
+    /// primitive types
+    pub fields: Vec<EdmField>,
+    /// Items of a collection type, named `element`
+    pub item: Option<Box<EdmField>>,
+}
+
+impl EdmField {
+    pub fn primitive(name: impl Into<String>, edm_type: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            edm_type: edm_type.into(),
+            fields: Vec::new(),
+            item: None,
+        }
+    }
+
+    /// Resolves the EDM type of the field, naming complex types of structs
+    /// after the parent type the same way as in `$metadata`
+    pub fn new(
+        field: &Field,
+        parent_type: &str,
+        namespace: &str,
+    ) -> Result<Self, UnsupportedDataType> {
+        if let Some(item) = collection_item(field.data_type()) {
+            if collection_item(item.data_type()).is_some() {
+                return Err(UnsupportedDataType::new(field.data_type().clone()));
+            }
+            let mut item = Self::new(
+                &item.as_ref().clone().with_name(field.name()),
+                parent_type,
+                namespace,
+            )?;
+            item.name = "element".to_string();
+            return Ok(Self {
+                name: field.name().clone(),
+                edm_type: format!("Collection({})", item.edm_type),
+                fields: Vec::new(),
+                item: Some(Box::new(item)),
+            });
+        }
+
+        let DataType::Struct(fields) = field.data_type() else {
+            return Ok(Self::primitive(
+                field.name(),
+                to_edm_type(field.data_type())?,
+            ));
+        };
+
+        let type_name = complex_type_name(parent_type, field.name());
+        Ok(Self {
+            name: field.name().clone(),
+            edm_type: format!("{namespace}.{type_name}"),
+            fields: fields
+                .iter()
+                .map(|f| Self::new(f, &type_name, namespace))
+                .collect::<Result<_, _>>()?,
+            item: None,
+        })
+    }
+
+    pub fn is_complex(&self) -> bool {
+        !self.fields.is_empty()
+    }
+}
+
+/// EDM fields along with the indices of their columns in a record batch
+pub type EdmFields = Vec<(EdmField, usize)>;
+
+/// Resolves EDM types of the schema columns skipping the synthetic key columns.
+/// Returns the fields with their column indices and the indices of the key
+/// columns in the order of the aliases.
+pub fn to_edm_fields(
+    schema: &Schema,
+    info: &CollectionInfo,
+) -> Result<(EdmFields, Vec<usize>), UnsupportedDataType> {
+    let key_column_aliases = info.key_column_aliases();
+    let mut fields = Vec::new();
+    let mut key_indices = vec![usize::MAX; key_column_aliases.len()];
+
+    for (index, field) in schema.fields().iter().enumerate() {
+        if let Some(i) = key_column_aliases.iter().position(|a| a == field.name()) {
+            key_indices[i] = index;
+            continue;
+        }
+        let edm_field = match info.edm_field(field) {
+            Ok(edm_field) => edm_field,
+            Err(err) => match info.on_unsupported {
+                OnUnsupported::Error => return Err(err),
+                OnUnsupported::Warn => {
+                    tracing::warn!(
+                        field = field.name(),
+                        error = %err,
+                        error_dbg = ?err,
+                        "Unsupported field type - skipping",
+                    );
+                    continue;
+                }
+            },
+        };
+
+        fields.push((edm_field, index));
+    }
+    Ok((fields, key_indices))
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Describes how records of a collection map onto entries: EDM types of the
+/// columns and navigation properties
+#[derive(Debug, Clone)]
+pub struct EntryLayout {
+    info: CollectionInfo,
+    fields: EdmFields,
+    key_indices: Vec<usize>,
+    navigation: Vec<Relationship>,
+}
+
+impl EntryLayout {
+    pub fn new(info: CollectionInfo, schema: &Schema) -> Result<Self, ODataError> {
+        let (fields, key_indices) = to_edm_fields(schema, &info)?;
+
+        Ok(Self {
+            info,
+            fields,
+            key_indices,
+            navigation: Vec::new(),
+        })
+    }
+
+    /// Sets navigation properties of the entries. Hidden columns are present
+    /// in the record batches only to resolve the related entries and are not
+    /// written as properties.
+    pub fn with_navigation(
+        mut self,
+        navigation: Vec<Relationship>,
+        hidden_columns: &[String],
+    ) -> Self {
+        self.navigation = navigation;
+        self.fields
+            .retain(|(field, _)| !hidden_columns.contains(&field.name));
+        self
+    }
+
+    pub fn info(&self) -> &CollectionInfo {
+        &self.info
+    }
+
+    /// Key predicate of the record, e.g. `1` or `order_id=1,line=2`, or `None`
+    /// when any of the key values is null
+    fn key(&self, batch: &RecordBatch, row: usize) -> Result<Option<String>, ODataError> {
+        if let [index] = self.key_indices[..] {
+            return key_literal(batch.column(index), row);
+        }
+
+        let mut values = Vec::with_capacity(self.key_indices.len());
+        for (index, name) in self.key_indices.iter().zip(&self.info.key_columns) {
+            let Some(value) = key_literal(batch.column(*index), row)? else {
+                return Ok(None);
+            };
+            values.push(format!("{name}={value}"));
+        }
+        Ok(Some(values.join(",")))
+    }
+
+    /// Writes a record as an entry, `expanded` holds the related entries of
+    /// the expanded navigation properties resolved for the same batch
+    pub fn write_row(
+        &self,
+        encoder: &mut dyn ResponseEncoder,
+        writer: &mut dyn Write,
+        batch: &RecordBatch,
+        row: usize,
+        expanded: &[RelatedEntries],
+    ) -> Result<(), ODataError> {
+        let key = self.key(batch, row)?;
+
+        encoder.start_entry(writer, &self.info, key.as_deref())?;
+        // Related entries cannot be addressed without the key
+        if let Some(key) = &key {
+            for relationship in &self.navigation {
+                let related = expanded
+                    .iter()
+                    .find(|r| r.relationship().name == relationship.name);
+
+                encoder.start_navigation(
+                    writer,
+                    &self.info,
+                    key,
+                    relationship,
+                    related.is_some(),
+                )?;
+                if let Some(related) = related {
+                    let url = format!(
+                        "{}({key})/{}",
+                        self.info.collection_base_url, relationship.name
+                    );
+                    related.write(encoder, writer, batch, row, &url)?;
+                }
+                encoder.end_navigation(writer)?;
+            }
+        }
+        for (field, index) in &self.fields {
+            encoder.property(writer, field, batch.column(*index), row)?;
+        }
+        encoder.end_entry(writer)
+    }
+}
+
+/// Formats a key value as a literal, e.g. `1`, `1.5M`, `'abc'` or
+/// `datetime'2024-01-01T00:00'`, or `None` for nulls
+fn key_literal(col: &ArrayRef, row: usize) -> Result<Option<String>, ODataError> {
+    let (col, row) = decode_encoded(col, row);
+    let col = &col;
+
+    // Value in the slot of a null is arbitrary
+    if col.is_null(row) {
+        return Ok(None);
+    }
+
+    // Binary literals are hex-encoded unlike the base64 payload values
+    if let Ok(bytes) = decode_binary(col, row) {
+        let hex: String = bytes.iter().map(|b| format!("{b:02X}")).collect();
+        return Ok(Some(format!("binary'{hex}'")));
+    }
+
+    let value = encode_primitive_dyn(col, row)?.decode()?;
+
+    Ok(Some(match to_edm_type(col.data_type())? {
+        "Edm.String" => format!("'{}'", value.replace('\'', "''")),
+        "Edm.DateTime" => format!("datetime'{value}'"),
+        "Edm.DateTimeOffset" => format!("datetimeoffset'{value}'"),
+        "Edm.Decimal" => format!("{value}M"),
+        "Edm.Time" => format!("time'{value}'"),
+        _ => value.into_owned(),
+    }))
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Encodes a feed incrementally one record batch at a time, so that the
+/// response can be streamed without materializing all records in memory
+pub struct FeedWriter {
+    encoder: Box<dyn ResponseEncoder>,
+    layout: EntryLayout,
+}
+
+impl FeedWriter {
+    pub fn new(
+        encoder: Box<dyn ResponseEncoder>,
+        info: CollectionInfo,
+        schema: &Schema,
+    ) -> Result<Self, ODataError> {
+        Ok(Self::with_layout(encoder, EntryLayout::new(info, schema)?))
+    }
+
+    pub fn with_layout(encoder: Box<dyn ResponseEncoder>, layout: EntryLayout) -> Self {
+        Self { encoder, layout }
+    }
+
+    pub fn encoder(&self) -> &dyn ResponseEncoder {
+        self.encoder.as_ref()
+    }
+
+    pub fn info(&self) -> &CollectionInfo {
+        self.layout.info()
+    }
+
+    pub fn start(&mut self, writer: &mut dyn Write, count: Option<u64>) -> Result<(), ODataError> {
+        self.encoder.start_feed(writer, &self.layout.info, count)
+    }
+
+    pub fn write_batch(
+        &mut self,
+        writer: &mut dyn Write,
+        batch: &RecordBatch,
+    ) -> Result<(), ODataError> {
+        self.write_expanded_batch(writer, batch, &[])
+    }
+
+    /// Writes a batch along with the related entries of the expanded
+    /// navigation properties resolved for it
+    pub fn write_expanded_batch(
+        &mut self,
+        writer: &mut dyn Write,
+        batch: &RecordBatch,
+        expanded: &[RelatedEntries],
+    ) -> Result<(), ODataError> {
+        for row in 0..batch.num_rows() {
+            self.layout
+                .write_row(self.encoder.as_mut(), writer, batch, row, expanded)?;
+        }
+        Ok(())
+    }
+
+    pub fn end(
+        &mut self,
+        writer: &mut dyn Write,
+        next_link: Option<&str>,
+    ) -> Result<(), ODataError> {
+        self.encoder.end_feed(writer, next_link)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::{
+        array::{Float64Array, Int64Array, StringArray},
+        datatypes::{DataType, Field},
+    };
+
+    use super::*;
+    use crate::negotiation::negotiate_encoder;
+
+    struct CsvFactory;
+
+    impl ResponseEncoderFactory for CsvFactory {
+        fn resolve(&self, range: &MediaRange) -> Option<Box<dyn ResponseEncoder>> {
+            range
+                .matches("text", "csv")
+                .then(|| Box::new(CsvEncoder) as Box<dyn ResponseEncoder>)
+        }
+    }
+
+    struct CsvEncoder;
+
+    impl ResponseEncoder for CsvEncoder {
+        fn media_type(&self) -> &str {
+            "text/csv"
+        }
+
+        fn start_feed(
+            &mut self,
+            _: &mut dyn Write,
+            _: &CollectionInfo,
+            _: Option<u64>,
+        ) -> Result<(), ODataError> {
+            Ok(())
+        }
+
+        fn start_entry(
+            &mut self,
+            writer: &mut dyn Write,
+            _: &CollectionInfo,
+            key: Option<&str>,
+        ) -> Result<(), ODataError> {
+            write!(writer, "{}", key.unwrap_or_default())?;
+            Ok(())
+        }
+
+        fn property(
+            &mut self,
+            writer: &mut dyn Write,
+            _: &EdmField,
+            col: &ArrayRef,
+            row: usize,
+        ) -> Result<(), ODataError> {
+            let value = if col.is_null(row) {
+                String::new()
+            } else {
+                encode_primitive_dyn(col, row)?.decode()?.into_owned()
+            };
+            write!(writer, ",{value}")?;
+            Ok(())
+        }
+
+        fn end_entry(&mut self, writer: &mut dyn Write) -> Result<(), ODataError> {
+            writeln!(writer)?;
+            Ok(())
+        }
+
+        fn end_feed(&mut self, _: &mut dyn Write, _: Option<&str>) -> Result<(), ODataError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_custom_encoder() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("__id__", DataType::Int64, false),
+            Field::new("symbol", DataType::Utf8, false),
+            Field::new("close", DataType::Float64, true),
+        ]));
+
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![0, 1])),
+                Arc::new(StringArray::from(vec!["spy", "spy"])),
+                Arc::new(Float64Array::from(vec![Some(135.5), None])),
+            ],
+        )
+        .unwrap();
+
+        let info = CollectionInfo {
+            service_base_url: "http://example.com/odata/".to_string(),
+            collection_base_url: "http://example.com/odata/tickers".to_string(),
+            collection_name: "tickers".to_string(),
+            type_namespace: "default".to_string(),
+            type_name: "tickers".to_string(),
+            key_column_alias: "__id__".to_string(),
+            key_columns: Vec::new(),
+            updated_time: DateTime::UNIX_EPOCH,
+            on_unsupported: OnUnsupported::Error,
+            enum_columns: Vec::new(),
+            spatial_columns: Vec::new(),
+        };
+
+        let mut factories = default_response_encoders();
+        factories.push(Arc::new(CsvFactory));
+
+        let mut headers = axum::http::HeaderMap::new();
+        headers.insert(http::header::ACCEPT, "text/csv".parse().unwrap());
+
+        let encoder = negotiate_encoder(None, &headers, &factories).unwrap();
+        assert_eq!(encoder.media_type(), "text/csv");
+
+        let mut feed = FeedWriter::new(encoder, info, &schema).unwrap();
+        let mut buf = Vec::new();
+        feed.start(&mut buf, None).unwrap();
+        feed.write_batch(&mut buf, &batch).unwrap();
+        feed.end(&mut buf, None).unwrap();
+
+        assert_eq!(String::from_utf8(buf).unwrap(), "0,spy,135.5\n1,spy,\n");
+
+        // Without a preference the first registered factory is used
+        let encoder = negotiate_encoder(None, &axum::http::HeaderMap::new(), &factories).unwrap();
+        assert_eq!(encoder.media_type(), crate::handlers::MEDIA_TYPE_ATOM);
+    }
+}
@@ -18,10 +18,14 @@ pub enum ODataError {
     #[error(transparent)]
     CollectionNotFound(#[from] CollectionNotFound),
     #[error(transparent)]
+    PropertyNotFound(#[from] PropertyNotFound),
+    #[error(transparent)]
     CollectionAddressNotAssigned(#[from] CollectionAddressNotAssigned),
     #[error(transparent)]
     KeyColumnNotAssigned(#[from] KeyColumnNotAssigned),
     #[error(transparent)]
+    NotAcceptable(#[from] NotAcceptable),
+    #[error(transparent)]
     Internal(InternalError),
 }
 
@@ -57,11 +61,13 @@ impl axum::response::IntoResponse for ODataError {
             }
             Self::BadRequest(e) => e.into_response(),
             Self::CollectionNotFound(e) => e.into_response(),
+            Self::PropertyNotFound(e) => e.into_response(),
             Self::UnsupportedDataType(e) => e.into_response(),
             Self::UnsupportedFeature(e) => e.into_response(),
             Self::CollectionAddressNotAssigned(e) => e.into_response(),
             Self::KeyColumnNotAssigned(e) => e.into_response(),
             Self::UnsupportedNetProtocol(e) => e.into_response(),
+            Self::NotAcceptable(e) => e.into_response(),
         }
     }
 }
@@ -130,6 +136,30 @@ impl axum::response::IntoResponse for CollectionNotFound {
 
 ///////////////////////////////////////////////////////////////////////////////
 
+#[derive(thiserror::Error, Debug)]
+#[error("Property {property} not found in collection {collection}")]
+pub struct PropertyNotFound {
+    pub collection: String,
+    pub property: String,
+}
+
+impl PropertyNotFound {
+    pub fn new(collection: impl Into<String>, property: impl Into<String>) -> Self {
+        Self {
+            collection: collection.into(),
+            property: property.into(),
+        }
+    }
+}
+
+impl axum::response::IntoResponse for PropertyNotFound {
+    fn into_response(self) -> axum::response::Response {
+        (http::StatusCode::NOT_FOUND, self.to_string()).into_response()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
 #[derive(thiserror::Error, Debug)]
 #[error("Key column not assigned")]
 pub struct KeyColumnNotAssigned;
@@ -174,6 +204,22 @@ impl axum::response::IntoResponse for UnsupportedDataType {
 
 ///////////////////////////////////////////////////////////////////////////////
 
+#[derive(thiserror::Error, Debug)]
+#[error("Invalid geometry: {reason}")]
+pub struct InvalidGeometry {
+    pub reason: String,
+}
+
+impl InvalidGeometry {
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
 #[derive(thiserror::Error, Debug)]
 #[error("Unsupported net protocol: {url}")]
 pub struct UnsupportedNetProtocol {
@@ -216,6 +262,28 @@ impl axum::response::IntoResponse for UnsupportedFeature {
 
 ///////////////////////////////////////////////////////////////////////////////
 
+#[derive(thiserror::Error, Debug)]
+#[error("None of the requested media types are supported: {requested}")]
+pub struct NotAcceptable {
+    pub requested: String,
+}
+
+impl NotAcceptable {
+    pub fn new(requested: impl Into<String>) -> Self {
+        Self {
+            requested: requested.into(),
+        }
+    }
+}
+
+impl axum::response::IntoResponse for NotAcceptable {
+    fn into_response(self) -> axum::response::Response {
+        (http::StatusCode::NOT_ACCEPTABLE, self.to_string()).into_response()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
 impl From<quick_xml::Error> for ODataError {
     fn from(error: quick_xml::Error) -> Self {
         ODataError::Internal(InternalError::new(error))
@@ -1,21 +1,66 @@
-use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use datafusion::{
-    logical_expr::{BinaryExpr, Operator, expr::InList},
+    arrow::{
+        array::{Array, AsArray, BooleanArray, RecordBatch, UInt32Array, new_empty_array},
+        compute::{concat, take},
+        datatypes::{
+            DataType, Field, IntervalDayTime, IntervalMonthDayNano, IntervalUnit, Schema,
+            SchemaRef, TimeUnit,
+        },
+    },
+    common::{
+        DFSchema, DataFusionError, internal_err,
+        tree_node::{TransformedResult, TreeNode},
+    },
+    logical_expr::{
+        BinaryExpr, ColumnarValue, ExprSchemable, Operator, ScalarFunctionArgs, ScalarUDF,
+        ScalarUDFImpl, Signature, Volatility, execution_props::ExecutionProps, expr::InList,
+    },
+    optimizer::analyzer::type_coercion::TypeCoercionRewriter,
+    physical_expr::create_physical_expr,
     prelude::*,
     scalar::ScalarValue,
 };
-use odata_params::filters as odata_filters;
 
-use crate::error::*;
+use crate::{
+    atom::decode_list,
+    context::{DEFAULT_NAMESPACE, EnumColumn},
+    error::*,
+    geo::{Geometry, SpatialFunction},
+    metadata::{collection_item, value_type},
+};
 
 ///////////////////////////////////////////////////////////////////////////////
 
-#[derive(Debug)]
-pub struct ODataFilter(Expr);
+#[derive(Debug, Clone)]
+pub struct ODataFilter {
+    raw: String,
+    expr: FilterExpr,
+}
 
-impl From<ODataFilter> for Expr {
-    fn from(value: ODataFilter) -> Self {
-        value.0
+impl ODataFilter {
+    /// Original text of the `$filter` query option
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Translates the filter into a DataFusion expression. Properties are
+    /// resolved against the collection schema and literals are coerced to the
+    /// types of the operands they are compared or combined with. Enum literals
+    /// must name members of the types declared for the enum columns.
+    pub fn to_expr(
+        &self,
+        schema: &Schema,
+        enum_columns: &[EnumColumn],
+    ) -> Result<Expr, ODataError> {
+        let schema = DFSchema::try_from(schema.clone()).map_err(ODataError::internal)?;
+        FilterCompiler {
+            schema,
+            enum_columns: enum_columns.to_vec(),
+        }
+        .compile(&self.expr)
     }
 }
 
@@ -25,9 +70,11 @@ impl std::str::FromStr for ODataFilter {
     type Err = ODataError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let odata_exprs = odata_params::filters::parse_str(s).map_err(ODataError::bad_request)?;
-        let df_exprs = odata_expr_to_df_expr(&odata_exprs)?;
-        Ok(ODataFilter(df_exprs))
+        let expr = Parser::new(tokenize(s)?).parse()?;
+        Ok(ODataFilter {
+            raw: s.to_string(),
+            expr,
+        })
     }
 }
 
@@ -54,77 +101,1707 @@ impl serde::de::Visitor<'_> for ODataFilterVisitor {
     }
 }
 
+/// Parses a value of the key predicate, e.g. `123L`, `'abc'` or
+/// `datetime'2024-01-01T00:00'`, and converts it to the type of the key column
+pub fn parse_key_value(text: &str, data_type: &DataType) -> Result<ScalarValue, ODataError> {
+    let literal = match tokenize(text)?.as_slice() {
+        [Token::Literal(Literal::Null)] => {
+            return Err(ODataError::bad_request("Key value cannot be null"));
+        }
+        [Token::Literal(literal)] => literal.clone(),
+        _ => {
+            return Err(ODataError::bad_request(format!(
+                "Invalid key value: {text}"
+            )));
+        }
+    };
+    coerce(&literal, data_type)
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
-fn odata_expr_to_df_expr(res: &odata_filters::Expr) -> Result<Expr, ODataError> {
-    match res {
-        odata_filters::Expr::Or(l, r) => Ok(Expr::BinaryExpr(BinaryExpr::new(
-            Box::new(odata_expr_to_df_expr(l)?),
-            Operator::Or,
-            Box::new(odata_expr_to_df_expr(r)?),
-        ))),
-        odata_filters::Expr::And(l, r) => Ok(Expr::BinaryExpr(BinaryExpr::new(
-            Box::new(odata_expr_to_df_expr(l)?),
-            Operator::And,
-            Box::new(odata_expr_to_df_expr(r)?),
-        ))),
-        odata_filters::Expr::Compare(l, op, r) => Ok(Expr::BinaryExpr(BinaryExpr::new(
-            Box::new(odata_expr_to_df_expr(l)?),
-            odata_op_to_df_op(op),
-            Box::new(odata_expr_to_df_expr(r)?),
-        ))),
-        odata_filters::Expr::Value(v) => Ok(Expr::Literal(odata_value_to_df_value(v)?, None)),
-        odata_filters::Expr::Not(e) => Ok(Expr::Not(Box::new(odata_expr_to_df_expr(e)?))),
-        odata_filters::Expr::In(i, l) => Ok(Expr::InList(InList::new(
-            Box::new(odata_expr_to_df_expr(i)?),
-            l.iter()
-                .map(odata_expr_to_df_expr)
-                .collect::<Result<Vec<Expr>, ODataError>>()?,
-            false,
-        ))),
-        odata_filters::Expr::Identifier(s) => Ok(Expr::Column(Column::new_unqualified(s))),
-        odata_filters::Expr::Function(..) => {
-            Err(UnsupportedFeature::new("Function within the filter is not supported").into())
+struct FilterCompiler {
+    schema: DFSchema,
+    enum_columns: Vec<EnumColumn>,
+}
+
+impl FilterCompiler {
+    fn compile(&self, expr: &FilterExpr) -> Result<Expr, ODataError> {
+        match expr {
+            FilterExpr::Binary(l, op, r) => {
+                let (l, r) = self.compile_operands(l, r)?;
+                Ok(Expr::BinaryExpr(BinaryExpr::new(
+                    Box::new(l),
+                    op.to_df_op(),
+                    Box::new(r),
+                )))
+            }
+            FilterExpr::Literal(v) => Ok(Expr::Literal(default_scalar(v)?, None)),
+            FilterExpr::Not(e) => Ok(Expr::Not(Box::new(self.compile(e)?))),
+            FilterExpr::Negate(e) => Ok(Expr::Negative(Box::new(self.compile(e)?))),
+            FilterExpr::In(i, l) => {
+                let expr = self.compile(i)?;
+                let list = l
+                    .iter()
+                    .map(|e| match e {
+                        FilterExpr::Literal(v) => Ok(Expr::Literal(self.coerce(v, &expr)?, None)),
+                        e => self.compile(e),
+                    })
+                    .collect::<Result<Vec<Expr>, ODataError>>()?;
+                Ok(Expr::InList(InList::new(Box::new(expr), list, false)))
+            }
+            FilterExpr::Identifier(s) => property_path(&self.schema, s).ok_or_else(|| {
+                ODataError::bad_request(format!("Unknown property '{s}' in the filter"))
+            }),
+            FilterExpr::Lambda(path, op, lambda) => self.compile_lambda(path, *op, lambda.as_ref()),
+            FilterExpr::Function(name, args) if name.starts_with("geo.") => {
+                self.compile_spatial_function(name, args)
+            }
+            FilterExpr::Function(name, args) => odata_function_to_df_expr(
+                name,
+                args.iter()
+                    .map(|e| self.compile(e))
+                    .collect::<Result<Vec<Expr>, ODataError>>()?,
+            ),
+        }
+    }
+
+    /// Compiles operands of a binary operator typing a literal operand after
+    /// the other one
+    fn compile_operands(&self, l: &FilterExpr, r: &FilterExpr) -> Result<(Expr, Expr), ODataError> {
+        match (l, r) {
+            (FilterExpr::Literal(v), r) if !matches!(r, FilterExpr::Literal(_)) => {
+                let r = self.compile(r)?;
+                let l = self.coerce(v, &r)?;
+                Ok((Expr::Literal(l, None), r))
+            }
+            (l, FilterExpr::Literal(v)) if !matches!(l, FilterExpr::Literal(_)) => {
+                let l = self.compile(l)?;
+                let r = self.coerce(v, &l)?;
+                Ok((l, Expr::Literal(r, None)))
+            }
+            (l, r) => Ok((self.compile(l)?, self.compile(r)?)),
+        }
+    }
+
+    /// Coerces a literal to the type of the operand it is compared or combined
+    /// with. Enum literals are only accepted for the enum columns and have to
+    /// name a member of the declared type.
+    fn coerce(&self, v: &Literal, operand: &Expr) -> Result<ScalarValue, ODataError> {
+        if let Literal::Enum(type_name, member) = v {
+            let enum_column = match operand {
+                Expr::Column(c) => self.enum_columns.iter().find(|e| e.column == c.name),
+                _ => None,
+            };
+            let Some(enum_column) = enum_column else {
+                return Err(ODataError::bad_request(format!(
+                    "Enum literal {v} in the filter is compared with a property of another type"
+                )));
+            };
+            let expected = format!("{DEFAULT_NAMESPACE}.{}", enum_column.type_name);
+            if *type_name != expected || !enum_column.members.contains(member) {
+                return Err(ODataError::bad_request(format!(
+                    "Literal {v} in the filter is not a member of enum type {expected}"
+                )));
+            }
+        }
+        coerce(v, &self.type_of(operand)?)
+    }
+
+    /// Compiles a lambda operator into a call of [`LambdaUdf`]. Predicate is
+    /// compiled in the scope of the entry extended with the lambda variable,
+    /// and the properties of the entry it references are passed to the
+    /// function along with the collection.
+    fn compile_lambda(
+        &self,
+        path: &str,
+        op: LambdaOp,
+        lambda: Option<&(String, Box<FilterExpr>)>,
+    ) -> Result<Expr, ODataError> {
+        let collection = property_path(&self.schema, path).ok_or_else(|| {
+            ODataError::bad_request(format!("Unknown property '{path}' in the filter"))
+        })?;
+        let Some(item) = collection_item(&self.type_of(&collection)?).cloned() else {
+            return Err(ODataError::bad_request(format!(
+                "Property '{path}' in the filter is not a collection"
+            )));
+        };
+
+        let Some((variable, predicate)) = lambda else {
+            return Ok(LambdaUdf::call(op, Schema::empty(), None, vec![collection]));
+        };
+
+        // Variable shadows the property of the same name
+        let variable_field = Field::new(variable, item.data_type().clone(), true);
+        let mut fields = vec![variable_field.clone()];
+        fields.extend(
+            self.schema
+                .fields()
+                .iter()
+                .filter(|f| f.name() != variable)
+                .map(|f| f.as_ref().clone().with_nullable(true)),
+        );
+        let scope = FilterCompiler {
+            schema: DFSchema::try_from(Schema::new(fields)).map_err(ODataError::internal)?,
+            enum_columns: self
+                .enum_columns
+                .iter()
+                .filter(|e| e.column != *variable)
+                .cloned()
+                .collect(),
+        };
+
+        let predicate = scope.compile(predicate)?;
+        if scope.type_of(&predicate)? != DataType::Boolean {
+            return Err(ODataError::bad_request(format!(
+                "Predicate of {path}/{op} in the filter is not boolean"
+            )));
+        }
+        // Predicate is evaluated outside of the query plan, so it is not
+        // coerced by the analyzer
+        let predicate = predicate
+            .rewrite(&mut TypeCoercionRewriter::new(&scope.schema))
+            .data()
+            .map_err(|e| ODataError::bad_request(format!("Invalid filter expression: {e}")))?;
+
+        let mut properties: Vec<_> = predicate
+            .column_refs()
+            .into_iter()
+            .filter(|c| c.name != *variable)
+            .map(|c| c.name.clone())
+            .collect();
+        properties.sort();
+
+        let mut fields = vec![variable_field];
+        let mut args = vec![collection];
+        for name in properties {
+            let field = scope
+                .schema
+                .field_with_unqualified_name(&name)
+                .map_err(ODataError::internal)?;
+            fields.push(field.as_ref().clone());
+            args.push(Expr::Column(Column::new_unqualified(name)));
+        }
+
+        Ok(LambdaUdf::call(
+            op,
+            Schema::new(fields),
+            Some(predicate),
+            args,
+        ))
+    }
+
+    /// Compiles `geo.distance` and `geo.intersects` into calls of the spatial
+    /// UDFs. Distance is measured on the sphere or in the plane depending on
+    /// the type of the literal argument, as the properties do not carry it.
+    fn compile_spatial_function(
+        &self,
+        name: &str,
+        args: &[FilterExpr],
+    ) -> Result<Expr, ODataError> {
+        let invalid_arg = |i: usize| {
+            ODataError::bad_request(format!(
+                "Argument {i} of function '{name}' must be of Geography or Geometry type"
+            ))
+        };
+
+        if args.len() != 2 {
+            return Err(ODataError::bad_request(format!(
+                "Function '{name}' expects 2 argument(s) but {} were given",
+                args.len()
+            )));
+        }
+
+        let mut geography = Vec::new();
+        let mut compiled = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            match arg {
+                FilterExpr::Literal(Literal::Geography(_)) => geography.push(true),
+                FilterExpr::Literal(Literal::Geometry(_)) => geography.push(false),
+                FilterExpr::Literal(Literal::Null) => {}
+                FilterExpr::Literal(_) => return Err(invalid_arg(i + 1)),
+                _ => {}
+            }
+            let arg = self.compile(arg)?;
+            // Properties hold WKB or WKT values
+            let spatial = matches!(
+                value_type(&self.type_of(&arg)?),
+                DataType::Null
+                    | DataType::Utf8
+                    | DataType::LargeUtf8
+                    | DataType::Utf8View
+                    | DataType::Binary
+                    | DataType::LargeBinary
+                    | DataType::BinaryView
+                    | DataType::FixedSizeBinary(_)
+            );
+            if !spatial {
+                return Err(invalid_arg(i + 1));
+            }
+            compiled.push(arg);
+        }
+        if geography.windows(2).any(|kinds| kinds[0] != kinds[1]) {
+            return Err(ODataError::bad_request(format!(
+                "Arguments of function '{name}' must be of the same type"
+            )));
+        }
+
+        let function = match (name, geography.first()) {
+            ("geo.intersects", _) => SpatialFunction::Intersects,
+            ("geo.distance", Some(true)) => SpatialFunction::GeographyDistance,
+            ("geo.distance", Some(false)) => SpatialFunction::GeometryDistance,
+            ("geo.distance", None) => {
+                return Err(ODataError::bad_request(
+                    "Function 'geo.distance' requires a geography or geometry literal argument",
+                ));
+            }
+            _ => {
+                return Err(
+                    UnsupportedFeature::new(format!("Function '{name}' is not supported")).into(),
+                );
+            }
+        };
+        Ok(function.udf().call(compiled))
+    }
+
+    fn type_of(&self, expr: &Expr) -> Result<DataType, ODataError> {
+        expr.get_type(&self.schema)
+            .map_err(|e| ODataError::bad_request(format!("Invalid filter expression: {e}")))
+    }
+}
+
+/// Evaluates the predicate of a lambda operator over the items of collections
+/// passed as the first argument. The rest of the arguments are the properties
+/// of the entry referenced by the predicate. Null collections are treated as
+/// empty ones, and items for which the predicate is null do not match it.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct LambdaUdf {
+    op: LambdaOp,
+    signature: Signature,
+    /// Lambda variable followed by the referenced properties
+    scope: SchemaRef,
+    predicate: Option<Expr>,
+}
+
+impl LambdaUdf {
+    fn call(op: LambdaOp, scope: Schema, predicate: Option<Expr>, args: Vec<Expr>) -> Expr {
+        ScalarUDF::new_from_impl(Self {
+            op,
+            signature: Signature::variadic_any(Volatility::Immutable),
+            scope: Arc::new(scope),
+            predicate,
+        })
+        .call(args)
+    }
+}
+
+impl ScalarUDFImpl for LambdaUdf {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        match self.op {
+            LambdaOp::Any => "any",
+            LambdaOp::All => "all",
+        }
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> datafusion::error::Result<DataType> {
+        Ok(DataType::Boolean)
+    }
+
+    fn invoke_with_args(
+        &self,
+        args: ScalarFunctionArgs,
+    ) -> datafusion::error::Result<ColumnarValue> {
+        let args = ColumnarValue::values_to_arrays(&args.args)?;
+        let Some((collections, properties)) = args.split_first() else {
+            return internal_err!("Lambda operator requires a collection argument");
+        };
+
+        // Items of all collections along with the indices of their entries
+        let mut items = Vec::new();
+        let mut rows = Vec::new();
+        for row in 0..collections.len() {
+            if collections.is_valid(row) {
+                let values = decode_list(collections, row)
+                    .map_err(|e| DataFusionError::External(e.into()))?;
+                rows.extend(std::iter::repeat_n(row as u32, values.len()));
+                items.push(values);
+            }
+        }
+
+        let mut result = vec![self.op == LambdaOp::All; collections.len()];
+
+        let Some(predicate) = &self.predicate else {
+            for row in rows {
+                result[row as usize] = true;
+            }
+            return Ok(ColumnarValue::Array(Arc::new(BooleanArray::from(result))));
+        };
+
+        let items = if items.is_empty() {
+            new_empty_array(self.scope.field(0).data_type())
+        } else {
+            concat(&items.iter().map(AsRef::as_ref).collect::<Vec<_>>())?
+        };
+        let rows = UInt32Array::from(rows);
+
+        let mut columns = vec![items];
+        for property in properties {
+            columns.push(take(property, &rows, None)?);
+        }
+        let batch = RecordBatch::try_new(self.scope.clone(), columns)?;
+
+        let scope = DFSchema::try_from(self.scope.clone())?;
+        let predicate = create_physical_expr(predicate, &scope, &ExecutionProps::new())?;
+        let matches = predicate.evaluate(&batch)?.into_array(batch.num_rows())?;
+        let matches = matches.as_boolean();
+
+        for (i, row) in rows.values().iter().enumerate() {
+            let matched = matches.is_valid(i) && matches.value(i);
+            match self.op {
+                LambdaOp::Any if matched => result[*row as usize] = true,
+                LambdaOp::All if !matched => result[*row as usize] = false,
+                _ => {}
+            }
+        }
+
+        Ok(ColumnarValue::Array(Arc::new(BooleanArray::from(result))))
+    }
+}
+
+/// Resolves a property, or a path to a member of a complex property like
+/// `address/city`, into an expression accessing the corresponding column
+pub(crate) fn property_path(schema: &DFSchema, path: &str) -> Option<Expr> {
+    let mut members = path.split('/');
+    let name = members.next()?;
+    let mut data_type = schema.field_with_unqualified_name(name).ok()?.data_type();
+    let mut expr = Expr::Column(Column::new_unqualified(name));
+
+    for member in members {
+        let DataType::Struct(fields) = data_type else {
+            return None;
+        };
+        data_type = fields.find(member)?.1.data_type();
+        expr = datafusion::functions::core::expr_fn::get_field(expr, member);
+    }
+
+    // DataFusion cannot compare run-end encoded values, so they are decoded
+    if let DataType::RunEndEncoded(_, values) = data_type {
+        expr = cast(expr, values.data_type().clone());
+    }
+    Some(expr)
+}
+
+// Canonical functions
+// See: https://www.odata.org/documentation/odata-version-3-0/url-conventions/#url5.1.2.4
+fn odata_function_to_df_expr(name: &str, args: Vec<Expr>) -> Result<Expr, ODataError> {
+    use ArgType::*;
+    use datafusion::functions::{datetime, math, string, unicode};
+
+    let expr = match name {
+        // String functions
+        "substringof" => {
+            let [substring, string] = check_args(name, args, [String, String])?;
+            string::contains().call(vec![string, substring])
+        }
+        "contains" => string::contains().call(check_args(name, args, [String, String])?.into()),
+        "startswith" => {
+            string::starts_with().call(check_args(name, args, [String, String])?.into())
+        }
+        "endswith" => string::ends_with().call(check_args(name, args, [String, String])?.into()),
+        "tolower" => string::lower().call(check_args(name, args, [String])?.into()),
+        "toupper" => string::upper().call(check_args(name, args, [String])?.into()),
+        "trim" => string::btrim().call(check_args(name, args, [String])?.into()),
+        "concat" => string::concat().call(check_args(name, args, [String, String])?.into()),
+        "length" => unicode::character_length().call(check_args(name, args, [String])?.into()),
+        // OData positions are zero-based
+        "indexof" => {
+            unicode::strpos().call(check_args(name, args, [String, String])?.into()) - lit(1)
+        }
+        "substring" if args.len() == 3 => {
+            let [string, start, length] = check_args(name, args, [String, Number, Number])?;
+            unicode::substr().call(vec![string, start + lit(1), length])
+        }
+        "substring" => {
+            let [string, start] = check_args(name, args, [String, Number])?;
+            unicode::substr().call(vec![string, start + lit(1)])
+        }
+        // Date functions
+        "year" | "month" | "day" | "hour" | "minute" | "second" => {
+            let [value] = check_args(name, args, [DateTime])?;
+            datetime::date_part().call(vec![lit(name), value])
+        }
+        // Math functions
+        "round" => math::round().call(check_args(name, args, [Number])?.into()),
+        "floor" => math::floor().call(check_args(name, args, [Number])?.into()),
+        "ceiling" => math::ceil().call(check_args(name, args, [Number])?.into()),
+        _ => {
+            return Err(
+                UnsupportedFeature::new(format!("Function '{name}' is not supported")).into(),
+            );
+        }
+    };
+
+    Ok(expr)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ArgType {
+    String,
+    Number,
+    DateTime,
+}
+
+impl ArgType {
+    fn accepts(&self, data_type: &DataType) -> bool {
+        match data_type {
+            DataType::Null => true,
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => {
+                matches!(self, Self::String)
+            }
+            DataType::Date32 | DataType::Date64 | DataType::Timestamp(..) => {
+                matches!(self, Self::DateTime)
+            }
+            dt if dt.is_numeric() => matches!(self, Self::Number),
+            _ => false,
         }
     }
 }
 
-fn odata_value_to_df_value(v: &odata_filters::Value) -> Result<ScalarValue, ODataError> {
+/// Validates the number of function arguments and types of the literal ones
+fn check_args<const N: usize>(
+    name: &str,
+    args: Vec<Expr>,
+    types: [ArgType; N],
+) -> Result<[Expr; N], ODataError> {
+    let args: [Expr; N] = args.try_into().map_err(|args: Vec<Expr>| {
+        ODataError::bad_request(format!(
+            "Function '{name}' expects {N} argument(s) but {} were given",
+            args.len()
+        ))
+    })?;
+
+    for (i, (arg, typ)) in args.iter().zip(types).enumerate() {
+        if let Expr::Literal(value, _) = arg
+            && !typ.accepts(&value.data_type())
+        {
+            return Err(ODataError::bad_request(format!(
+                "Argument {} of function '{name}' must be of {typ:?} type",
+                i + 1
+            )));
+        }
+    }
+
+    Ok(args)
+}
+
+/// Type of a literal which has no typed operand to be coerced to, e.g. in
+/// function arguments
+fn default_scalar(v: &Literal) -> Result<ScalarValue, ODataError> {
     match v {
-        odata_filters::Value::String(s) => Ok(ScalarValue::LargeUtf8(Some(s.clone()))),
-        odata_filters::Value::Bool(b) => Ok(ScalarValue::Boolean(Some(*b))),
-        odata_filters::Value::Null => Ok(ScalarValue::Null),
-        odata_filters::Value::Number(d) => {
-            let d = d
-                .to_string()
-                .parse::<i64>()
-                .map_err(|_| BadRequest::new("Filter contains invalid number"))?;
-            Ok(ScalarValue::Int64(Some(d)))
+        Literal::String(s)
+        | Literal::Guid(s)
+        | Literal::Enum(_, s)
+        | Literal::Geography(s)
+        | Literal::Geometry(s) => Ok(ScalarValue::LargeUtf8(Some(s.clone()))),
+        Literal::Bool(b) => Ok(ScalarValue::Boolean(Some(*b))),
+        Literal::Null => Ok(ScalarValue::Null),
+        Literal::Number(d) => {
+            if let Ok(d) = d.parse::<i64>() {
+                Ok(ScalarValue::Int64(Some(d)))
+            } else {
+                let d = d
+                    .parse::<f64>()
+                    .map_err(|_| BadRequest::new("Filter contains invalid number"))?;
+                Ok(ScalarValue::Float64(Some(d)))
+            }
+        }
+        Literal::DateTime(d) => timestamp_scalar(v, d, &TimeUnit::Microsecond, &Some("UTC".into())),
+        Literal::Date(d) => Ok(ScalarValue::Date32(Some(days_since_epoch(d)))),
+        Literal::Binary(b) => Ok(ScalarValue::Binary(Some(b.clone()))),
+        Literal::Time(t) => {
+            if let Some(nanos) = parse_time_of_day(t) {
+                return Ok(ScalarValue::Time64Nanosecond(Some(nanos)));
+            }
+            match parse_duration(t) {
+                Some((0, days, nanos)) => days
+                    .checked_mul(NANOS_PER_DAY)
+                    .and_then(|d| d.checked_add(nanos))
+                    .map(|nanos| ScalarValue::DurationNanosecond(Some(nanos)))
+                    .ok_or_else(|| ODataError::bad_request(format!("Literal {v} is out of range"))),
+                Some((months, days, nanos)) => interval_scalar(months, days, nanos)
+                    .ok_or_else(|| ODataError::bad_request(format!("Literal {v} is out of range"))),
+                None => Err(ODataError::bad_request(format!(
+                    "Invalid time literal: {t}"
+                ))),
+            }
+        }
+    }
+}
+
+/// Converts a literal to the given type returning bad request error when types
+/// are incompatible
+fn coerce(v: &Literal, data_type: &DataType) -> Result<ScalarValue, ODataError> {
+    let incompatible =
+        || ODataError::bad_request(format!("Literal {v} is incompatible with type {data_type}"));
+
+    match (v, data_type) {
+        (_, DataType::Null) => default_scalar(v),
+        (_, DataType::Dictionary(_, value_type)) => coerce(v, value_type),
+        (_, DataType::RunEndEncoded(_, values)) => coerce(v, values.data_type()),
+        (Literal::Null, _) => Ok(ScalarValue::try_from(data_type).unwrap_or(ScalarValue::Null)),
+        (Literal::Bool(b), DataType::Boolean) => Ok(ScalarValue::Boolean(Some(*b))),
+        // Values not fitting the type (e.g. fractions compared to an integer
+        // property) keep their default type and get promoted by DataFusion
+        (Literal::Number(d), data_type) if data_type.is_numeric() => {
+            ScalarValue::try_from_string(d.clone(), data_type).or_else(|_| default_scalar(v))
+        }
+        (
+            Literal::String(s) | Literal::Guid(s) | Literal::Enum(_, s),
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View,
+        ) => ScalarValue::try_from_string(s.clone(), data_type).map_err(|_| incompatible()),
+        (Literal::Binary(b), DataType::Binary) => Ok(ScalarValue::Binary(Some(b.clone()))),
+        (Literal::Binary(b), DataType::LargeBinary) => {
+            Ok(ScalarValue::LargeBinary(Some(b.clone())))
+        }
+        (Literal::Binary(b), DataType::BinaryView) => Ok(ScalarValue::BinaryView(Some(b.clone()))),
+        (Literal::Binary(b), DataType::FixedSizeBinary(size)) if b.len() == *size as usize => {
+            Ok(ScalarValue::FixedSizeBinary(*size, Some(b.clone())))
+        }
+        // Values not representable in the unit of the column are left to DataFusion
+        (Literal::Time(t), DataType::Time32(unit) | DataType::Time64(unit)) => {
+            let nanos = parse_time_of_day(t).ok_or_else(incompatible)?;
+            time_scalar(nanos, unit).map_or_else(|| default_scalar(v), Ok)
         }
-        odata_filters::Value::DateTime(d) => Ok(ScalarValue::Date64(Some(d.timestamp()))),
-        odata_filters::Value::Date(d) => {
-            let d = d
-                .and_hms_opt(0, 0, 0)
-                .ok_or(BadRequest::new("Filter contains invalid date"))?;
-            let timestamp = DateTime::<Utc>::from_naive_utc_and_offset(d, Utc).timestamp();
-            Ok(ScalarValue::Date64(Some(timestamp)))
+        (Literal::Time(t), DataType::Duration(unit)) => {
+            let (0, days, nanos) = parse_duration(t).ok_or_else(incompatible)? else {
+                return Err(incompatible());
+            };
+            days.checked_mul(NANOS_PER_DAY)
+                .and_then(|d| d.checked_add(nanos))
+                .and_then(|nanos| duration_scalar(nanos, unit))
+                .map_or_else(|| default_scalar(v), Ok)
         }
-        odata_filters::Value::Uuid(u) => Ok(ScalarValue::LargeUtf8(Some(u.to_string()))),
-        odata_filters::Value::Time(_) => {
-            Err(UnsupportedFeature::new("Time value in filter is not supported").into())
+        (Literal::Time(t), DataType::Interval(unit)) => {
+            let (months, days, nanos) = parse_duration(t).ok_or_else(incompatible)?;
+            let value = match unit {
+                IntervalUnit::YearMonth if days == 0 && nanos == 0 => i32::try_from(months)
+                    .ok()
+                    .map(|m| ScalarValue::IntervalYearMonth(Some(m))),
+                IntervalUnit::DayTime if months == 0 && nanos % 1_000_000 == 0 => {
+                    i32::try_from(days)
+                        .ok()
+                        .zip(i32::try_from(nanos / 1_000_000).ok())
+                        .map(|(days, millis)| {
+                            ScalarValue::IntervalDayTime(Some(IntervalDayTime::new(days, millis)))
+                        })
+                }
+                _ => interval_scalar(months, days, nanos),
+            };
+            value.ok_or_else(incompatible)
         }
+        (Literal::DateTime(d), DataType::Timestamp(unit, tz)) => timestamp_scalar(v, d, unit, tz),
+        (Literal::Date(d), DataType::Timestamp(unit, tz)) => {
+            timestamp_scalar(v, &d.and_time(Default::default()).and_utc(), unit, tz)
+        }
+        (Literal::Date(d), DataType::Date32) => Ok(ScalarValue::Date32(Some(days_since_epoch(d)))),
+        (Literal::Date(d), DataType::Date64) => Ok(ScalarValue::Date64(Some(
+            d.and_time(Default::default()).and_utc().timestamp_millis(),
+        ))),
+        // Comparing a date property with a point in time within a day is
+        // left to DataFusion to avoid truncating the literal
+        (Literal::DateTime(d), DataType::Date32 | DataType::Date64) => {
+            match d.time() == Default::default() {
+                true => coerce(&Literal::Date(d.date_naive()), data_type),
+                false => default_scalar(v),
+            }
+        }
+        _ => Err(incompatible()),
+    }
+}
+
+fn timestamp_scalar(
+    v: &Literal,
+    d: &DateTime<Utc>,
+    unit: &TimeUnit,
+    tz: &Option<std::sync::Arc<str>>,
+) -> Result<ScalarValue, ODataError> {
+    let tz = tz.clone();
+    Ok(match unit {
+        TimeUnit::Second => ScalarValue::TimestampSecond(Some(d.timestamp()), tz),
+        TimeUnit::Millisecond => ScalarValue::TimestampMillisecond(Some(d.timestamp_millis()), tz),
+        TimeUnit::Microsecond => ScalarValue::TimestampMicrosecond(Some(d.timestamp_micros()), tz),
+        TimeUnit::Nanosecond => {
+            let nanos = d
+                .timestamp_nanos_opt()
+                .ok_or_else(|| ODataError::bad_request(format!("Literal {v} is out of range")))?;
+            ScalarValue::TimestampNanosecond(Some(nanos), tz)
+        }
+    })
+}
+
+fn days_since_epoch(d: &NaiveDate) -> i32 {
+    (*d - DateTime::UNIX_EPOCH.date_naive()).num_days() as i32
+}
+
+const NANOS_PER_DAY: i64 = 86_400_000_000_000;
+
+/// Nanoseconds per unit of time
+fn unit_nanos(unit: &TimeUnit) -> i64 {
+    match unit {
+        TimeUnit::Second => 1_000_000_000,
+        TimeUnit::Millisecond => 1_000_000,
+        TimeUnit::Microsecond => 1_000,
+        TimeUnit::Nanosecond => 1,
+    }
+}
+
+/// Time of day in the given unit, or `None` when the value has a finer precision
+fn time_scalar(nanos: i64, unit: &TimeUnit) -> Option<ScalarValue> {
+    if nanos % unit_nanos(unit) != 0 {
+        return None;
+    }
+    let value = nanos / unit_nanos(unit);
+    Some(match unit {
+        TimeUnit::Second => ScalarValue::Time32Second(Some(value as i32)),
+        TimeUnit::Millisecond => ScalarValue::Time32Millisecond(Some(value as i32)),
+        TimeUnit::Microsecond => ScalarValue::Time64Microsecond(Some(value)),
+        TimeUnit::Nanosecond => ScalarValue::Time64Nanosecond(Some(value)),
+    })
+}
+
+/// Duration in the given unit, or `None` when the value has a finer precision
+fn duration_scalar(nanos: i64, unit: &TimeUnit) -> Option<ScalarValue> {
+    if nanos % unit_nanos(unit) != 0 {
+        return None;
     }
+    let value = Some(nanos / unit_nanos(unit));
+    Some(match unit {
+        TimeUnit::Second => ScalarValue::DurationSecond(value),
+        TimeUnit::Millisecond => ScalarValue::DurationMillisecond(value),
+        TimeUnit::Microsecond => ScalarValue::DurationMicrosecond(value),
+        TimeUnit::Nanosecond => ScalarValue::DurationNanosecond(value),
+    })
 }
 
-fn odata_op_to_df_op(op: &odata_filters::CompareOperator) -> Operator {
-    match op {
-        odata_filters::CompareOperator::Equal => Operator::Eq,
-        odata_filters::CompareOperator::NotEqual => Operator::NotEq,
-        odata_filters::CompareOperator::LessThan => Operator::Lt,
-        odata_filters::CompareOperator::GreaterThan => Operator::Gt,
-        odata_filters::CompareOperator::LessOrEqual => Operator::LtEq,
-        odata_filters::CompareOperator::GreaterOrEqual => Operator::GtEq,
+fn interval_scalar(months: i64, days: i64, nanos: i64) -> Option<ScalarValue> {
+    let value = IntervalMonthDayNano::new(
+        i32::try_from(months).ok()?,
+        i32::try_from(days).ok()?,
+        nanos,
+    );
+    Some(ScalarValue::IntervalMonthDayNano(Some(value)))
+}
+
+/// Parses time of day as `hh:mm[:ss[.fffffffff]]` or as xsd:duration within a
+/// day used by OData v3 (`time'PT13H20M'`) into nanoseconds since midnight
+fn parse_time_of_day(text: &str) -> Option<i64> {
+    let re = regex::Regex::new(r"^(\d{1,2}):(\d{2})(:(\d{2})(\.(\d{1,9}))?)?$").unwrap();
+    if let Some(c) = re.captures(text) {
+        let number = |i: usize| c.get(i).map_or(Some(0), |m| m.as_str().parse::<i64>().ok());
+        let (hours, minutes, seconds) = (number(1)?, number(2)?, number(4)?);
+        if hours > 23 || minutes > 59 || seconds > 59 {
+            return None;
+        }
+        let fraction = c.get(6).map_or(Some(0), |m| fraction_nanos(m.as_str()))?;
+        return Some(((hours * 60 + minutes) * 60 + seconds) * 1_000_000_000 + fraction);
+    }
+
+    match parse_duration(text)? {
+        (0, 0, nanos) if (0..NANOS_PER_DAY).contains(&nanos) => Some(nanos),
+        _ => None,
     }
 }
 
+/// Parses xsd:duration, e.g. `P1Y2M3DT4H5M6.5S` or `-PT0.5S`, into months,
+/// days and nanoseconds of the same sign
+fn parse_duration(text: &str) -> Option<(i64, i64, i64)> {
+    let re = regex::Regex::new(
+        r"^(-)?P((\d+)Y)?((\d+)M)?((\d+)D)?(T((\d+)H)?((\d+)M)?((\d+)(\.(\d{1,9}))?S)?)?$",
+    )
+    .unwrap();
+    let c = re.captures(text)?;
+    if text.ends_with('P') || text.ends_with('T') {
+        return None;
+    }
+
+    let number = |i: usize| c.get(i).map_or(Some(0), |m| m.as_str().parse::<i64>().ok());
+    let months = number(3)?.checked_mul(12)?.checked_add(number(5)?)?;
+    let days = number(7)?;
+    let seconds = number(10)?
+        .checked_mul(60)?
+        .checked_add(number(12)?)?
+        .checked_mul(60)?
+        .checked_add(number(14)?)?;
+    let fraction = c.get(16).map_or(Some(0), |m| fraction_nanos(m.as_str()))?;
+    let nanos = seconds.checked_mul(1_000_000_000)?.checked_add(fraction)?;
+
+    Some(match c.get(1) {
+        Some(_) => (-months, -days, -nanos),
+        None => (months, days, nanos),
+    })
+}
+
+/// Converts up to 9 digits of a fraction of a second into nanoseconds
+fn fraction_nanos(digits: &str) -> Option<i64> {
+    format!("{digits:0<9}").parse().ok()
+}
+
 ///////////////////////////////////////////////////////////////////////////////
+// Parsing
+///////////////////////////////////////////////////////////////////////////////
+
+// See: https://docs.oasis-open.org/odata/odata/v4.01/odata-v4.01-part2-url-conventions.html#sec_OperatorPrecedence
+#[derive(Debug, Clone, PartialEq)]
+enum FilterExpr {
+    Literal(Literal),
+    Identifier(String),
+    Function(String, Vec<FilterExpr>),
+    Not(Box<FilterExpr>),
+    Negate(Box<FilterExpr>),
+    Binary(Box<FilterExpr>, BinaryOp, Box<FilterExpr>),
+    In(Box<FilterExpr>, Vec<FilterExpr>),
+    /// Lambda operator over a collection property, e.g. `tags/any(t: t eq 'etf')`.
+    /// Lambda variable and predicate are absent in `any()`.
+    Lambda(String, LambdaOp, Option<(String, Box<FilterExpr>)>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum LambdaOp {
+    Any,
+    All,
+}
+
+impl std::fmt::Display for LambdaOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::All => write!(f, "all"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+impl BinaryOp {
+    fn to_df_op(self) -> Operator {
+        match self {
+            Self::Or => Operator::Or,
+            Self::And => Operator::And,
+            Self::Eq => Operator::Eq,
+            Self::Ne => Operator::NotEq,
+            Self::Gt => Operator::Gt,
+            Self::Ge => Operator::GtEq,
+            Self::Lt => Operator::Lt,
+            Self::Le => Operator::LtEq,
+            Self::Add => Operator::Plus,
+            Self::Sub => Operator::Minus,
+            Self::Mul => Operator::Multiply,
+            Self::Div => Operator::Divide,
+            Self::Mod => Operator::Modulo,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Null,
+    Bool(bool),
+    /// Textual form without the type suffix, e.g. `-1.5` for `-1.5M`
+    Number(String),
+    String(String),
+    Date(NaiveDate),
+    DateTime(DateTime<Utc>),
+    Time(String),
+    Guid(String),
+    Binary(Vec<u8>),
+    /// Member of an enum type by its qualified type name, e.g. `ns.Status'Active'`
+    Enum(String, String),
+    /// WKT with an optional SRID prefix, e.g. `SRID=4326;POINT(-123.1 49.25)`
+    Geography(String),
+    Geometry(String),
+}
+
+impl std::fmt::Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => write!(f, "null"),
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Number(d) => write!(f, "{d}"),
+            Self::String(s) => write!(f, "'{}'", s.replace('\'', "''")),
+            Self::Date(d) => write!(f, "{d}"),
+            Self::DateTime(d) => write!(f, "datetime'{}'", d.to_rfc3339()),
+            Self::Time(t) => write!(f, "time'{t}'"),
+            Self::Guid(g) => write!(f, "guid'{g}'"),
+            Self::Binary(b) => {
+                write!(f, "X'")?;
+                for byte in b {
+                    write!(f, "{byte:02X}")?;
+                }
+                write!(f, "'")
+            }
+            Self::Enum(t, m) => write!(f, "{t}'{m}'"),
+            Self::Geography(g) => write!(f, "geography'{g}'"),
+            Self::Geometry(g) => write!(f, "geometry'{g}'"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Literal(Literal),
+    Minus,
+    LParen,
+    RParen,
+    Comma,
+    Colon,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, ODataError> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            ':' => {
+                tokens.push(Token::Colon);
+                i += 1;
+            }
+            '\'' => {
+                let (value, end) = read_quoted(&chars, i)?;
+                tokens.push(Token::Literal(Literal::String(value)));
+                i = end;
+            }
+            '-' if !chars.get(i + 1).is_some_and(char::is_ascii_digit) => {
+                tokens.push(Token::Minus);
+                i += 1;
+            }
+            // Numbers, dates, times and GUIDs
+            c if c == '-' || c.is_ascii_digit() => {
+                let start = i;
+                i += 1;
+                while i < chars.len()
+                    && (chars[i].is_ascii_alphanumeric()
+                        || matches!(chars[i], '.' | ':' | '-' | '+'))
+                {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                tokens.push(Token::Literal(classify_literal(&text)?));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric()
+                        || matches!(chars[i], '_' | '.')
+                        // Member of a complex property, e.g. address/city
+                        || chars[i] == '/'
+                            && chars
+                                .get(i + 1)
+                                .is_some_and(|c| c.is_alphabetic() || *c == '_'))
+                {
+                    i += 1;
+                }
+                let ident: String = chars[start..i].iter().collect();
+
+                if chars.get(i) == Some(&'\'') {
+                    // Typed literal, e.g. datetime'2000-01-01T00:00'
+                    let (value, end) = read_quoted(&chars, i)?;
+                    tokens.push(Token::Literal(typed_literal(&ident, &value)?));
+                    i = end;
+                } else {
+                    tokens.push(match ident.to_ascii_lowercase().as_str() {
+                        "null" => Token::Literal(Literal::Null),
+                        "true" => Token::Literal(Literal::Bool(true)),
+                        "false" => Token::Literal(Literal::Bool(false)),
+                        _ => Token::Ident(ident),
+                    });
+                }
+            }
+            _ => {
+                return Err(ODataError::bad_request(format!(
+                    "Unexpected character '{c}' at position {i} of the filter"
+                )));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Reads a single-quoted string starting at `start` returning its unescaped
+/// value and the position past the closing quote. Quotes are escaped by
+/// doubling them as per OData ABNF, other characters including backslashes
+/// are taken as is.
+fn read_quoted(chars: &[char], start: usize) -> Result<(String, usize), ODataError> {
+    let mut value = String::new();
+    let mut i = start + 1;
+
+    loop {
+        match chars.get(i) {
+            None => return Err(ODataError::bad_request("Unterminated string in the filter")),
+            Some('\'') if chars.get(i + 1) == Some(&'\'') => {
+                value.push('\'');
+                i += 2;
+            }
+            Some('\'') => return Ok((value, i + 1)),
+            Some(c) => {
+                value.push(*c);
+                i += 1;
+            }
+        }
+    }
+}
+
+fn classify_literal(text: &str) -> Result<Literal, ODataError> {
+    let is_match = |re: &str| regex::Regex::new(re).unwrap().is_match(text);
+
+    if is_match(r"^-?\d+(\.\d*)?([eE][+-]?\d+)?[LlMmDdFf]?$") {
+        let number = text.trim_end_matches(['L', 'l', 'M', 'm', 'D', 'd', 'F', 'f']);
+        Ok(Literal::Number(number.to_string()))
+    } else if is_match(
+        r"^[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}$",
+    ) {
+        Ok(Literal::Guid(text.to_string()))
+    } else if is_match(r"^\d{4}-\d{2}-\d{2}$") {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d")
+            .map(Literal::Date)
+            .map_err(|_| ODataError::bad_request(format!("Invalid date literal: {text}")))
+    } else if is_match(r"^\d{4}-\d{2}-\d{2}T") {
+        parse_date_time(text).map(Literal::DateTime)
+    } else if is_match(r"^\d{1,2}:\d{2}(:\d{2}(\.\d{1,9})?)?$") {
+        Ok(Literal::Time(text.to_string()))
+    } else {
+        Err(ODataError::bad_request(format!(
+            "Invalid literal in the filter: {text}"
+        )))
+    }
+}
+
+/// Decodes hex digits of a binary literal, e.g. `0AFF` of `X'0AFF'`
+fn parse_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.is_ascii() || !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).ok())
+        .collect()
+}
+
+// See: https://www.odata.org/documentation/odata-version-3-0/abnf/
+fn typed_literal(prefix: &str, value: &str) -> Result<Literal, ODataError> {
+    match prefix.to_ascii_lowercase().as_str() {
+        "datetime" | "datetimeoffset" => match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            Ok(d) => Ok(Literal::Date(d)),
+            Err(_) => parse_date_time(value).map(Literal::DateTime),
+        },
+        "guid" => Ok(Literal::Guid(value.to_string())),
+        "time" | "duration" => Ok(Literal::Time(value.to_string())),
+        "geography" => Geometry::from_wkt(value)
+            .map(|_| Literal::Geography(value.to_string()))
+            .map_err(ODataError::bad_request),
+        "geometry" => Geometry::from_wkt(value)
+            .map(|_| Literal::Geometry(value.to_string()))
+            .map_err(ODataError::bad_request),
+        "binary" | "x" => parse_hex(value)
+            .map(Literal::Binary)
+            .ok_or_else(|| ODataError::bad_request(format!("Invalid binary literal: {value}"))),
+        // Enum members are published as the values of string columns, the type
+        // is checked against the operand when the literal is coerced
+        _ if prefix.contains('.') => Ok(Literal::Enum(prefix.to_string(), value.to_string())),
+        _ => Err(ODataError::bad_request(format!(
+            "Unsupported literal type in the filter: {prefix}"
+        ))),
+    }
+}
+
+/// Parses date and time with an optional offset, values without an offset are
+/// considered to be in UTC
+fn parse_date_time(text: &str) -> Result<DateTime<Utc>, ODataError> {
+    let normalized = match text.strip_suffix('Z') {
+        Some(t) => format!("{t}+00:00"),
+        None => text.to_string(),
+    };
+
+    for format in ["%Y-%m-%dT%H:%M:%S%.f%:z", "%Y-%m-%dT%H:%M%:z"] {
+        if let Ok(dt) = DateTime::parse_from_str(&normalized, format) {
+            return Ok(dt.to_utc());
+        }
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(text, format) {
+            return Ok(dt.and_utc());
+        }
+    }
+
+    Err(ODataError::bad_request(format!(
+        "Invalid datetime literal: {text}"
+    )))
+}
+
+/// Recursive descent parser of the filter expressions. Grammar in the order of
+/// increasing precedence:
+///
+/// ```text
+/// or         := and ("or" and)*
+/// and        := equality ("and" equality)*
+/// equality   := "not" equality | relational (("eq" | "ne") relational)*
+/// relational := additive (("gt" | "ge" | "lt" | "le") additive | "in" "(" list ")")*
+/// additive   := multiplicative (("add" | "sub") multiplicative)*
+/// multiplicative := unary (("mul" | "div" | "mod") unary)*
+/// unary      := "-" unary | primary
+/// primary    := "(" or ")" | literal | ident "(" list ")" | ident
+/// ```
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self { tokens, pos: 0 }
+    }
+
+    fn parse(mut self) -> Result<FilterExpr, ODataError> {
+        let expr = self.parse_or()?;
+        match self.peek() {
+            None => Ok(expr),
+            Some(token) => Err(Self::unexpected(token)),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn unexpected(token: &Token) -> ODataError {
+        ODataError::bad_request(format!("Unexpected token in the filter: {token:?}"))
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ODataError> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(Self::unexpected(&token)),
+            None => Err(ODataError::bad_request("Unexpected end of the filter")),
+        }
+    }
+
+    /// Consumes the next token if it is a keyword matching one of the operators
+    fn keyword(&mut self, operators: &[(&str, BinaryOp)]) -> Option<BinaryOp> {
+        let Some(Token::Ident(ident)) = self.peek() else {
+            return None;
+        };
+        let op = operators
+            .iter()
+            .find(|(kw, _)| ident == kw)
+            .map(|(_, op)| *op)?;
+        self.pos += 1;
+        Some(op)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(ident)) if ident == keyword)
+    }
+
+    fn binary_chain(
+        &mut self,
+        operators: &[(&str, BinaryOp)],
+        operand: fn(&mut Self) -> Result<FilterExpr, ODataError>,
+    ) -> Result<FilterExpr, ODataError> {
+        let mut left = operand(self)?;
+        while let Some(op) = self.keyword(operators) {
+            let right = operand(self)?;
+            left = FilterExpr::Binary(Box::new(left), op, Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_or(&mut self) -> Result<FilterExpr, ODataError> {
+        self.binary_chain(&[("or", BinaryOp::Or)], Self::parse_and)
+    }
+
+    fn parse_and(&mut self) -> Result<FilterExpr, ODataError> {
+        self.binary_chain(&[("and", BinaryOp::And)], Self::parse_equality)
+    }
+
+    fn parse_equality(&mut self) -> Result<FilterExpr, ODataError> {
+        if self.is_keyword("not") {
+            self.pos += 1;
+            return Ok(FilterExpr::Not(Box::new(self.parse_equality()?)));
+        }
+        self.binary_chain(
+            &[("eq", BinaryOp::Eq), ("ne", BinaryOp::Ne)],
+            Self::parse_relational,
+        )
+    }
+
+    fn parse_relational(&mut self) -> Result<FilterExpr, ODataError> {
+        let operators = [
+            ("gt", BinaryOp::Gt),
+            ("ge", BinaryOp::Ge),
+            ("lt", BinaryOp::Lt),
+            ("le", BinaryOp::Le),
+        ];
+
+        let mut left = self.parse_additive()?;
+        loop {
+            if let Some(op) = self.keyword(&operators) {
+                let right = self.parse_additive()?;
+                left = FilterExpr::Binary(Box::new(left), op, Box::new(right));
+            } else if self.is_keyword("in") {
+                self.pos += 1;
+                self.expect(Token::LParen)?;
+                let list = self.parse_list()?;
+                left = FilterExpr::In(Box::new(left), list);
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    fn parse_additive(&mut self) -> Result<FilterExpr, ODataError> {
+        self.binary_chain(
+            &[("add", BinaryOp::Add), ("sub", BinaryOp::Sub)],
+            Self::parse_multiplicative,
+        )
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<FilterExpr, ODataError> {
+        self.binary_chain(
+            &[
+                ("mul", BinaryOp::Mul),
+                ("div", BinaryOp::Div),
+                ("mod", BinaryOp::Mod),
+            ],
+            Self::parse_unary,
+        )
+    }
+
+    fn parse_unary(&mut self) -> Result<FilterExpr, ODataError> {
+        if self.peek() == Some(&Token::Minus) {
+            self.pos += 1;
+            return Ok(FilterExpr::Negate(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<FilterExpr, ODataError> {
+        match self.next() {
+            Some(Token::LParen) => {
+                let expr = self.parse_or()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Some(Token::Literal(literal)) => Ok(FilterExpr::Literal(literal)),
+            Some(Token::Ident(ident)) => {
+                if self.peek() == Some(&Token::LParen) {
+                    self.pos += 1;
+                    if let Some((path, op)) = ident.rsplit_once('/') {
+                        return self.parse_lambda(path, op);
+                    }
+                    let args = self.parse_list()?;
+                    Ok(FilterExpr::Function(ident, args))
+                } else {
+                    Ok(FilterExpr::Identifier(ident))
+                }
+            }
+            Some(token) => Err(Self::unexpected(&token)),
+            None => Err(ODataError::bad_request("Unexpected end of the filter")),
+        }
+    }
+
+    /// Parses `any` or `all` lambda operator up to and including the closing paren
+    fn parse_lambda(&mut self, path: &str, op: &str) -> Result<FilterExpr, ODataError> {
+        let op = match op {
+            "any" => LambdaOp::Any,
+            "all" => LambdaOp::All,
+            _ => {
+                return Err(ODataError::bad_request(format!(
+                    "Unknown lambda operator in the filter: {op}"
+                )));
+            }
+        };
+
+        if op == LambdaOp::Any && self.peek() == Some(&Token::RParen) {
+            self.pos += 1;
+            return Ok(FilterExpr::Lambda(path.to_string(), op, None));
+        }
+
+        let variable = match self.next() {
+            Some(Token::Ident(variable)) if !variable.contains('/') => variable,
+            Some(token) => return Err(Self::unexpected(&token)),
+            None => return Err(ODataError::bad_request("Unexpected end of the filter")),
+        };
+        self.expect(Token::Colon)?;
+        let predicate = self.parse_or()?;
+        self.expect(Token::RParen)?;
+
+        Ok(FilterExpr::Lambda(
+            path.to_string(),
+            op,
+            Some((variable, Box::new(predicate))),
+        ))
+    }
+
+    /// Parses comma-separated expressions up to and including the closing paren
+    fn parse_list(&mut self) -> Result<Vec<FilterExpr>, ODataError> {
+        let mut list = Vec::new();
+        if self.peek() == Some(&Token::RParen) {
+            self.pos += 1;
+            return Ok(list);
+        }
+        loop {
+            list.push(self.parse_or()?);
+            match self.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::RParen) => return Ok(list),
+                Some(token) => return Err(Self::unexpected(&token)),
+                None => return Err(ODataError::bad_request("Unexpected end of the filter")),
+            }
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(filter: &str) -> Result<Expr, ODataError> {
+        let schema = Schema::new(vec![
+            Field::new("a", DataType::Int64, true),
+            Field::new("b", DataType::Int64, true),
+            Field::new("c", DataType::Int64, true),
+            Field::new("s", DataType::Utf8, true),
+            Field::new("close", DataType::Float64, true),
+            Field::new("price", DataType::Decimal128(10, 2), true),
+            Field::new(
+                "event_time",
+                DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+                true,
+            ),
+            Field::new("day", DataType::Date32, true),
+            Field::new("opens", DataType::Time32(TimeUnit::Second), true),
+            Field::new("lag", DataType::Duration(TimeUnit::Millisecond), true),
+            Field::new("period", DataType::Interval(IntervalUnit::YearMonth), true),
+            Field::new("tags", DataType::new_list(DataType::Utf8, true), true),
+            Field::new("location", DataType::Binary, true),
+            Field::new("status", DataType::Utf8, true),
+        ]);
+        let enum_columns = [EnumColumn {
+            column: "status".to_string(),
+            type_name: "Status".to_string(),
+            members: vec!["Active".to_string(), "Closed".to_string()],
+        }];
+        filter
+            .parse::<ODataFilter>()?
+            .to_expr(&schema, &enum_columns)
+    }
+
+    #[test]
+    fn test_filter_functions() {
+        assert_eq!(
+            compile("startswith(s,'sp') eq true").unwrap(),
+            datafusion::functions::string::starts_with()
+                .call(vec![
+                    col("s"),
+                    lit(ScalarValue::LargeUtf8(Some("sp".to_string())))
+                ])
+                .eq(lit(true))
+        );
+
+        assert!(matches!(
+            compile("startswith(s) eq true"),
+            Err(ODataError::BadRequest(_))
+        ));
+        assert!(matches!(
+            compile("round('abc') eq 1"),
+            Err(ODataError::BadRequest(_))
+        ));
+        assert!(matches!(
+            compile("year(42) eq 1999"),
+            Err(ODataError::BadRequest(_))
+        ));
+        assert!(matches!(
+            compile("isof(close) eq true"),
+            Err(ODataError::UnsupportedFeature(_))
+        ));
+    }
+
+    #[test]
+    fn test_filter_lambda() {
+        for valid in [
+            "tags/any()",
+            "tags/any(t: t eq 'etf')",
+            "tags/all(t: startswith(t, s) or length(t) gt a)",
+            "not tags/any(tags: tags eq 'etf')",
+        ] {
+            assert!(
+                matches!(compile(valid), Ok(Expr::ScalarFunction(_) | Expr::Not(_))),
+                "{valid}"
+            );
+        }
+
+        for invalid in [
+            "tags/any(t: t)",
+            "tags/any(t: x eq 1)",
+            "s/any(t: t eq 'etf')",
+            "missing/any(t: t eq 'etf')",
+        ] {
+            assert!(
+                matches!(compile(invalid), Err(ODataError::BadRequest(_))),
+                "{invalid}"
+            );
+        }
+
+        for invalid in [
+            "tags/all()",
+            "tags/some(t: t eq 'etf')",
+            "tags/any(t t eq 'etf')",
+            "tags/any(t: t eq 'etf'",
+        ] {
+            assert!(
+                matches!(
+                    invalid.parse::<ODataFilter>(),
+                    Err(ODataError::BadRequest(_))
+                ),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_filter_spatial_functions() {
+        let wkt = |s: &str| lit(ScalarValue::LargeUtf8(Some(s.to_string())));
+
+        assert_eq!(
+            compile("geo.distance(location, geography'SRID=4326;POINT(-123.1 49.25)') lt 900")
+                .unwrap(),
+            SpatialFunction::GeographyDistance
+                .udf()
+                .call(vec![col("location"), wkt("SRID=4326;POINT(-123.1 49.25)")])
+                .lt(lit(ScalarValue::Float64(Some(900.0))))
+        );
+        assert_eq!(
+            compile("geo.distance(geometry'POINT(0 0)', location) gt 1.5").unwrap(),
+            SpatialFunction::GeometryDistance
+                .udf()
+                .call(vec![wkt("POINT(0 0)"), col("location")])
+                .gt(lit(ScalarValue::Float64(Some(1.5))))
+        );
+        assert_eq!(
+            compile("geo.intersects(location, geography'POLYGON((0 0, 1 0, 1 1, 0 0))')").unwrap(),
+            SpatialFunction::Intersects
+                .udf()
+                .call(vec![col("location"), wkt("POLYGON((0 0, 1 0, 1 1, 0 0))")])
+        );
+
+        for invalid in [
+            "geo.distance(location, location) lt 1",
+            "geo.distance(location, 'POINT(0 0)') lt 1",
+            "geo.distance(geography'POINT(0 0)', geometry'POINT(0 0)') lt 1",
+            "geo.distance(a, geography'POINT(0 0)') lt 1",
+            "geo.intersects(location)",
+            "geo.intersects(location, geography'POINT(0)')",
+            "location eq geography'POINT(0 0)'",
+        ] {
+            assert!(
+                matches!(compile(invalid), Err(ODataError::BadRequest(_))),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_filter_arithmetic_precedence() {
+        let parse = |s: &str| compile(s).unwrap();
+        let int = |v: i64| lit(ScalarValue::Int64(Some(v)));
+
+        assert_eq!(
+            parse("a add b mul 2 eq 7"),
+            (col("a") + col("b") * int(2)).eq(int(7))
+        );
+        assert_eq!(
+            parse("a sub 4 div 2 mod 3 gt 0"),
+            (col("a") - int(4) / int(2) % int(3)).gt(int(0))
+        );
+        assert_eq!(
+            parse("(a add b) mul 2 eq 7"),
+            ((col("a") + col("b")) * int(2)).eq(int(7))
+        );
+        assert_eq!(
+            parse("a sub b sub c eq 0"),
+            (col("a") - col("b") - col("c")).eq(int(0))
+        );
+        assert_eq!(
+            parse("-a eq -1"),
+            Expr::Negative(Box::new(col("a"))).eq(int(-1))
+        );
+        assert_eq!(
+            parse("a gt 1 and not b eq 2 or c lt 3"),
+            col("a")
+                .gt(int(1))
+                .and(Expr::Not(Box::new(col("b").eq(int(2)))))
+                .or(col("c").lt(int(3)))
+        );
+        assert_eq!(
+            parse("a in (1, 2) and s eq 'it''s'"),
+            col("a")
+                .in_list(vec![int(1), int(2)], false)
+                .and(col("s").eq(lit(ScalarValue::Utf8(Some("it's".to_string())))))
+        );
+
+        // Backslash is not an escape character
+        let string = |s: &str| lit(ScalarValue::Utf8(Some(s.to_string())));
+        assert_eq!(parse(r"s eq 'C:\new'"), col("s").eq(string(r"C:\new")));
+        assert_eq!(
+            parse(r"s eq 'a\' or s eq 'b'"),
+            col("s").eq(string(r"a\")).or(col("s").eq(string("b")))
+        );
+
+        for invalid in [
+            "a add",
+            "a eq (1",
+            "a eq 1)",
+            "a eq 1 2",
+            "a eq foo'x'",
+            "a eq 'x",
+        ] {
+            assert!(
+                matches!(
+                    invalid.parse::<ODataFilter>(),
+                    Err(ODataError::BadRequest(_))
+                ),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_filter_literal_coercion() {
+        let parse = |s: &str| compile(s).unwrap();
+        let ts = |v: i64| {
+            lit(ScalarValue::TimestampMillisecond(
+                Some(v),
+                Some("UTC".into()),
+            ))
+        };
+
+        assert_eq!(
+            parse("close gt 135"),
+            col("close").gt(lit(ScalarValue::Float64(Some(135.0))))
+        );
+        assert_eq!(
+            parse("3.14 lt price"),
+            lit(ScalarValue::Decimal128(Some(314), 10, 2)).lt(col("price"))
+        );
+        // Fraction does not fit integer type and is left for DataFusion to promote
+        assert_eq!(
+            parse("a gt 1.5"),
+            col("a").gt(lit(ScalarValue::Float64(Some(1.5))))
+        );
+        assert_eq!(
+            parse("event_time ge datetime'1999-11-02T00:00:00' and event_time lt 1999-11-03"),
+            col("event_time")
+                .gt_eq(ts(941_500_800_000))
+                .and(col("event_time").lt(ts(941_500_800_000 + 86_400_000)))
+        );
+        assert_eq!(
+            parse("event_time eq 1999-11-02T03:00:00+03:00"),
+            col("event_time").eq(ts(941_500_800_000))
+        );
+        assert_eq!(
+            parse("day eq datetime'1999-11-02T00:00'"),
+            col("day").eq(lit(ScalarValue::Date32(Some(10897))))
+        );
+        assert_eq!(
+            parse("s in ('x', null)"),
+            col("s").in_list(
+                vec![
+                    lit(ScalarValue::Utf8(Some("x".to_string()))),
+                    lit(ScalarValue::Utf8(None))
+                ],
+                false
+            )
+        );
+        assert_eq!(
+            parse("status eq default.Status'Active'"),
+            col("status").eq(lit(ScalarValue::Utf8(Some("Active".to_string()))))
+        );
+        assert_eq!(
+            parse("status in (default.Status'Active', default.Status'Closed')"),
+            col("status").in_list(
+                vec![
+                    lit(ScalarValue::Utf8(Some("Active".to_string()))),
+                    lit(ScalarValue::Utf8(Some("Closed".to_string())))
+                ],
+                false
+            )
+        );
+
+        for invalid in [
+            "s eq 5",
+            "a eq default.Status'Active'",
+            "s eq default.Status'Active'",
+            "status eq ns.Status'Active'",
+            "status eq default.Other'Active'",
+            "status eq default.Status'Pending'",
+            "status in (default.Status'Active', default.Status'active')",
+            "status eq Status'Active'",
+            "a eq 'x'",
+            "day eq true",
+            "close gt 1999-11-02",
+            "unknown eq 1",
+            "opens eq time'P1D'",
+            "opens eq 24:00",
+            "lag eq duration'P1M'",
+            "lag eq duration'PT'",
+            "s eq time'PT1H'",
+        ] {
+            assert!(
+                matches!(compile(invalid), Err(ODataError::BadRequest(_))),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_filter_time_literals() {
+        let parse = |s: &str| compile(s).unwrap();
+
+        assert_eq!(
+            parse("opens ge 09:30 and opens lt time'PT16H'"),
+            col("opens")
+                .gt_eq(lit(ScalarValue::Time32Second(Some(34_200))))
+                .and(col("opens").lt(lit(ScalarValue::Time32Second(Some(57_600)))))
+        );
+        // Fraction of a second does not fit the unit and is left for DataFusion to promote
+        assert_eq!(
+            parse("opens gt 09:30:00.5"),
+            col("opens").gt(lit(ScalarValue::Time64Nanosecond(Some(34_200_500_000_000))))
+        );
+        assert_eq!(
+            parse("lag gt duration'P1DT0.25S' or lag lt time'-PT1M'"),
+            col("lag")
+                .gt(lit(ScalarValue::DurationMillisecond(Some(86_400_250))))
+                .or(col("lag").lt(lit(ScalarValue::DurationMillisecond(Some(-60_000)))))
+        );
+        assert_eq!(
+            parse("period eq duration'P1Y2M'"),
+            col("period").eq(lit(ScalarValue::IntervalYearMonth(Some(14))))
+        );
+    }
+
+    #[test]
+    fn test_key_value_parsing() {
+        let utc = Some("UTC".into());
+
+        assert_eq!(
+            parse_key_value("123L", &DataType::Int64).unwrap(),
+            ScalarValue::Int64(Some(123))
+        );
+        assert_eq!(
+            parse_key_value("-5", &DataType::Int32).unwrap(),
+            ScalarValue::Int32(Some(-5))
+        );
+        assert_eq!(
+            parse_key_value("'it''s'", &DataType::Utf8).unwrap(),
+            ScalarValue::Utf8(Some("it's".to_string()))
+        );
+        assert_eq!(
+            parse_key_value(
+                "guid'01234567-89ab-cdef-0123-456789abcdef'",
+                &DataType::LargeUtf8
+            )
+            .unwrap(),
+            ScalarValue::LargeUtf8(Some("01234567-89ab-cdef-0123-456789abcdef".to_string()))
+        );
+        assert_eq!(
+            parse_key_value(
+                "datetimeoffset'1999-11-02T00:00:00Z'",
+                &DataType::Timestamp(TimeUnit::Millisecond, utc.clone())
+            )
+            .unwrap(),
+            ScalarValue::TimestampMillisecond(Some(941_500_800_000), utc)
+        );
+        assert_eq!(
+            parse_key_value("datetime'1999-11-02'", &DataType::Date32).unwrap(),
+            ScalarValue::Date32(Some(10_897))
+        );
+        assert_eq!(
+            parse_key_value("binary'0aFF'", &DataType::Binary).unwrap(),
+            ScalarValue::Binary(Some(vec![0x0a, 0xff]))
+        );
+        assert_eq!(
+            parse_key_value("X'0AFF'", &DataType::FixedSizeBinary(2)).unwrap(),
+            ScalarValue::FixedSizeBinary(2, Some(vec![0x0a, 0xff]))
+        );
+
+        for (invalid, data_type) in [
+            ("abc", DataType::Utf8),
+            ("'a' 'b'", DataType::Utf8),
+            ("1", DataType::Utf8),
+            ("'1'", DataType::Int64),
+            ("null", DataType::Int64),
+            ("", DataType::Int64),
+            ("X'0AF'", DataType::Binary),
+            ("X'0AFG'", DataType::Binary),
+            ("X'0AFF'", DataType::FixedSizeBinary(3)),
+            ("X'0AFF'", DataType::Utf8),
+        ] {
+            assert!(
+                matches!(
+                    parse_key_value(invalid, &data_type),
+                    Err(ODataError::BadRequest(_))
+                ),
+                "{invalid}"
+            );
+        }
+    }
+}
@@ -0,0 +1,847 @@
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::{
+        array::{Array, ArrayRef, AsArray, BooleanArray, Float64Array},
+        datatypes::DataType,
+    },
+    common::{DataFusionError, internal_err},
+    logical_expr::{
+        ColumnarValue, ScalarFunctionArgs, ScalarUDF, ScalarUDFImpl, Signature, Volatility,
+    },
+};
+
+use crate::{
+    atom::{decode_binary, decode_encoded},
+    error::InvalidGeometry,
+};
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Spatial EDM type of a column, see
+/// [`crate::context::CollectionContext::spatial_columns`]
+///
+/// See: https://www.odata.org/documentation/odata-version-3-0/common-schema-definition-language-csdl/#csdl5.3
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpatialType {
+    /// Values on the round earth with longitude and latitude coordinates
+    Geography(SpatialShape),
+    /// Values in the flat-earth coordinate system
+    Geometry(SpatialShape),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpatialShape {
+    /// Value of any shape, i.e. `Edm.Geography` or `Edm.Geometry`
+    Any,
+    Point,
+    LineString,
+    Polygon,
+    MultiPoint,
+    MultiLineString,
+    MultiPolygon,
+    Collection,
+}
+
+const SHAPE_NAMES: [(SpatialShape, &str); 8] = [
+    (SpatialShape::Any, ""),
+    (SpatialShape::Point, "Point"),
+    (SpatialShape::LineString, "LineString"),
+    (SpatialShape::Polygon, "Polygon"),
+    (SpatialShape::MultiPoint, "MultiPoint"),
+    (SpatialShape::MultiLineString, "MultiLineString"),
+    (SpatialShape::MultiPolygon, "MultiPolygon"),
+    (SpatialShape::Collection, "Collection"),
+];
+
+impl SpatialType {
+    /// Name of the EDM type, e.g. `Edm.GeographyPoint`
+    pub fn edm_type(&self) -> String {
+        let (kind, shape) = match self {
+            Self::Geography(shape) => ("Geography", shape),
+            Self::Geometry(shape) => ("Geometry", shape),
+        };
+        let (_, name) = SHAPE_NAMES.iter().find(|(s, _)| s == shape).unwrap();
+        format!("Edm.{kind}{name}")
+    }
+
+    pub fn from_edm_type(edm_type: &str) -> Option<Self> {
+        let shape = |name: &str| {
+            SHAPE_NAMES
+                .iter()
+                .find(|(_, n)| *n == name)
+                .map(|(s, _)| *s)
+        };
+        if let Some(name) = edm_type.strip_prefix("Edm.Geography") {
+            shape(name).map(Self::Geography)
+        } else if let Some(name) = edm_type.strip_prefix("Edm.Geometry") {
+            shape(name).map(Self::Geometry)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_geography(&self) -> bool {
+        matches!(self, Self::Geography(_))
+    }
+
+    /// Default coordinate reference system of the type: WGS 84 for geography
+    /// and the unitless plane for geometry
+    pub fn srid(&self) -> u32 {
+        match self {
+            Self::Geography(_) => 4326,
+            Self::Geometry(_) => 0,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Position as `[x, y]`, i.e. `[longitude, latitude]` for geography
+pub type Coord = [f64; 2];
+
+/// Spatial value following the OGC simple features model. Only the first two
+/// dimensions of the coordinates are retained.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Geometry {
+    /// Point without coordinates is empty
+    Point(Option<Coord>),
+    LineString(Vec<Coord>),
+    /// Exterior ring followed by the interior ones
+    Polygon(Vec<Vec<Coord>>),
+    MultiPoint(Vec<Coord>),
+    MultiLineString(Vec<Vec<Coord>>),
+    MultiPolygon(Vec<Vec<Vec<Coord>>>),
+    GeometryCollection(Vec<Geometry>),
+}
+
+impl Geometry {
+    /// Decodes a non-null value of a binary column holding WKB or of a string
+    /// column holding WKT
+    pub fn decode(col: &ArrayRef, row: usize) -> Result<Self, InvalidGeometry> {
+        let (col, row) = decode_encoded(col, row);
+        match col.data_type() {
+            DataType::Utf8 => Self::from_wkt(col.as_string::<i32>().value(row)),
+            DataType::LargeUtf8 => Self::from_wkt(col.as_string::<i64>().value(row)),
+            DataType::Utf8View => Self::from_wkt(col.as_string_view().value(row)),
+            _ => Self::from_wkb(
+                decode_binary(&col, row).map_err(|e| InvalidGeometry::new(e.to_string()))?,
+            ),
+        }
+    }
+
+    /// Parses Well-Known Binary, including the extended PostGIS and ISO
+    /// variants with an SRID or Z and M coordinates
+    pub fn from_wkb(bytes: &[u8]) -> Result<Self, InvalidGeometry> {
+        let mut reader = WkbReader { bytes, pos: 0 };
+        let geometry = reader.geometry()?;
+        if reader.pos != bytes.len() {
+            return Err(InvalidGeometry::new("trailing bytes after WKB value"));
+        }
+        Ok(geometry)
+    }
+
+    /// Parses Well-Known Text with an optional `SRID=4326;` prefix
+    pub fn from_wkt(text: &str) -> Result<Self, InvalidGeometry> {
+        let text = match text.trim_start().split_once(';') {
+            Some((srid, text)) if srid.to_ascii_uppercase().starts_with("SRID=") => text,
+            _ => text,
+        };
+        let mut parser = WktParser {
+            chars: text.chars().collect(),
+            pos: 0,
+        };
+        let geometry = parser.geometry()?;
+        parser.skip_whitespace();
+        if parser.pos != parser.chars.len() {
+            return Err(InvalidGeometry::new(format!("unexpected WKT text: {text}")));
+        }
+        Ok(geometry)
+    }
+
+    /// Name of the type as in GeoJSON, e.g. `MultiPolygon`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Point(_) => "Point",
+            Self::LineString(_) => "LineString",
+            Self::Polygon(_) => "Polygon",
+            Self::MultiPoint(_) => "MultiPoint",
+            Self::MultiLineString(_) => "MultiLineString",
+            Self::MultiPolygon(_) => "MultiPolygon",
+            Self::GeometryCollection(_) => "GeometryCollection",
+        }
+    }
+
+    /// Distance between two points: great-circle distance in meters for
+    /// geography and Euclidean distance for geometry. Other shapes and empty
+    /// points have no distance.
+    pub fn distance(&self, other: &Self, geodesic: bool) -> Option<f64> {
+        let (Self::Point(Some(a)), Self::Point(Some(b))) = (self, other) else {
+            return None;
+        };
+        if !geodesic {
+            return Some((a[0] - b[0]).hypot(a[1] - b[1]));
+        }
+
+        let (lon1, lat1) = (a[0].to_radians(), a[1].to_radians());
+        let (lon2, lat2) = (b[0].to_radians(), b[1].to_radians());
+        let h = ((lat2 - lat1) / 2.0).sin().powi(2)
+            + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+        Some(2.0 * EARTH_RADIUS * h.sqrt().asin())
+    }
+
+    /// Whether two values share at least one point. Coordinates are treated as
+    /// planar for geography as well, so edges follow the lines of the
+    /// longitude / latitude grid rather than great circles.
+    pub fn intersects(&self, other: &Self) -> bool {
+        let (mut a, mut b) = (Parts::default(), Parts::default());
+        self.collect_parts(&mut a);
+        other.collect_parts(&mut b);
+
+        a.segments
+            .iter()
+            .any(|s| b.segments.iter().any(|t| segments_intersect(*s, *t)))
+            || a.vertices
+                .iter()
+                .any(|v| b.polygons.iter().any(|p| polygon_contains(p, *v)))
+            || b.vertices
+                .iter()
+                .any(|v| a.polygons.iter().any(|p| polygon_contains(p, *v)))
+    }
+
+    fn collect_parts<'a>(&'a self, parts: &mut Parts<'a>) {
+        match self {
+            Self::Point(None) => {}
+            Self::Point(Some(c)) => parts.add_line(std::slice::from_ref(c)),
+            Self::MultiPoint(coords) => coords
+                .iter()
+                .for_each(|c| parts.add_line(std::slice::from_ref(c))),
+            Self::LineString(line) => parts.add_line(line),
+            Self::MultiLineString(lines) => lines.iter().for_each(|l| parts.add_line(l)),
+            Self::Polygon(rings) => parts.add_polygon(rings),
+            Self::MultiPolygon(polygons) => polygons.iter().for_each(|p| parts.add_polygon(p)),
+            Self::GeometryCollection(members) => {
+                members.iter().for_each(|m| m.collect_parts(parts))
+            }
+        }
+    }
+}
+
+/// Mean radius of the earth in meters
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Geometry broken down into the parts the intersection test operates on.
+/// Points are degenerate segments.
+#[derive(Default)]
+struct Parts<'a> {
+    segments: Vec<(Coord, Coord)>,
+    polygons: Vec<&'a [Vec<Coord>]>,
+    /// One vertex of every line and polygon, used to test whether they are
+    /// contained by a polygon without crossing its boundary
+    vertices: Vec<Coord>,
+}
+
+impl<'a> Parts<'a> {
+    fn add_line(&mut self, line: &[Coord]) {
+        match line {
+            [] => {}
+            [c] => self.segments.push((*c, *c)),
+            _ => self
+                .segments
+                .extend(line.windows(2).map(|pair| (pair[0], pair[1]))),
+        }
+        self.vertices.extend(line.first());
+    }
+
+    fn add_polygon(&mut self, rings: &'a [Vec<Coord>]) {
+        rings.iter().for_each(|ring| self.add_line(ring));
+        if !rings.is_empty() {
+            self.polygons.push(rings);
+        }
+    }
+}
+
+/// Cross product sign of `p` relative to the line through `a` and `b`
+fn orientation(a: Coord, b: Coord, p: Coord) -> f64 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+fn on_segment(p: Coord, (a, b): (Coord, Coord)) -> bool {
+    orientation(a, b, p) == 0.0
+        && p[0] >= a[0].min(b[0])
+        && p[0] <= a[0].max(b[0])
+        && p[1] >= a[1].min(b[1])
+        && p[1] <= a[1].max(b[1])
+}
+
+fn segments_intersect(s: (Coord, Coord), t: (Coord, Coord)) -> bool {
+    let d1 = orientation(t.0, t.1, s.0);
+    let d2 = orientation(t.0, t.1, s.1);
+    let d3 = orientation(s.0, s.1, t.0);
+    let d4 = orientation(s.0, s.1, t.1);
+    if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+        return true;
+    }
+    on_segment(s.0, t) || on_segment(s.1, t) || on_segment(t.0, s) || on_segment(t.1, s)
+}
+
+/// Whether the point is in the interior of the polygon, points on the boundary
+/// are covered by the segment test
+fn polygon_contains(rings: &[Vec<Coord>], p: Coord) -> bool {
+    let Some((exterior, interiors)) = rings.split_first() else {
+        return false;
+    };
+    ring_contains(exterior, p) && !interiors.iter().any(|ring| ring_contains(ring, p))
+}
+
+// Ray casting, rings may be closed or not
+fn ring_contains(ring: &[Coord], p: Coord) -> bool {
+    let mut inside = false;
+    for (a, b) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+        if (a[1] > p[1]) != (b[1] > p[1])
+            && p[0] < (b[0] - a[0]) * (p[1] - a[1]) / (b[1] - a[1]) + a[0]
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+// See: https://libgeos.org/specifications/wkb/
+struct WkbReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl WkbReader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], InvalidGeometry> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + N)
+            .ok_or_else(|| InvalidGeometry::new("unexpected end of WKB value"))?;
+        self.pos += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u32(&mut self, little_endian: bool) -> Result<u32, InvalidGeometry> {
+        let bytes = self.take::<4>()?;
+        Ok(match little_endian {
+            true => u32::from_le_bytes(bytes),
+            false => u32::from_be_bytes(bytes),
+        })
+    }
+
+    fn coord(&mut self, little_endian: bool, dims: usize) -> Result<Coord, InvalidGeometry> {
+        let mut values = [0.0; 4];
+        for value in values.iter_mut().take(dims) {
+            let bytes = self.take::<8>()?;
+            *value = match little_endian {
+                true => f64::from_le_bytes(bytes),
+                false => f64::from_be_bytes(bytes),
+            };
+        }
+        Ok([values[0], values[1]])
+    }
+
+    fn coords(&mut self, little_endian: bool, dims: usize) -> Result<Vec<Coord>, InvalidGeometry> {
+        let count = self.u32(little_endian)?;
+        (0..count)
+            .map(|_| self.coord(little_endian, dims))
+            .collect()
+    }
+
+    fn members<T>(
+        &mut self,
+        little_endian: bool,
+        member: impl Fn(Geometry) -> Option<T>,
+    ) -> Result<Vec<T>, InvalidGeometry> {
+        let count = self.u32(little_endian)?;
+        (0..count)
+            .map(|_| {
+                let geometry = self.geometry()?;
+                let type_name = geometry.type_name();
+                member(geometry).ok_or_else(|| {
+                    InvalidGeometry::new(format!("unexpected {type_name} member in WKB value"))
+                })
+            })
+            .collect()
+    }
+
+    fn geometry(&mut self) -> Result<Geometry, InvalidGeometry> {
+        let little_endian = match self.take::<1>()? {
+            [0] => false,
+            [1] => true,
+            [b] => return Err(InvalidGeometry::new(format!("invalid WKB byte order {b}"))),
+        };
+        let typ = self.u32(little_endian)?;
+
+        // Extended WKB flags
+        let mut dims = 2;
+        if typ & 0x8000_0000 != 0 {
+            dims += 1;
+        }
+        if typ & 0x4000_0000 != 0 {
+            dims += 1;
+        }
+        if typ & 0x2000_0000 != 0 {
+            self.u32(little_endian)?;
+        }
+        // ISO WKB dimensions
+        let typ = typ & 0x0fff_ffff;
+        dims += match typ / 1000 {
+            0 => 0,
+            1 | 2 => 1,
+            3 => 2,
+            _ => return Err(InvalidGeometry::new(format!("invalid WKB type {typ}"))),
+        };
+
+        match typ % 1000 {
+            1 => {
+                let coord = self.coord(little_endian, dims)?;
+                Ok(Geometry::Point(
+                    (!coord.iter().all(|v| v.is_nan())).then_some(coord),
+                ))
+            }
+            2 => Ok(Geometry::LineString(self.coords(little_endian, dims)?)),
+            3 => {
+                let count = self.u32(little_endian)?;
+                let rings = (0..count)
+                    .map(|_| self.coords(little_endian, dims))
+                    .collect::<Result<_, _>>()?;
+                Ok(Geometry::Polygon(rings))
+            }
+            4 => Ok(Geometry::MultiPoint(self.members(
+                little_endian,
+                |g| match g {
+                    Geometry::Point(Some(c)) => Some(c),
+                    _ => None,
+                },
+            )?)),
+            5 => Ok(Geometry::MultiLineString(self.members(
+                little_endian,
+                |g| match g {
+                    Geometry::LineString(l) => Some(l),
+                    _ => None,
+                },
+            )?)),
+            6 => Ok(Geometry::MultiPolygon(self.members(
+                little_endian,
+                |g| match g {
+                    Geometry::Polygon(p) => Some(p),
+                    _ => None,
+                },
+            )?)),
+            7 => Ok(Geometry::GeometryCollection(
+                self.members(little_endian, Some)?,
+            )),
+            _ => Err(InvalidGeometry::new(format!("invalid WKB type {typ}"))),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+// See: https://libgeos.org/specifications/wkt/
+struct WktParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl WktParser {
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn unexpected(&self) -> InvalidGeometry {
+        let rest: String = self.chars[self.pos.min(self.chars.len())..]
+            .iter()
+            .collect();
+        InvalidGeometry::new(format!("unexpected WKT text: {rest}"))
+    }
+
+    /// Consumes a keyword returning it in upper case
+    fn word(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self
+            .chars
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_alphabetic())
+        {
+            self.pos += 1;
+        }
+        let word: String = self.chars[start..self.pos].iter().collect();
+        (!word.is_empty()).then(|| word.to_ascii_uppercase())
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        let found = self.chars.get(self.pos) == Some(&c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), InvalidGeometry> {
+        match self.eat(c) {
+            true => Ok(()),
+            false => Err(self.unexpected()),
+        }
+    }
+
+    /// Parenthesized comma-separated list
+    fn list<T>(
+        &mut self,
+        item: impl Fn(&mut Self) -> Result<T, InvalidGeometry>,
+    ) -> Result<Vec<T>, InvalidGeometry> {
+        self.expect('(')?;
+        let mut items = vec![item(self)?];
+        while self.eat(',') {
+            items.push(item(self)?);
+        }
+        self.expect(')')?;
+        Ok(items)
+    }
+
+    fn number(&mut self) -> Option<f64> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self
+            .chars
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        match text.parse() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                self.pos = start;
+                None
+            }
+        }
+    }
+
+    /// Position of two to four ordinates, e.g. `-122.3 47.6`
+    fn coord(&mut self) -> Result<Coord, InvalidGeometry> {
+        let mut values = Vec::new();
+        while let Some(value) = self.number() {
+            values.push(value);
+        }
+        match values[..] {
+            [x, y] | [x, y, _] | [x, y, _, _] => Ok([x, y]),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn coords(&mut self) -> Result<Vec<Coord>, InvalidGeometry> {
+        self.list(Self::coord)
+    }
+
+    fn rings(&mut self) -> Result<Vec<Vec<Coord>>, InvalidGeometry> {
+        self.list(Self::coords)
+    }
+
+    /// Member of a multipoint, parenthesized or not
+    fn point_member(&mut self) -> Result<Coord, InvalidGeometry> {
+        if !self.eat('(') {
+            return self.coord();
+        }
+        let coord = self.coord()?;
+        self.expect(')')?;
+        Ok(coord)
+    }
+
+    fn geometry(&mut self) -> Result<Geometry, InvalidGeometry> {
+        let tag = self.word().ok_or_else(|| self.unexpected())?;
+
+        let checkpoint = self.pos;
+        let mut word = self.word();
+        if matches!(word.as_deref(), Some("Z" | "M" | "ZM")) {
+            word = self.word();
+        } else if word.as_deref() != Some("EMPTY") {
+            self.pos = checkpoint;
+        }
+        let empty = word.as_deref() == Some("EMPTY");
+
+        Ok(match tag.as_str() {
+            "POINT" if empty => Geometry::Point(None),
+            "POINT" => {
+                self.expect('(')?;
+                let coord = self.coord()?;
+                self.expect(')')?;
+                Geometry::Point(Some(coord))
+            }
+            "LINESTRING" if empty => Geometry::LineString(Vec::new()),
+            "LINESTRING" => Geometry::LineString(self.coords()?),
+            "POLYGON" if empty => Geometry::Polygon(Vec::new()),
+            "POLYGON" => Geometry::Polygon(self.rings()?),
+            "MULTIPOINT" if empty => Geometry::MultiPoint(Vec::new()),
+            "MULTIPOINT" => Geometry::MultiPoint(self.list(Self::point_member)?),
+            "MULTILINESTRING" if empty => Geometry::MultiLineString(Vec::new()),
+            "MULTILINESTRING" => Geometry::MultiLineString(self.rings()?),
+            "MULTIPOLYGON" if empty => Geometry::MultiPolygon(Vec::new()),
+            "MULTIPOLYGON" => Geometry::MultiPolygon(self.list(Self::rings)?),
+            "GEOMETRYCOLLECTION" if empty => Geometry::GeometryCollection(Vec::new()),
+            "GEOMETRYCOLLECTION" => Geometry::GeometryCollection(self.list(Self::geometry)?),
+            _ => {
+                return Err(InvalidGeometry::new(format!("unsupported WKT type: {tag}")));
+            }
+        })
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Spatial functions evaluated over WKB and WKT values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpatialFunction {
+    /// `geo.distance` of geography points in meters
+    GeographyDistance,
+    /// `geo.distance` of geometry points
+    GeometryDistance,
+    /// `geo.intersects`
+    Intersects,
+}
+
+impl SpatialFunction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::GeographyDistance => "geography_distance",
+            Self::GeometryDistance => "geometry_distance",
+            Self::Intersects => "geo_intersects",
+        }
+    }
+
+    pub fn udf(self) -> ScalarUDF {
+        ScalarUDF::new_from_impl(SpatialUdf {
+            function: self,
+            signature: Signature::any(2, Volatility::Immutable),
+        })
+    }
+}
+
+/// UDFs of all spatial functions, register them in the session context to use
+/// the functions in SQL
+pub fn spatial_udfs() -> Vec<ScalarUDF> {
+    [
+        SpatialFunction::GeographyDistance,
+        SpatialFunction::GeometryDistance,
+        SpatialFunction::Intersects,
+    ]
+    .into_iter()
+    .map(SpatialFunction::udf)
+    .collect()
+}
+
+/// Evaluates a spatial function over two arguments, which are either binary
+/// WKB or string WKT values. Result is null when any of the arguments is null.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct SpatialUdf {
+    function: SpatialFunction,
+    signature: Signature,
+}
+
+impl ScalarUDFImpl for SpatialUdf {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        self.function.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> datafusion::error::Result<DataType> {
+        match self.function {
+            SpatialFunction::Intersects => Ok(DataType::Boolean),
+            _ => Ok(DataType::Float64),
+        }
+    }
+
+    fn invoke_with_args(
+        &self,
+        args: ScalarFunctionArgs,
+    ) -> datafusion::error::Result<ColumnarValue> {
+        let [a, b] = args.args.as_slice() else {
+            return internal_err!("Function {} requires two arguments", self.name());
+        };
+        let a = a.clone().into_array(args.number_rows)?;
+        let b = b.clone().into_array(args.number_rows)?;
+
+        let decode = |col: &ArrayRef, row: usize| {
+            let (col, row) = decode_encoded(col, row);
+            match col.is_null(row) {
+                true => Ok(None),
+                false => Geometry::decode(&col, row)
+                    .map(Some)
+                    .map_err(|e| DataFusionError::External(e.into())),
+            }
+        };
+
+        let mut distances = Vec::new();
+        let mut intersections = Vec::new();
+        for row in 0..args.number_rows {
+            let (Some(a), Some(b)) = (decode(&a, row)?, decode(&b, row)?) else {
+                distances.push(None);
+                intersections.push(None);
+                continue;
+            };
+            match self.function {
+                SpatialFunction::GeographyDistance => distances.push(a.distance(&b, true)),
+                SpatialFunction::GeometryDistance => distances.push(a.distance(&b, false)),
+                SpatialFunction::Intersects => intersections.push(Some(a.intersects(&b))),
+            }
+        }
+
+        let result: ArrayRef = match self.function {
+            SpatialFunction::Intersects => Arc::new(BooleanArray::from(intersections)),
+            _ => Arc::new(Float64Array::from(distances)),
+        };
+        Ok(ColumnarValue::Array(result))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wkt() {
+        assert_eq!(
+            Geometry::from_wkt("SRID=4326;POINT(-123.1 49.25)").unwrap(),
+            Geometry::Point(Some([-123.1, 49.25]))
+        );
+        assert_eq!(
+            Geometry::from_wkt("point z (1 2 3)").unwrap(),
+            Geometry::Point(Some([1.0, 2.0]))
+        );
+        assert_eq!(
+            Geometry::from_wkt("POINT EMPTY").unwrap(),
+            Geometry::Point(None)
+        );
+        assert_eq!(
+            Geometry::from_wkt("MULTIPOINT (1 2, (3 4))").unwrap(),
+            Geometry::MultiPoint(vec![[1.0, 2.0], [3.0, 4.0]])
+        );
+        assert_eq!(
+            Geometry::from_wkt("POLYGON((0 0, 4 0, 4 4, 0 0), (1 1, 2 1, 2 2, 1 1))").unwrap(),
+            Geometry::Polygon(vec![
+                vec![[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 0.0]],
+                vec![[1.0, 1.0], [2.0, 1.0], [2.0, 2.0], [1.0, 1.0]],
+            ])
+        );
+        assert_eq!(
+            Geometry::from_wkt("GEOMETRYCOLLECTION(POINT(1 2), LINESTRING(0 0, 1e1 -1.5))")
+                .unwrap(),
+            Geometry::GeometryCollection(vec![
+                Geometry::Point(Some([1.0, 2.0])),
+                Geometry::LineString(vec![[0.0, 0.0], [10.0, -1.5]]),
+            ])
+        );
+
+        for invalid in ["POINT(1)", "POINT(1 2", "CIRCLE(1 2)", "POINT(1 2) x", ""] {
+            assert!(Geometry::from_wkt(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_wkb() {
+        let point = |little_endian: bool, x: f64, y: f64| {
+            let mut wkb = vec![little_endian as u8];
+            if little_endian {
+                wkb.extend(1u32.to_le_bytes());
+                wkb.extend(x.to_le_bytes());
+                wkb.extend(y.to_le_bytes());
+            } else {
+                wkb.extend(1u32.to_be_bytes());
+                wkb.extend(x.to_be_bytes());
+                wkb.extend(y.to_be_bytes());
+            }
+            wkb
+        };
+
+        assert_eq!(
+            Geometry::from_wkb(&point(true, 1.0, 2.0)).unwrap(),
+            Geometry::Point(Some([1.0, 2.0]))
+        );
+        assert_eq!(
+            Geometry::from_wkb(&point(false, 1.0, 2.0)).unwrap(),
+            Geometry::Point(Some([1.0, 2.0]))
+        );
+        assert_eq!(
+            Geometry::from_wkb(&point(true, f64::NAN, f64::NAN)).unwrap(),
+            Geometry::Point(None)
+        );
+
+        // Multipoint with a point in the other byte order
+        let mut wkb = vec![1];
+        wkb.extend(4u32.to_le_bytes());
+        wkb.extend(2u32.to_le_bytes());
+        wkb.extend(point(true, 1.0, 2.0));
+        wkb.extend(point(false, 3.0, 4.0));
+        assert_eq!(
+            Geometry::from_wkb(&wkb).unwrap(),
+            Geometry::MultiPoint(vec![[1.0, 2.0], [3.0, 4.0]])
+        );
+
+        // EWKB point with SRID and Z coordinate
+        let mut wkb = vec![1];
+        wkb.extend(0xa000_0001u32.to_le_bytes());
+        wkb.extend(4326u32.to_le_bytes());
+        for v in [1.0f64, 2.0, 3.0] {
+            wkb.extend(v.to_le_bytes());
+        }
+        assert_eq!(
+            Geometry::from_wkb(&wkb).unwrap(),
+            Geometry::Point(Some([1.0, 2.0]))
+        );
+
+        for invalid in [&point(true, 1.0, 2.0)[..20], &[2, 1, 0, 0, 0], &[]] {
+            assert!(Geometry::from_wkb(invalid).is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn test_spatial_predicates() {
+        let wkt = |s: &str| Geometry::from_wkt(s).unwrap();
+
+        let vancouver = wkt("POINT(-123.1207 49.2827)");
+        let seattle = wkt("POINT(-122.3321 47.6062)");
+        let distance = vancouver.distance(&seattle, true).unwrap();
+        assert!((distance - 195_600.0).abs() < 1_000.0, "{distance}");
+        assert_eq!(
+            wkt("POINT(0 0)").distance(&wkt("POINT(3 4)"), false),
+            Some(5.0)
+        );
+        assert_eq!(wkt("POINT(0 0)").distance(&wkt("POINT EMPTY"), false), None);
+
+        let square = wkt("POLYGON((0 0, 4 0, 4 4, 0 4, 0 0), (1 1, 2 1, 2 2, 1 2, 1 1))");
+        for (other, expected) in [
+            ("POINT(3 3)", true),
+            ("POINT(4 2)", true),
+            ("POINT(1.5 1.5)", false),
+            ("POINT(5 5)", false),
+            ("LINESTRING(-1 2, 5 2)", true),
+            ("LINESTRING(5 0, 5 4)", false),
+            ("POLYGON((3 3, 3.5 3, 3.5 3.5, 3 3))", true),
+            ("POLYGON((-1 -1, 5 -1, 5 5, -1 5, -1 -1))", true),
+            ("MULTIPOINT((5 5), (3 3))", true),
+            ("GEOMETRYCOLLECTION(POINT(5 5))", false),
+        ] {
+            assert_eq!(square.intersects(&wkt(other)), expected, "{other}");
+            assert_eq!(wkt(other).intersects(&square), expected, "{other}");
+        }
+    }
+}
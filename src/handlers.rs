@@ -1,15 +1,39 @@
 use std::sync::Arc;
 
-use axum::{Extension, extract::Query, response::Response};
+use axum::{
+    Extension,
+    body::{Body, Bytes},
+    extract::Query,
+    response::Response,
+};
+use datafusion::{
+    arrow::{
+        array::{Array, ArrayRef, AsArray, RecordBatch},
+        datatypes::{DataType, Int64Type},
+    },
+    dataframe::DataFrame,
+    execution::SendableRecordBatchStream,
+};
+use futures::StreamExt;
 
 use crate::{
-    collection::QueryParamsRaw,
-    context::{CollectionContext, DEFAULT_NAMESPACE, OnUnsupported, ServiceContext},
-    error::{ODataError, UnsupportedDataType},
+    atom::{decode_binary, decode_encoded, encode_primitive_dyn},
+    collection::{CollectionAddr, KeyPredicate, QueryParams, QueryParamsRaw, SkipToken},
+    context::{
+        CollectionContext, DEFAULT_NAMESPACE, Multiplicity, OnUnsupported, Relationship,
+        ServiceContext,
+    },
+    encoder::{CollectionInfo, EntryLayout, FeedWriter, ResponseEncoder},
+    error::{ODataError, PropertyNotFound},
+    filter::parse_key_value,
+    json::{JsonFormat, JsonMetadata},
     metadata::{
-        DataServices, Edmx, EntityContainer, EntityKey, EntitySet, EntityType, Property,
-        PropertyRef, to_edm_type,
+        Association, AssociationEnd, AssociationSet, AssociationSetEnd, DataServices, Edmx,
+        EntityContainer, EntityKey, EntitySet, EntityType, EnumType, NavigationProperty,
+        PropertyRef, to_enum_property, to_property, to_spatial_property,
     },
+    navigation::{Expansion, NavigationTarget, RelatedEntries, adjust_select},
+    negotiation::{FormatQueryParam, ResponseFormat, negotiate_encoder, negotiate_format},
     service::{Collection, Service, Workspace},
 };
 
@@ -17,6 +41,15 @@ use crate::{
 
 pub const MEDIA_TYPE_ATOM: &str = "application/atom+xml;type=feed;charset=utf-8";
 pub const MEDIA_TYPE_XML: &str = "application/xml;charset=utf-8";
+pub const MEDIA_TYPE_TEXT: &str = "text/plain;charset=utf-8";
+pub const MEDIA_TYPE_BINARY: &str = "application/octet-stream";
+pub const MEDIA_TYPE_JSON_VERBOSE: &str = "application/json;odata=verbose;charset=utf-8";
+pub const MEDIA_TYPE_JSON_METADATA_NONE: &str =
+    "application/json;odata.metadata=none;charset=utf-8";
+pub const MEDIA_TYPE_JSON_METADATA_MINIMAL: &str =
+    "application/json;odata.metadata=minimal;charset=utf-8";
+pub const MEDIA_TYPE_JSON_METADATA_FULL: &str =
+    "application/json;odata.metadata=full;charset=utf-8";
 
 const DEFAULT_COLLECTION_RESPONSE_SIZE: usize = 512_000;
 
@@ -24,7 +57,19 @@ const DEFAULT_COLLECTION_RESPONSE_SIZE: usize = 512_000;
 
 pub async fn odata_service_handler(
     Extension(odata_ctx): Extension<Arc<dyn ServiceContext>>,
+    Query(query): Query<FormatQueryParam>,
+    headers: axum::http::HeaderMap,
 ) -> Result<Response<String>, ODataError> {
+    let format = negotiate_format(
+        query.format.as_deref(),
+        &headers,
+        &[
+            ResponseFormat::Xml,
+            ResponseFormat::Json(JsonFormat::V4(JsonMetadata::Minimal)),
+            ResponseFormat::Json(JsonFormat::Verbose),
+        ],
+    )?;
+
     let mut collections = Vec::new();
 
     for coll in odata_ctx.list_collections().await? {
@@ -42,11 +87,17 @@ pub async fn odata_service_handler(
         },
     );
 
-    let xml = write_object_to_xml("service", &service)?;
+    let body = match format {
+        ResponseFormat::Json(json_format) => {
+            let mut buf = Vec::new();
+            crate::json::write_json_service_document(&service, json_format, &mut buf)?;
+            String::from_utf8(buf)?
+        }
+        _ => write_object_to_xml("service", &service)?,
+    };
 
-    Response::builder()
-        .header(http::header::CONTENT_TYPE.as_str(), MEDIA_TYPE_XML)
-        .body(xml)
+    response_builder(format)
+        .body(body)
         .map_err(ODataError::internal)
 }
 
@@ -54,25 +105,52 @@ pub async fn odata_service_handler(
 
 pub async fn odata_metadata_handler(
     Extension(odata_ctx): Extension<Arc<dyn ServiceContext>>,
+    Query(query): Query<FormatQueryParam>,
+    headers: axum::http::HeaderMap,
 ) -> Result<Response<String>, ODataError> {
+    // CSDL is only available in XML
+    let format = negotiate_format(query.format.as_deref(), &headers, &[ResponseFormat::Xml])?;
+
+    let relationships = odata_ctx.relationships();
+
     let mut entity_types = Vec::new();
+    let mut complex_types = Vec::new();
+    let mut enum_types: Vec<EnumType> = Vec::new();
+    let mut associations = Vec::new();
     let mut entity_container = EntityContainer {
         name: DEFAULT_NAMESPACE.to_string(),
         is_default: true,
         entity_set: Vec::new(),
+        association_set: Vec::new(),
     };
 
     for coll in odata_ctx.list_collections().await? {
         let collection_name = coll.collection_name()?;
+        let enum_columns = coll.enum_columns();
+        let spatial_columns = coll.spatial_columns();
         let mut properties = Vec::new();
 
         for field in coll.schema().await?.fields() {
-            let typ = match to_edm_type(field.data_type()) {
-                Ok(typ) => typ,
+            let enum_column = enum_columns.iter().find(|e| e.column == *field.name());
+            let spatial_column = spatial_columns.iter().find(|s| s.column == *field.name());
+            let property = match (enum_column, spatial_column) {
+                (Some(enum_column), _) => {
+                    to_enum_property(field, DEFAULT_NAMESPACE, &enum_column.type_name)
+                }
+                (None, Some(spatial_column)) => {
+                    to_spatial_property(field, spatial_column.spatial_type)
+                }
+                (None, None) => to_property(
+                    field,
+                    &collection_name,
+                    DEFAULT_NAMESPACE,
+                    &mut complex_types,
+                ),
+            };
+            let property = match property {
+                Ok(property) => property,
                 Err(err) => match odata_ctx.on_unsupported_feature() {
-                    OnUnsupported::Error => {
-                        Err(UnsupportedDataType::new(field.data_type().clone()))?
-                    }
+                    OnUnsupported::Error => return Err(err.into()),
                     OnUnsupported::Warn => {
                         tracing::error!(
                             table = collection_name,
@@ -86,15 +164,25 @@ pub async fn odata_metadata_handler(
                 },
             };
 
-            properties.push(Property::primitive(field.name(), typ, field.is_nullable()));
+            // Enum types are shared by the collections declaring the same name
+            if let Some(enum_column) = enum_column
+                && !enum_types.iter().any(|t| t.name == enum_column.type_name)
+            {
+                enum_types.push(EnumType::new(
+                    &enum_column.type_name,
+                    enum_column.members.clone(),
+                )?);
+            }
+
+            properties.push(property);
         }
 
         // https://www.odata.org/documentation/odata-version-3-0/common-schema-definition-language-csdl/#csdl6.3
-        let property_ref_name = match coll.key_column() {
+        let key_columns = match coll.key_columns() {
             Ok(kc) => kc,
             Err(ODataError::KeyColumnNotAssigned(_)) => match properties.first() {
-                Some(prop) => prop.name.clone(),
-                None => collection_name.to_string(),
+                Some(prop) => vec![prop.name.clone()],
+                None => vec![collection_name.to_string()],
             },
             Err(err) => {
                 tracing::error!(
@@ -107,12 +195,30 @@ pub async fn odata_metadata_handler(
             }
         };
 
+        let navigation_properties = relationships
+            .iter()
+            .filter(|rel| rel.from_collection == collection_name)
+            .map(|rel| {
+                let (from_role, to_role) = association_roles(rel);
+                NavigationProperty {
+                    name: rel.name.clone(),
+                    relationship: format!("{DEFAULT_NAMESPACE}.{}", association_name(rel)),
+                    from_role,
+                    to_role,
+                }
+            })
+            .collect();
+
         entity_types.push(EntityType {
             name: collection_name.clone(),
-            key: EntityKey::new(vec![PropertyRef {
-                name: property_ref_name,
-            }]),
+            key: EntityKey::new(
+                key_columns
+                    .into_iter()
+                    .map(|name| PropertyRef { name })
+                    .collect(),
+            ),
             properties,
+            navigation_properties,
         });
 
         entity_container.entity_set.push(EntitySet {
@@ -121,107 +227,598 @@ pub async fn odata_metadata_handler(
         });
     }
 
+    // https://www.odata.org/documentation/odata-version-3-0/common-schema-definition-language-csdl/#csdl7
+    for rel in &relationships {
+        let name = association_name(rel);
+        let (from_role, to_role) = association_roles(rel);
+
+        // Relationships are declared from the source side only, so the source
+        // end is assumed to be optional for to-many relationships
+        let from_multiplicity = match rel.multiplicity {
+            Multiplicity::Many => Multiplicity::ZeroOrOne,
+            Multiplicity::ZeroOrOne | Multiplicity::One => Multiplicity::Many,
+        };
+
+        associations.push(Association {
+            name: name.clone(),
+            ends: vec![
+                AssociationEnd {
+                    typ: format!("{DEFAULT_NAMESPACE}.{}", rel.from_collection),
+                    role: from_role.clone(),
+                    multiplicity: from_multiplicity.as_str().to_string(),
+                },
+                AssociationEnd {
+                    typ: format!("{DEFAULT_NAMESPACE}.{}", rel.to_collection),
+                    role: to_role.clone(),
+                    multiplicity: rel.multiplicity.as_str().to_string(),
+                },
+            ],
+        });
+
+        entity_container.association_set.push(AssociationSet {
+            association: format!("{DEFAULT_NAMESPACE}.{name}"),
+            name,
+            ends: vec![
+                AssociationSetEnd {
+                    role: from_role,
+                    entity_set: rel.from_collection.clone(),
+                },
+                AssociationSetEnd {
+                    role: to_role,
+                    entity_set: rel.to_collection.clone(),
+                },
+            ],
+        });
+    }
+
     let metadata = Edmx::new(DataServices::new(vec![crate::metadata::Schema::new(
         DEFAULT_NAMESPACE.to_string(),
         entity_types,
+        complex_types,
+        enum_types,
+        associations,
         vec![entity_container],
     )]));
 
     let xml = write_object_to_xml("edmx:Edmx", &metadata)?;
 
-    Response::builder()
-        .header(http::header::CONTENT_TYPE.as_str(), MEDIA_TYPE_XML)
+    response_builder(format)
         .body(xml)
         .map_err(ODataError::internal)
 }
 
+fn association_name(rel: &Relationship) -> String {
+    format!("{}_{}", rel.from_collection, rel.name)
+}
+
+/// Roles are named after the collections unless the relationship refers to
+/// the same collection
+fn association_roles(rel: &Relationship) -> (String, String) {
+    if rel.from_collection == rel.to_collection {
+        (rel.from_collection.clone(), rel.name.clone())
+    } else {
+        (rel.from_collection.clone(), rel.to_collection.clone())
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
 pub async fn odata_collection_handler(
     Extension(ctx): Extension<Arc<dyn CollectionContext>>,
     Query(query): Query<QueryParamsRaw>,
-    _headers: axum::http::HeaderMap,
-) -> Result<Response<String>, ODataError> {
-    let query = query.decode()?;
+    headers: axum::http::HeaderMap,
+) -> Result<Response<Body>, ODataError> {
+    // Original query options are preserved in the next page links
+    let raw_query = query.clone();
+
+    let addr = ctx.addr()?.clone();
+    if let Some(key) = &addr.key {
+        validate_key(ctx.as_ref(), key).await?;
+    }
+
+    // Navigation from an entry addresses entries of the related collection
+    let (ctx, resource_url, navigation_filter, single_entry) = match &addr.property {
+        Some(name) if !ctx.relationships().iter().any(|rel| rel.name == *name) => {
+            return property_response(
+                ctx.as_ref(),
+                &addr,
+                name,
+                raw_query.format.as_deref(),
+                &headers,
+            )
+            .await;
+        }
+        Some(_) if addr.value => {
+            return Err(ODataError::bad_request(
+                "Navigation properties have no raw value",
+            ));
+        }
+        None => {
+            let resource_url = ctx.collection_base_url()?.trim_end_matches('/').to_string();
+            (ctx, resource_url, None, addr.key.is_some())
+        }
+        Some(name) => match NavigationTarget::resolve(ctx.as_ref(), name).await? {
+            Some(target) => (
+                target.ctx,
+                target.url,
+                Some(target.filter),
+                !target.relationship.to_many(),
+            ),
+            None => {
+                return Response::builder()
+                    .status(http::StatusCode::NOT_FOUND)
+                    .body(Body::empty())
+                    .map_err(ODataError::internal);
+            }
+        },
+    };
+
+    let schema = ctx.schema().await?;
+    let mut query = query.decode(&schema, &ctx.enum_columns())?;
+    query.page_size = ctx.max_page_size();
+    if let Some(navigation_filter) = navigation_filter {
+        query.filter = Some(match query.filter {
+            Some(filter) => filter.and(navigation_filter),
+            None => navigation_filter,
+        });
+    }
     tracing::debug!(?query, "Decoded query");
 
+    let navigation = ctx.relationships();
+    let expand = Expansion::resolve(ctx.as_ref(), &navigation, &query.expand).await?;
+    let mut hidden_columns = adjust_select(&mut query.select, &navigation, &expand);
+
+    // Skip token of the next page carries values of the ordering columns, so
+    // they are selected even when not requested and hidden from the entries
+    if (query.page_size.is_some() || query.skip_token.is_some()) && !query.select.is_empty() {
+        for (column, _) in &query.order_by {
+            if !query.select.contains(column) {
+                query.select.push(column.clone());
+                hidden_columns.push(column.clone());
+            }
+        }
+    }
+
+    // Raw count value is always returned as plain text
+    if addr.count {
+        let count = count_records(ctx.as_ref(), query).await?;
+        return Response::builder()
+            .header(http::header::CONTENT_TYPE.as_str(), MEDIA_TYPE_TEXT)
+            .body(Body::from(count.to_string()))
+            .map_err(ODataError::internal);
+    }
+
+    let encoder = negotiate_encoder(
+        raw_query.format.as_deref(),
+        &headers,
+        &ctx.response_encoders(),
+    )?;
+
+    let info = CollectionInfo::new(ctx.as_ref()).await?;
+
+    let count = if query.inline_count && !single_entry {
+        Some(count_records(ctx.as_ref(), query.clone()).await?)
+    } else {
+        None
+    };
+
+    let page_size = query.page_size;
+    let mut token_columns: Vec<_> = query.order_by.iter().map(|(c, _)| c.clone()).collect();
+    token_columns.extend(info.key_column_aliases());
+
+    // Token carries values of all ordering columns plus the key columns
+    if let Some(skip_token) = &query.skip_token
+        && skip_token.values.len() != token_columns.len()
+    {
+        return Err(ODataError::bad_request("Invalid $skiptoken"));
+    }
+
     let df = ctx.query(query).await?;
 
     let schema: datafusion::arrow::datatypes::SchemaRef = df.schema().inner().clone();
-    let record_batches = df.collect().await.map_err(ODataError::internal)?;
+    let layout = EntryLayout::new(info, &schema)?.with_navigation(navigation, &hidden_columns);
 
-    ctx.validate(&record_batches).await?;
+    if single_entry {
+        return entry_response(ctx, encoder, layout, &expand, df).await;
+    }
 
-    let num_rows: usize = record_batches.iter().map(|b| b.num_rows()).sum();
-    let raw_bytes: usize = record_batches
-        .iter()
-        .map(|b: &datafusion::arrow::array::RecordBatch| b.get_array_memory_size())
-        .sum();
-
-    let mut writer = quick_xml::Writer::new(Vec::<u8>::new());
-
-    if ctx.addr()?.key.is_none() {
-        crate::atom::write_atom_feed_from_records(
-            &schema,
-            record_batches,
-            ctx.as_ref(),
-            ctx.last_updated_time().await,
-            &mut writer,
-        )?;
-    } else {
-        let num_rows: usize = record_batches.iter().map(|b| b.num_rows()).sum();
-        if num_rows > 1 {
-            return Err(ODataError::internal(BatchUnexpectedRowsNumber::new(
-                num_rows,
-            )));
-        }
+    let stream = df.execute_stream().await.map_err(ODataError::internal)?;
+
+    let paging = match page_size {
+        Some(page_size) => Some(FeedPaging {
+            page_size,
+            resource_url,
+            query: raw_query,
+            token_columns: token_columns
+                .iter()
+                .map(|c| schema.index_of(c))
+                .collect::<Result<_, _>>()
+                .map_err(ODataError::internal)?,
+            last_token: None,
+            has_more: false,
+        }),
+        None => None,
+    };
 
-        if record_batches.len() > 1 {
-            return Err(ODataError::internal(UnexpectedBatchesNumber::new(
-                record_batches.len(),
-            )));
-        }
+    let mut feed = FeedWriter::with_layout(encoder, layout);
+    let mut head = Vec::<u8>::new();
+    feed.start(&mut head, count)?;
+
+    // Encoding the first batch before responding allows to report most of the
+    // query errors with a proper status code rather than an aborted body
+    let mut state = FeedStreamState {
+        ctx,
+        stream,
+        feed,
+        expand,
+        paging,
+        head: None,
+        finished: false,
+        num_rows: 0,
+        raw_bytes: 0,
+        body_bytes: 0,
+    };
+    match state.stream.next().await {
+        Some(batch) => state.write_batch(&mut head, batch).await?,
+        None => state.finish(&mut head)?,
+    }
+    state.head = Some(head);
 
-        let record_batch = match record_batches.into_iter().next() {
-            Some(rb) => rb,
+    let mut builder = Response::builder().header(
+        http::header::CONTENT_TYPE.as_str(),
+        state.feed.encoder().media_type(),
+    );
+    for (name, value) in state.feed.encoder().headers() {
+        builder = builder.header(name, value);
+    }
+
+    let body = futures::stream::try_unfold(state, |mut state| async move {
+        let buf = match state.head.take() {
+            Some(head) => head,
+            None if state.finished => {
+                tracing::debug!(
+                    media_type = state.feed.encoder().media_type(),
+                    num_rows = state.num_rows,
+                    raw_bytes = state.raw_bytes,
+                    body_bytes = state.body_bytes,
+                    "Streamed a response"
+                );
+                return Ok(None);
+            }
             None => {
-                return Response::builder()
-                    .status(http::StatusCode::NOT_FOUND)
-                    .body(String::new())
-                    .map_err(ODataError::internal);
+                let mut buf = Vec::<u8>::new();
+                match state.stream.next().await {
+                    Some(batch) => state.write_batch(&mut buf, batch).await,
+                    None => state.finish(&mut buf),
+                }
+                .inspect_err(|err| {
+                    tracing::error!(error = %err, error_dbg = ?err, "Failed to stream a response");
+                })?;
+                buf
             }
         };
 
-        if record_batch.num_rows() != 1 {
+        state.body_bytes += buf.len();
+        Ok::<_, ODataError>(Some((Bytes::from(buf), state)))
+    });
+
+    builder
+        .body(Body::from_stream(body))
+        .map_err(ODataError::internal)
+}
+
+struct FeedStreamState {
+    ctx: Arc<dyn CollectionContext>,
+    stream: SendableRecordBatchStream,
+    feed: FeedWriter,
+    expand: Vec<Expansion>,
+    paging: Option<FeedPaging>,
+    head: Option<Vec<u8>>,
+    finished: bool,
+    num_rows: usize,
+    raw_bytes: usize,
+    body_bytes: usize,
+}
+
+impl FeedStreamState {
+    async fn write_batch(
+        &mut self,
+        buf: &mut Vec<u8>,
+        batch: datafusion::error::Result<RecordBatch>,
+    ) -> Result<(), ODataError> {
+        let mut batch = batch.map_err(ODataError::internal)?;
+
+        // Query fetches one record past the page to detect the next page
+        if let Some(paging) = &mut self.paging {
+            let remaining = paging.page_size.saturating_sub(self.num_rows);
+            if batch.num_rows() > remaining {
+                paging.has_more = true;
+                batch = batch.slice(0, remaining);
+            }
+        }
+
+        self.ctx.validate(std::slice::from_ref(&batch)).await?;
+
+        let mut related = Vec::with_capacity(self.expand.len());
+        for expansion in &self.expand {
+            related.push(RelatedEntries::fetch(expansion, &batch).await?);
+        }
+        self.feed.write_expanded_batch(buf, &batch, &related)?;
+
+        self.num_rows += batch.num_rows();
+        self.raw_bytes += batch.get_array_memory_size();
+
+        if let Some(paging) = &mut self.paging {
+            if batch.num_rows() != 0 {
+                paging.last_token = Some(paging.skip_token(&batch, batch.num_rows() - 1)?);
+            }
+            if paging.has_more {
+                self.finish(buf)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, buf: &mut Vec<u8>) -> Result<(), ODataError> {
+        let next_link = self.paging.as_ref().and_then(FeedPaging::next_link);
+
+        self.feed.end(buf, next_link.as_deref())?;
+        self.finished = true;
+        Ok(())
+    }
+}
+
+/// State of the server-driven paging of a feed
+struct FeedPaging {
+    page_size: usize,
+    /// URL of the paged resource without a trailing slash
+    resource_url: String,
+    query: QueryParamsRaw,
+    /// Indices of the ordering columns followed by the key column
+    token_columns: Vec<usize>,
+    last_token: Option<SkipToken>,
+    has_more: bool,
+}
+
+impl FeedPaging {
+    fn skip_token(&self, batch: &RecordBatch, row: usize) -> Result<SkipToken, ODataError> {
+        SkipToken::from_row(batch, &self.token_columns, row).map_err(ODataError::internal)
+    }
+
+    fn next_link(&self) -> Option<String> {
+        if !self.has_more {
+            return None;
+        }
+        let skip_token = self.last_token.as_ref()?;
+        let top = self
+            .query
+            .top
+            .map(|top| top.saturating_sub(self.page_size as u64));
+
+        Some(format!(
+            "{}?{}",
+            self.resource_url,
+            self.query.next_page_query(top, skip_token)
+        ))
+    }
+}
+
+/// Responds with a single property of an entry, or with its raw value when
+/// addressed via `$value`
+async fn property_response(
+    ctx: &dyn CollectionContext,
+    addr: &CollectionAddr,
+    name: &str,
+    format: Option<&str>,
+    headers: &axum::http::HeaderMap,
+) -> Result<Response<Body>, ODataError> {
+    if addr.count {
+        return Err(ODataError::bad_request("Only collections can be counted"));
+    }
+
+    let schema = ctx.schema().await?;
+    let Ok(field) = schema.field_with_name(name) else {
+        return Err(PropertyNotFound::new(ctx.collection_name()?, name).into());
+    };
+    let field = CollectionInfo::new(ctx).await?.edm_field(field)?;
+
+    let df = ctx
+        .query(QueryParams {
+            select: vec![name.to_string()],
+            ..Default::default()
+        })
+        .await?;
+    let record_batches = df.collect().await.map_err(ODataError::internal)?;
+    ctx.validate(&record_batches).await?;
+
+    let not_found = || {
+        Response::builder()
+            .status(http::StatusCode::NOT_FOUND)
+            .body(Body::empty())
+            .map_err(ODataError::internal)
+    };
+
+    let Some(batch) = record_batches.iter().find(|b| b.num_rows() != 0) else {
+        return not_found();
+    };
+    let col = batch.column(
+        batch
+            .schema()
+            .index_of(name)
+            .map_err(ODataError::internal)?,
+    );
+
+    // Null values have no raw representation
+    if addr.value {
+        let (col, row) = decode_encoded(col, 0);
+        if col.is_null(row) {
+            return not_found();
+        }
+        let (media_type, body) = raw_value(&col, row)?;
+        return Response::builder()
+            .header(http::header::CONTENT_TYPE.as_str(), media_type)
+            .body(Body::from(body))
+            .map_err(ODataError::internal);
+    }
+
+    let format = negotiate_format(
+        format,
+        headers,
+        &[
+            ResponseFormat::Xml,
+            ResponseFormat::Json(JsonFormat::V4(JsonMetadata::Minimal)),
+            ResponseFormat::Json(JsonFormat::Verbose),
+        ],
+    )?;
+
+    let mut buf = Vec::new();
+    match format {
+        ResponseFormat::Json(json_format) => {
+            let info = CollectionInfo::new(ctx).await?;
+            let context_url = format!(
+                "{}$metadata#{}({})/{name}",
+                info.service_base_url,
+                info.collection_name,
+                addr.key
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default(),
+            );
+            crate::json::write_json_property(&field, col, 0, json_format, &context_url, &mut buf)?;
+        }
+        _ => crate::atom::write_property_xml(&mut buf, &field, col, 0)?,
+    }
+
+    response_builder(format)
+        .body(Body::from(buf))
+        .map_err(ODataError::internal)
+}
+
+/// Raw value of a property along with its media type
+fn raw_value(col: &ArrayRef, row: usize) -> Result<(&'static str, Vec<u8>), ODataError> {
+    let bytes = match col.data_type() {
+        DataType::Binary
+        | DataType::LargeBinary
+        | DataType::BinaryView
+        | DataType::FixedSizeBinary(_) => decode_binary(col, row)?,
+        _ => {
+            let text = encode_primitive_dyn(col, row)?.decode()?.into_owned();
+            return Ok((MEDIA_TYPE_TEXT, text.into_bytes()));
+        }
+    };
+    Ok((MEDIA_TYPE_BINARY, bytes.to_vec()))
+}
+
+/// Checks that the key predicate specifies values of all key columns and
+/// that the values are compatible with their types
+async fn validate_key(ctx: &dyn CollectionContext, key: &KeyPredicate) -> Result<(), ODataError> {
+    let key_columns = match ctx.key_columns() {
+        Ok(key_columns) => key_columns,
+        Err(ODataError::KeyColumnNotAssigned(_)) => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let key_columns: Vec<_> = key_columns.iter().map(String::as_str).collect();
+
+    let schema = ctx.schema().await?;
+    for (key_column, value) in key_columns.iter().zip(key.values(&key_columns)?) {
+        let field = schema
+            .field_with_name(key_column)
+            .map_err(ODataError::internal)?;
+        parse_key_value(value, field.data_type())?;
+    }
+    Ok(())
+}
+
+/// Counts records matching the filter regardless of ordering and paging
+async fn count_records(ctx: &dyn CollectionContext, query: QueryParams) -> Result<u64, ODataError> {
+    let df = ctx
+        .query(QueryParams {
+            count: true,
+            ..query
+        })
+        .await?;
+
+    let record_batches = df.collect().await.map_err(ODataError::internal)?;
+
+    let count = record_batches
+        .iter()
+        .find(|b| b.num_rows() != 0)
+        .and_then(|b| b.column(0).as_primitive_opt::<Int64Type>())
+        .map(|c| c.value(0))
+        .ok_or_else(|| ODataError::internal("Count query returned no value"))?;
+
+    Ok(count as u64)
+}
+
+async fn entry_response(
+    ctx: Arc<dyn CollectionContext>,
+    mut encoder: Box<dyn ResponseEncoder>,
+    layout: EntryLayout,
+    expand: &[Expansion],
+    df: DataFrame,
+) -> Result<Response<Body>, ODataError> {
+    let record_batches = df.collect().await.map_err(ODataError::internal)?;
+
+    ctx.validate(&record_batches).await?;
+
+    let num_rows: usize = record_batches.iter().map(|b| b.num_rows()).sum();
+    if num_rows > 1 {
+        return Err(ODataError::internal(BatchUnexpectedRowsNumber::new(
+            num_rows,
+        )));
+    }
+
+    if record_batches.len() > 1 {
+        return Err(ODataError::internal(UnexpectedBatchesNumber::new(
+            record_batches.len(),
+        )));
+    }
+
+    let record_batch = match record_batches.into_iter().next() {
+        Some(rb) if rb.num_rows() == 1 => rb,
+        _ => {
             return Response::builder()
                 .status(http::StatusCode::NOT_FOUND)
-                .body(String::new())
+                .body(Body::empty())
                 .map_err(ODataError::internal);
         }
+    };
 
-        crate::atom::write_atom_entry_from_record(
-            &schema,
-            record_batch,
-            ctx.as_ref(),
-            ctx.last_updated_time().await,
-            &mut writer,
-        )?;
+    let mut related = Vec::with_capacity(expand.len());
+    for expansion in expand {
+        related.push(RelatedEntries::fetch(expansion, &record_batch).await?);
     }
 
-    let body = String::from_utf8(writer.into_inner()).map_err(ODataError::internal)?;
+    let mut buf = Vec::<u8>::new();
+    layout.write_row(encoder.as_mut(), &mut buf, &record_batch, 0, &related)?;
 
     tracing::debug!(
-        media_type = MEDIA_TYPE_ATOM,
+        media_type = encoder.media_type(),
         num_rows,
-        raw_bytes,
-        xml_bytes = body.len(),
+        raw_bytes = record_batch.get_array_memory_size(),
+        body_bytes = buf.len(),
         "Prepared a response"
     );
 
-    Response::builder()
-        .header(http::header::CONTENT_TYPE.as_str(), MEDIA_TYPE_ATOM)
-        .body(body)
-        .map_err(ODataError::internal)
+    let mut builder =
+        Response::builder().header(http::header::CONTENT_TYPE.as_str(), encoder.media_type());
+    for (name, value) in encoder.headers() {
+        builder = builder.header(name, value);
+    }
+
+    builder.body(Body::from(buf)).map_err(ODataError::internal)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+fn response_builder(format: ResponseFormat) -> http::response::Builder {
+    let builder =
+        Response::builder().header(http::header::CONTENT_TYPE.as_str(), format.media_type());
+
+    match format {
+        ResponseFormat::Json(JsonFormat::V4(_)) => builder.header("OData-Version", "4.0"),
+        _ => builder,
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
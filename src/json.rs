@@ -0,0 +1,1053 @@
+use std::{io::Write, sync::Arc};
+
+use base64::Engine as _;
+use datafusion::arrow::{
+    array::{Array, ArrayRef, AsArray},
+    datatypes::{DataType, *},
+};
+use serde::ser::SerializeMap;
+use serde_json::{Value, value::RawValue};
+
+use crate::{
+    atom::{
+        decode_binary, decode_date, decode_decimal, decode_duration, decode_encoded, decode_float,
+        decode_list, decode_time, decode_timestamp, encode_xsd_duration,
+    },
+    context::Relationship,
+    encoder::{CollectionInfo, EdmField, ResponseEncoder},
+    error::{ODataError, UnsupportedDataType},
+    geo::{Coord, Geometry, SpatialType},
+    handlers::{
+        MEDIA_TYPE_JSON_METADATA_FULL, MEDIA_TYPE_JSON_METADATA_MINIMAL,
+        MEDIA_TYPE_JSON_METADATA_NONE, MEDIA_TYPE_JSON_VERBOSE,
+    },
+    metadata::{collection_item, value_type},
+    service::Service,
+};
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonFormat {
+    /// OData v2/v3 JSON Verbose (`application/json;odata=verbose`)
+    Verbose,
+    /// OData v4 JSON (`application/json;odata.metadata=...`)
+    V4(JsonMetadata),
+}
+
+/// Amount of control information included into OData v4 JSON payloads
+///
+/// See: https://docs.oasis-open.org/odata/odata-json-format/v4.01/odata-json-format-v4.01.html#sec_ControllingtheAmountofControlInforma
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonMetadata {
+    None,
+    Minimal,
+    Full,
+}
+
+impl std::str::FromStr for JsonMetadata {
+    type Err = ODataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "minimal" => Ok(Self::Minimal),
+            "full" => Ok(Self::Full),
+            _ => Err(ODataError::bad_request(format!(
+                "Invalid odata.metadata parameter: {s}"
+            ))),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Value of a payload. Unlike [`Value`] numbers are kept in their exact textual
+/// form, so that e.g. decimals which do not fit a double are not rounded.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(untagged)]
+enum JsonValue {
+    Number(Box<RawValue>),
+    Array(Vec<JsonValue>),
+    Object(JsonObject),
+    Value(Value),
+}
+
+/// Object keeping the members in the order of insertion, so that properties
+/// follow the order of the columns and annotations precede the values they
+/// describe
+#[derive(Debug, Clone, Default)]
+struct JsonObject(Vec<(String, JsonValue)>);
+
+impl JsonObject {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a member or replaces the value of an existing one in place
+    fn insert(&mut self, key: impl Into<String>, value: JsonValue) {
+        let key = key.into();
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => self.0.push((key, value)),
+        }
+    }
+}
+
+impl<const N: usize> From<[(&str, JsonValue); N]> for JsonObject {
+    fn from(members: [(&str, JsonValue); N]) -> Self {
+        let mut object = Self::new();
+        for (key, value) in members {
+            object.insert(key, value);
+        }
+        object
+    }
+}
+
+impl serde::Serialize for JsonObject {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in &self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl JsonValue {
+    /// Number in its textual form, which must be a valid JSON number
+    fn number(text: String) -> Result<Self, serde_json::Error> {
+        RawValue::from_string(text).map(Self::Number)
+    }
+}
+
+impl From<Value> for JsonValue {
+    fn from(value: Value) -> Self {
+        Self::Value(value)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+// JSON Verbose: https://www.odata.org/documentation/odata-version-2-0/json-format/
+//
+// {
+//   "d": {
+//     "results": [
+//       {
+//         "__metadata": {
+//           "id": "http://example.com/odata/tickers.spy(0)",
+//           "uri": "http://example.com/odata/tickers.spy(0)",
+//           "type": "default.tickers.spy"
+//         },
+//         "offset": "0",
+//         "from_symbol": "spy",
+//         "to_symbol": "usd",
+//         "close": 135.5625
+//       }
+//     ]
+//   }
+// }
+//
+// JSON v4: https://docs.oasis-open.org/odata/odata-json-format/v4.01/odata-json-format-v4.01.html#sec_CollectionofEntities
+//
+// {
+//   "@odata.context": "http://example.com/odata/$metadata#tickers.spy",
+//   "value": [
+//     {
+//       "offset": 0,
+//       "from_symbol": "spy",
+//       "to_symbol": "usd",
+//       "close": 135.5625
+//     }
+//   ]
+// }
+//
+// Single entry is represented as `{"d": {...}}` in JSON Verbose and as an
+// object with `$entity` context URL in JSON v4.
+pub struct JsonEncoder {
+    format: JsonFormat,
+    in_feed: bool,
+    entries_written: usize,
+    // Entries are accumulated into objects and serialized as a whole, outer
+    // entries stay on the stack while their related entries are written
+    entries: Vec<JsonObject>,
+    // Currently open navigation properties, the expanded ones collect
+    // their related entries
+    navigation: Vec<Option<ExpandedNavigation>>,
+}
+
+struct ExpandedNavigation {
+    name: String,
+    to_many: bool,
+    entries: Vec<JsonValue>,
+    next_link: Option<String>,
+}
+
+impl JsonEncoder {
+    pub fn new(format: JsonFormat) -> Self {
+        Self {
+            format,
+            in_feed: false,
+            entries_written: 0,
+            entries: Vec::new(),
+            navigation: Vec::new(),
+        }
+    }
+
+    fn current_entry(&mut self) -> Result<&mut JsonObject, ODataError> {
+        self.entries
+            .last_mut()
+            .ok_or_else(|| ODataError::internal("Property written outside of an entry"))
+    }
+}
+
+impl ResponseEncoder for JsonEncoder {
+    fn media_type(&self) -> &str {
+        match self.format {
+            JsonFormat::Verbose => MEDIA_TYPE_JSON_VERBOSE,
+            JsonFormat::V4(JsonMetadata::None) => MEDIA_TYPE_JSON_METADATA_NONE,
+            JsonFormat::V4(JsonMetadata::Minimal) => MEDIA_TYPE_JSON_METADATA_MINIMAL,
+            JsonFormat::V4(JsonMetadata::Full) => MEDIA_TYPE_JSON_METADATA_FULL,
+        }
+    }
+
+    fn headers(&self) -> Vec<(&'static str, &'static str)> {
+        match self.format {
+            JsonFormat::Verbose => Vec::new(),
+            JsonFormat::V4(_) => vec![("OData-Version", "4.0")],
+        }
+    }
+
+    // Count is control information that is present regardless of the
+    // metadata level, JSON Verbose represents it as a string:
+    //
+    // {"d": {"__count": "2", "results": [...]}}
+    // {"@odata.context": "...", "@odata.count": 2, "value": [...]}
+    fn start_feed(
+        &mut self,
+        writer: &mut dyn Write,
+        info: &CollectionInfo,
+        count: Option<u64>,
+    ) -> Result<(), ODataError> {
+        // Entries of a nested feed are collected by the navigation property
+        if !self.navigation.is_empty() {
+            return Ok(());
+        }
+
+        match self.format {
+            JsonFormat::Verbose => {
+                writer.write_all(br#"{"d":{"#)?;
+                if let Some(count) = count {
+                    write!(writer, r#""__count":"{count}","#)?;
+                }
+                writer.write_all(br#""results":["#)?;
+            }
+            JsonFormat::V4(metadata) => {
+                writer.write_all(b"{")?;
+                if metadata != JsonMetadata::None {
+                    writer.write_all(br#""@odata.context":"#)?;
+                    serde_json::to_writer(&mut *writer, &context_url(info, false))
+                        .map_err(ODataError::internal)?;
+                    writer.write_all(b",")?;
+                }
+                if let Some(count) = count {
+                    write!(writer, r#""@odata.count":{count},"#)?;
+                }
+                writer.write_all(br#""value":["#)?;
+            }
+        }
+
+        self.in_feed = true;
+        self.entries_written = 0;
+        Ok(())
+    }
+
+    fn start_entry(
+        &mut self,
+        _writer: &mut dyn Write,
+        info: &CollectionInfo,
+        key: Option<&str>,
+    ) -> Result<(), ODataError> {
+        // Entries of records with null keys have no id and edit link
+        let entry_url_rel = key.map(|key| format!("{}({key})", info.collection_name));
+        let entry_url_full = key.map(|key| format!("{}({key})", info.collection_base_url));
+
+        let mut entry = JsonObject::new();
+
+        match self.format {
+            JsonFormat::Verbose => {
+                let mut metadata = JsonObject::new();
+                if let Some(entry_url_full) = entry_url_full {
+                    metadata.insert("id", Value::String(entry_url_full.clone()).into());
+                    metadata.insert("uri", Value::String(entry_url_full).into());
+                }
+                metadata.insert("type", Value::String(info.fq_type()).into());
+                entry.insert("__metadata", JsonValue::Object(metadata));
+            }
+            JsonFormat::V4(metadata) => {
+                if !self.in_feed && self.navigation.is_empty() && metadata != JsonMetadata::None {
+                    entry.insert(
+                        "@odata.context",
+                        Value::String(context_url(info, true)).into(),
+                    );
+                }
+                if metadata == JsonMetadata::Full {
+                    entry.insert(
+                        "@odata.type",
+                        Value::String(format!("#{}", info.fq_type())).into(),
+                    );
+                    if let (Some(entry_url_full), Some(entry_url_rel)) =
+                        (entry_url_full, entry_url_rel)
+                    {
+                        entry.insert("@odata.id", Value::String(entry_url_full).into());
+                        entry.insert("@odata.editLink", Value::String(entry_url_rel).into());
+                    }
+                }
+            }
+        }
+
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    // Deferred navigation properties are represented as links in JSON Verbose
+    // and when full metadata is requested in JSON v4, expanded ones hold the
+    // related entries (an object or null for to-one relationships) and a next
+    // link when they do not fit a page:
+    //
+    // {"Orders": {"__deferred": {"uri": "http://example.com/odata/Customers(1)/Orders"}}}
+    // {"Orders": {"results": [...], "__next": "http://example.com/odata/Customers(1)/Orders?$skiptoken=..."}}
+    // {"Orders@odata.navigationLink": "Customers(1)/Orders"}
+    // {"Orders@odata.nextLink": "http://example.com/odata/Customers(1)/Orders?$skiptoken=...", "Orders": [...]}
+    fn start_navigation(
+        &mut self,
+        _writer: &mut dyn Write,
+        info: &CollectionInfo,
+        key: &str,
+        relationship: &Relationship,
+        expanded: bool,
+    ) -> Result<(), ODataError> {
+        if expanded {
+            self.navigation.push(Some(ExpandedNavigation {
+                name: relationship.name.clone(),
+                to_many: relationship.to_many(),
+                entries: Vec::new(),
+                next_link: None,
+            }));
+            return Ok(());
+        }
+
+        let format = self.format;
+        let entry = self.current_entry()?;
+        match format {
+            JsonFormat::Verbose => {
+                let uri = format!("{}({key})/{}", info.collection_base_url, relationship.name);
+                entry.insert(
+                    relationship.name.clone(),
+                    serde_json::json!({"__deferred": {"uri": uri}}).into(),
+                );
+            }
+            JsonFormat::V4(JsonMetadata::Full) => {
+                entry.insert(
+                    format!("{}@odata.navigationLink", relationship.name),
+                    Value::String(format!(
+                        "{}({key})/{}",
+                        info.collection_name, relationship.name
+                    ))
+                    .into(),
+                );
+            }
+            JsonFormat::V4(_) => {}
+        }
+        self.navigation.push(None);
+        Ok(())
+    }
+
+    fn end_navigation(&mut self, _writer: &mut dyn Write) -> Result<(), ODataError> {
+        let Some(Some(expanded)) = self.navigation.pop() else {
+            return Ok(());
+        };
+
+        let format = self.format;
+        let entry = self.current_entry()?;
+        match (expanded.to_many, format) {
+            (true, JsonFormat::Verbose) => {
+                let mut feed = JsonObject::new();
+                feed.insert("results", JsonValue::Array(expanded.entries));
+                if let Some(next_link) = expanded.next_link {
+                    feed.insert("__next", Value::String(next_link).into());
+                }
+                entry.insert(expanded.name, JsonValue::Object(feed));
+            }
+            (true, JsonFormat::V4(_)) => {
+                if let Some(next_link) = expanded.next_link {
+                    entry.insert(
+                        format!("{}@odata.nextLink", expanded.name),
+                        Value::String(next_link).into(),
+                    );
+                }
+                entry.insert(expanded.name, JsonValue::Array(expanded.entries));
+            }
+            (false, _) => {
+                let value =
+                    (expanded.entries.into_iter().next()).unwrap_or(JsonValue::Value(Value::Null));
+                entry.insert(expanded.name, value);
+            }
+        }
+        Ok(())
+    }
+
+    fn property(
+        &mut self,
+        _writer: &mut dyn Write,
+        field: &EdmField,
+        col: &ArrayRef,
+        row: usize,
+    ) -> Result<(), ODataError> {
+        let format = self.format;
+        let entry = self.current_entry()?;
+        insert_property(entry, &field.name, field, col, row, format)
+    }
+
+    fn end_entry(&mut self, writer: &mut dyn Write) -> Result<(), ODataError> {
+        let entry = self
+            .entries
+            .pop()
+            .ok_or_else(|| ODataError::internal("Entry end without a start"))?;
+
+        if let Some(Some(expanded)) = self.navigation.last_mut() {
+            expanded.entries.push(JsonValue::Object(entry));
+            return Ok(());
+        }
+
+        if self.in_feed {
+            if self.entries_written != 0 {
+                writer.write_all(b",")?;
+            }
+            serde_json::to_writer(&mut *writer, &entry).map_err(ODataError::internal)?;
+        } else if self.format == JsonFormat::Verbose {
+            writer.write_all(br#"{"d":"#)?;
+            serde_json::to_writer(&mut *writer, &entry).map_err(ODataError::internal)?;
+            writer.write_all(b"}")?;
+        } else {
+            serde_json::to_writer(&mut *writer, &entry).map_err(ODataError::internal)?;
+        }
+
+        self.entries_written += 1;
+        Ok(())
+    }
+
+    // Next link is control information that is present regardless of the
+    // metadata level:
+    //
+    // {"d": {"results": [...], "__next": "http://example.com/odata/tickers.spy?$skiptoken=..."}}
+    // {"value": [...], "@odata.nextLink": "http://example.com/odata/tickers.spy?$skiptoken=..."}
+    fn end_feed(
+        &mut self,
+        writer: &mut dyn Write,
+        next_link: Option<&str>,
+    ) -> Result<(), ODataError> {
+        // Next link of a nested feed is written by the navigation property
+        if let Some(Some(expanded)) = self.navigation.last_mut() {
+            expanded.next_link = next_link.map(str::to_string);
+            return Ok(());
+        }
+        if !self.navigation.is_empty() {
+            return Ok(());
+        }
+
+        writer.write_all(b"]")?;
+
+        if let Some(next_link) = next_link {
+            match self.format {
+                JsonFormat::Verbose => writer.write_all(br#","__next":"#)?,
+                JsonFormat::V4(_) => writer.write_all(br#","@odata.nextLink":"#)?,
+            }
+            serde_json::to_writer(&mut *writer, next_link).map_err(ODataError::internal)?;
+        }
+
+        match self.format {
+            JsonFormat::Verbose => writer.write_all(b"}}")?,
+            JsonFormat::V4(_) => writer.write_all(b"}")?,
+        }
+
+        self.in_feed = false;
+        Ok(())
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+// JSON Verbose: https://www.odata.org/documentation/odata-version-2-0/json-format/#ServiceDocuments
+//
+// {
+//   "d": {
+//     "EntitySets": ["covid19.canada", "tickers.spy"]
+//   }
+// }
+//
+// JSON v4: https://docs.oasis-open.org/odata/odata-json-format/v4.01/odata-json-format-v4.01.html#sec_ServiceDocument
+//
+// {
+//   "@odata.context": "http://example.com/odata/$metadata",
+//   "value": [
+//     {"name": "covid19.canada", "kind": "EntitySet", "url": "covid19.canada"},
+//     {"name": "tickers.spy", "kind": "EntitySet", "url": "tickers.spy"}
+//   ]
+// }
+pub fn write_json_service_document<W>(
+    service: &Service,
+    format: JsonFormat,
+    writer: &mut W,
+) -> Result<(), ODataError>
+where
+    W: std::io::Write,
+{
+    let collections = &service.workspace.collections;
+
+    let doc = match format {
+        JsonFormat::Verbose => serde_json::json!({
+            "d": {
+                "EntitySets": collections.iter().map(|c| c.href.as_str()).collect::<Vec<_>>(),
+            }
+        })
+        .into(),
+        JsonFormat::V4(metadata) => {
+            let mut doc = JsonObject::new();
+            if metadata != JsonMetadata::None {
+                let base_url = service.base_url.trim_end_matches('/');
+                doc.insert(
+                    "@odata.context",
+                    Value::String(format!("{base_url}/$metadata")).into(),
+                );
+            }
+            doc.insert(
+                "value",
+                JsonValue::Array(
+                    collections
+                        .iter()
+                        .map(|c| {
+                            JsonValue::Object(JsonObject::from([
+                                ("name", Value::String(c.title.clone()).into()),
+                                ("kind", Value::String("EntitySet".to_string()).into()),
+                                ("url", Value::String(c.href.clone()).into()),
+                            ]))
+                        })
+                        .collect(),
+                ),
+            );
+            JsonValue::Object(doc)
+        }
+    };
+
+    serde_json::to_writer(writer, &doc).map_err(ODataError::internal)?;
+
+    Ok(())
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+// Single property of an entry, e.g. `tickers_spy(1)/close`
+//
+// Verbose:
+// {"d": {"close": 134.5937}}
+//
+// V4 (`context_url` is omitted when metadata is `none`):
+// {
+//   "@odata.context": "http://example.com/odata/$metadata#tickers_spy(1)/close",
+//   "value": 134.5937
+// }
+pub fn write_json_property<W>(
+    field: &EdmField,
+    col: &ArrayRef,
+    row: usize,
+    format: JsonFormat,
+    context_url: &str,
+    writer: &mut W,
+) -> Result<(), ODataError>
+where
+    W: std::io::Write,
+{
+    let mut doc = JsonObject::new();
+    match format {
+        JsonFormat::Verbose => {
+            let mut property = JsonObject::new();
+            insert_property(&mut property, &field.name, field, col, row, format)?;
+            doc.insert("d", JsonValue::Object(property));
+        }
+        JsonFormat::V4(metadata) => {
+            if metadata != JsonMetadata::None {
+                doc.insert(
+                    "@odata.context",
+                    Value::String(context_url.to_string()).into(),
+                );
+            }
+            insert_property(&mut doc, "value", field, col, row, format)?;
+        }
+    }
+
+    serde_json::to_writer(writer, &doc).map_err(ODataError::internal)?;
+
+    Ok(())
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+// See: https://docs.oasis-open.org/odata/odata-json-format/v4.01/odata-json-format-v4.01.html#sec_ContextURL
+fn context_url(info: &CollectionInfo, entity: bool) -> String {
+    let CollectionInfo {
+        service_base_url,
+        collection_name,
+        ..
+    } = info;
+
+    if entity {
+        format!("{service_base_url}$metadata#{collection_name}/$entity")
+    } else {
+        format!("{service_base_url}$metadata#{collection_name}")
+    }
+}
+
+// Inserts the value of a property under the specified key along with its type
+// annotation in full metadata mode. Values of complex types are nested objects
+// and collections are arrays:
+//
+// Verbose:
+// "address": {"__metadata": {"type": "default.tickers_address"}, "city": "Vancouver"}
+// "tags": {"__metadata": {"type": "Collection(Edm.String)"}, "results": ["etf"]}
+//
+// V4:
+// "address": {"city": "Vancouver"}
+// "tags": ["etf"]
+fn insert_property(
+    object: &mut JsonObject,
+    key: &str,
+    field: &EdmField,
+    col: &ArrayRef,
+    row: usize,
+    format: JsonFormat,
+) -> Result<(), ODataError> {
+    if format == JsonFormat::V4(JsonMetadata::Full)
+        && let Some(typ) = v4_field_type_annotation(field, col.data_type())
+    {
+        object.insert(
+            format!("{key}@odata.type"),
+            Value::String(format!("#{typ}")).into(),
+        );
+    }
+
+    object.insert(key.to_string(), encode_property(field, col, row, format)?);
+    Ok(())
+}
+
+fn encode_property(
+    field: &EdmField,
+    col: &ArrayRef,
+    row: usize,
+    format: JsonFormat,
+) -> Result<JsonValue, ODataError> {
+    let (col, row) = decode_encoded(col, row);
+    let col = &col;
+    if col.is_null(row) {
+        return Ok(Value::Null.into());
+    }
+
+    if let Some(members) = col.as_struct_opt()
+        && field.is_complex()
+    {
+        let mut value = JsonObject::new();
+        if format == JsonFormat::Verbose {
+            value.insert(
+                "__metadata",
+                serde_json::json!({ "type": field.edm_type }).into(),
+            );
+        }
+        for (member, member_col) in field.fields.iter().zip(members.columns()) {
+            insert_property(&mut value, &member.name, member, member_col, row, format)?;
+        }
+        return Ok(JsonValue::Object(value));
+    }
+
+    if let Some(item) = &field.item {
+        let items = decode_list(col, row)?;
+        let values = (0..items.len())
+            .map(|i| encode_property(item, &items, i, format))
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(match format {
+            JsonFormat::Verbose => JsonValue::Object(JsonObject::from([
+                (
+                    "__metadata",
+                    serde_json::json!({ "type": field.edm_type }).into(),
+                ),
+                ("results", JsonValue::Array(values)),
+            ])),
+            JsonFormat::V4(_) => JsonValue::Array(values),
+        });
+    }
+
+    if let Some(spatial_type) = SpatialType::from_edm_type(&field.edm_type) {
+        let geometry = Geometry::decode(col, row).map_err(ODataError::internal)?;
+        let mut value = JsonObject::new();
+        if format == JsonFormat::Verbose {
+            value.insert(
+                "__metadata",
+                serde_json::json!({ "type": field.edm_type }).into(),
+            );
+        }
+        value.0.extend(encode_geojson(&geometry).0);
+        value.insert(
+            "crs",
+            JsonValue::Object(JsonObject::from([
+                ("type", Value::String("name".to_string()).into()),
+                (
+                    "properties",
+                    serde_json::json!({ "name": format!("EPSG:{}", spatial_type.srid()) }).into(),
+                ),
+            ])),
+        );
+        return Ok(JsonValue::Object(value));
+    }
+
+    Ok(match format {
+        JsonFormat::Verbose => encode_json_verbose_dyn(col, row)?,
+        JsonFormat::V4(_) => encode_json_dyn(col, row)?,
+    })
+}
+
+// See: https://datatracker.ietf.org/doc/html/rfc7946#section-3.1
+//
+// {"type": "Point", "coordinates": [-123.1, 49.25]}
+fn encode_geojson(geometry: &Geometry) -> JsonObject {
+    let position = |c: &Coord| serde_json::json!(c);
+    let line = |coords: &[Coord]| Value::Array(coords.iter().map(position).collect());
+    let polygon = |rings: &[Vec<Coord>]| Value::Array(rings.iter().map(|r| line(r)).collect());
+
+    let (key, value) = match geometry {
+        // Empty point has no coordinates
+        Geometry::Point(coord) => (
+            "coordinates",
+            coord
+                .as_ref()
+                .map_or_else(|| Value::Array(Vec::new()), position)
+                .into(),
+        ),
+        Geometry::LineString(coords) | Geometry::MultiPoint(coords) => {
+            ("coordinates", line(coords).into())
+        }
+        Geometry::Polygon(rings) | Geometry::MultiLineString(rings) => {
+            ("coordinates", polygon(rings).into())
+        }
+        Geometry::MultiPolygon(polygons) => (
+            "coordinates",
+            Value::Array(polygons.iter().map(|p| polygon(p)).collect()).into(),
+        ),
+        Geometry::GeometryCollection(members) => (
+            "geometries",
+            JsonValue::Array(
+                members
+                    .iter()
+                    .map(|m| JsonValue::Object(encode_geojson(m)))
+                    .collect(),
+            ),
+        ),
+    };
+
+    JsonObject::from([
+        (
+            "type",
+            Value::String(geometry.type_name().to_string()).into(),
+        ),
+        (key, value),
+    ])
+}
+
+// Collections are annotated when their items are
+fn v4_field_type_annotation(field: &EdmField, data_type: &DataType) -> Option<String> {
+    match (&field.item, collection_item(data_type)) {
+        (Some(item), Some(item_field)) => v4_field_type_annotation(item, item_field.data_type())
+            .map(|typ| format!("Collection({typ})")),
+        _ => v4_type_annotation(&field.edm_type, data_type).map(str::to_string),
+    }
+}
+
+// Types that cannot be heuristically determined from JSON values need to be
+// annotated in full metadata mode.
+//
+// See: https://docs.oasis-open.org/odata/odata-json-format/v4.01/odata-json-format-v4.01.html#sec_ControlInformationtypeodatatype
+fn v4_type_annotation<'a>(edm_type: &'a str, data_type: &DataType) -> Option<&'a str> {
+    match edm_type {
+        "Edm.String" | "Edm.Boolean" | "Edm.Double" => None,
+        // There is no `Edm.DateTime` in v4
+        "Edm.DateTime" => Some("DateTimeOffset"),
+        // `Edm.Time` of v3 is split into `Edm.TimeOfDay` and `Edm.Duration` in v4
+        "Edm.Time" => match value_type(data_type) {
+            DataType::Time32(_) | DataType::Time64(_) => Some("TimeOfDay"),
+            _ => Some("Duration"),
+        },
+        typ => Some(typ.strip_prefix("Edm.").unwrap_or(typ)),
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+// See: https://www.odata.org/documentation/odata-version-2-0/json-format/#PrimitiveTypes
+fn encode_json_verbose_dyn(
+    col: &Arc<dyn Array>,
+    row: usize,
+) -> Result<JsonValue, UnsupportedDataType> {
+    let (col, row) = decode_encoded(col, row);
+    let col = &col;
+    if col.is_null(row) {
+        return Ok(Value::Null.into());
+    }
+
+    let value = match col.data_type() {
+        // Edm.Int64 is represented as a string to avoid precision loss in JS clients
+        DataType::Int64 => col.as_primitive::<Int64Type>().value(row).to_string(),
+        DataType::UInt64 => col.as_primitive::<UInt64Type>().value(row).to_string(),
+        DataType::Decimal32(_, _)
+        | DataType::Decimal64(_, _)
+        | DataType::Decimal128(_, _)
+        | DataType::Decimal256(_, _) => decode_decimal(col, row)?,
+        DataType::Binary
+        | DataType::LargeBinary
+        | DataType::BinaryView
+        | DataType::FixedSizeBinary(_) => {
+            base64::engine::general_purpose::STANDARD.encode(decode_binary(col, row)?)
+        }
+        DataType::Time32(_) | DataType::Time64(_) => {
+            encode_xsd_duration(0, 0, decode_time(col, row)?.into())
+        }
+        DataType::Timestamp(unit, None) => {
+            let dt = decode_timestamp(col, row, *unit)?;
+            format!("/Date({})/", dt.timestamp_millis())
+        }
+        DataType::Date32 | DataType::Date64 => {
+            let date = decode_date(col, row)?;
+            let dt = date.and_time(chrono::NaiveTime::MIN).and_utc();
+            format!("/Date({})/", dt.timestamp_millis())
+        }
+        _ => return encode_json_dyn(col, row),
+    };
+    Ok(Value::String(value).into())
+}
+
+// See: https://docs.oasis-open.org/odata/odata-json-format/v4.01/odata-json-format-v4.01.html#sec_PrimitiveValue
+fn encode_json_dyn(col: &Arc<dyn Array>, row: usize) -> Result<JsonValue, UnsupportedDataType> {
+    let (col, row) = decode_encoded(col, row);
+    let col = &col;
+    if col.is_null(row) {
+        return Ok(Value::Null.into());
+    }
+
+    match col.data_type() {
+        // Special values have no JSON number representation and are sent as strings
+        DataType::Float16 | DataType::Float32 | DataType::Float64 => {
+            let value = decode_float(col, row)?;
+            if matches!(value.as_str(), "NaN" | "INF" | "-INF") {
+                return Ok(Value::String(value).into());
+            }
+            JsonValue::number(value).map_err(|_| UnsupportedDataType::new(col.data_type().clone()))
+        }
+        // Number is kept in its exact textual form to avoid float rounding
+        DataType::Decimal32(_, _)
+        | DataType::Decimal64(_, _)
+        | DataType::Decimal128(_, _)
+        | DataType::Decimal256(_, _) => JsonValue::number(decode_decimal(col, row)?)
+            .map_err(|_| UnsupportedDataType::new(col.data_type().clone())),
+        _ => encode_json_value(col, row).map(JsonValue::from),
+    }
+}
+
+/// Encodes values that are represented exactly by [`Value`]
+fn encode_json_value(col: &Arc<dyn Array>, row: usize) -> Result<Value, UnsupportedDataType> {
+    match col.data_type() {
+        DataType::Boolean => Ok(Value::Bool(col.as_boolean().value(row))),
+        DataType::Int8 => Ok(col.as_primitive::<Int8Type>().value(row).into()),
+        DataType::Int16 => Ok(col.as_primitive::<Int16Type>().value(row).into()),
+        DataType::Int32 => Ok(col.as_primitive::<Int32Type>().value(row).into()),
+        DataType::Int64 => Ok(col.as_primitive::<Int64Type>().value(row).into()),
+        DataType::UInt8 => Ok(col.as_primitive::<UInt8Type>().value(row).into()),
+        DataType::UInt16 => Ok(col.as_primitive::<UInt16Type>().value(row).into()),
+        DataType::UInt32 => Ok(col.as_primitive::<UInt32Type>().value(row).into()),
+        DataType::UInt64 => Ok(col.as_primitive::<UInt64Type>().value(row).into()),
+        DataType::Timestamp(unit, _) => {
+            let dt = decode_timestamp(col, row, *unit)?;
+            Ok(Value::String(
+                dt.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true),
+            ))
+        }
+        DataType::Date32 | DataType::Date64 => {
+            let date = decode_date(col, row)?;
+            let dt = date.and_time(chrono::NaiveTime::MIN).and_utc();
+            Ok(Value::String(
+                dt.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            ))
+        }
+        DataType::Utf8 => Ok(Value::String(col.as_string::<i32>().value(row).to_string())),
+        DataType::LargeUtf8 => Ok(Value::String(col.as_string::<i64>().value(row).to_string())),
+        DataType::Utf8View => Ok(Value::String(col.as_string_view().value(row).to_string())),
+        // v4 uses URL-safe alphabet unlike Atom and JSON Verbose
+        DataType::Binary
+        | DataType::LargeBinary
+        | DataType::BinaryView
+        | DataType::FixedSizeBinary(_) => Ok(Value::String(
+            base64::engine::general_purpose::URL_SAFE.encode(decode_binary(col, row)?),
+        )),
+        DataType::Time32(_) | DataType::Time64(_) => {
+            Ok(Value::String(encode_time_of_day(decode_time(col, row)?)))
+        }
+        DataType::Duration(_) | DataType::Interval(_) => {
+            Ok(Value::String(decode_duration(col, row)?))
+        }
+        typ => Err(UnsupportedDataType::new(typ.clone())),
+    }
+}
+
+/// Formats nanoseconds since midnight as `hh:mm:ss[.fffffffff]`
+fn encode_time_of_day(nanos: i64) -> String {
+    let (seconds, fraction) = (nanos / 1_000_000_000, nanos % 1_000_000_000);
+    let time = format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    if fraction == 0 {
+        time
+    } else {
+        let fraction = format!("{fraction:09}");
+        format!("{time}.{}", fraction.trim_end_matches('0'))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use datafusion::arrow::array::{
+        BinaryArray, Date32Array, Decimal128Array, Float32Array, Float64Array, Int64Array,
+        StringArray, TimestampMillisecondArray,
+    };
+
+    /// Payload value as parsed by clients
+    fn to_value(value: Result<JsonValue, UnsupportedDataType>) -> Value {
+        serde_json::to_value(value.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_encode_json_verbose() {
+        let values = Arc::new(Int64Array::from(vec![Some(1), None])) as Arc<dyn Array>;
+        assert_eq!(
+            to_value(encode_json_verbose_dyn(&values, 0)),
+            Value::String("1".to_string())
+        );
+        assert_eq!(to_value(encode_json_verbose_dyn(&values, 1)), Value::Null);
+
+        let values = Arc::new(Float64Array::from(vec![135.5625])) as Arc<dyn Array>;
+        assert_eq!(
+            to_value(encode_json_verbose_dyn(&values, 0)),
+            serde_json::json!(135.5625)
+        );
+
+        let values = Arc::new(StringArray::from(vec!["a\"b"])) as Arc<dyn Array>;
+        assert_eq!(
+            to_value(encode_json_verbose_dyn(&values, 0)),
+            Value::String("a\"b".to_string())
+        );
+
+        // 2024-09-11
+        let values = Arc::new(Date32Array::from(vec![19977])) as Arc<dyn Array>;
+        assert_eq!(
+            to_value(encode_json_verbose_dyn(&values, 0)),
+            Value::String("/Date(1726012800000)/".to_string())
+        );
+
+        // 2020-01-01T12:00:00.001
+        let values =
+            Arc::new(TimestampMillisecondArray::from(vec![1_577_880_000_001])) as Arc<dyn Array>;
+        assert_eq!(
+            to_value(encode_json_verbose_dyn(&values, 0)),
+            Value::String("/Date(1577880000001)/".to_string())
+        );
+
+        let values = Arc::new(
+            TimestampMillisecondArray::from(vec![1_577_880_000_001])
+                .with_timezone(Arc::from("UTC")),
+        ) as Arc<dyn Array>;
+        assert_eq!(
+            to_value(encode_json_verbose_dyn(&values, 0)),
+            Value::String("2020-01-01T12:00:00.001Z".to_string())
+        );
+    }
+
+    #[test]
+    fn test_encode_json() {
+        let values = Arc::new(Int64Array::from(vec![Some(1), None])) as Arc<dyn Array>;
+        assert_eq!(to_value(encode_json_dyn(&values, 0)), serde_json::json!(1));
+        assert_eq!(to_value(encode_json_dyn(&values, 1)), Value::Null);
+
+        // 2024-09-11
+        let values = Arc::new(Date32Array::from(vec![19977])) as Arc<dyn Array>;
+        assert_eq!(
+            to_value(encode_json_dyn(&values, 0)),
+            Value::String("2024-09-11T00:00:00Z".to_string())
+        );
+
+        // 2020-01-01T12:00:00.001
+        let values =
+            Arc::new(TimestampMillisecondArray::from(vec![1_577_880_000_001])) as Arc<dyn Array>;
+        assert_eq!(
+            to_value(encode_json_dyn(&values, 0)),
+            Value::String("2020-01-01T12:00:00.001Z".to_string())
+        );
+
+        let values = Arc::new(
+            Decimal128Array::from(vec![i128::MAX])
+                .with_precision_and_scale(38, 10)
+                .unwrap(),
+        ) as Arc<dyn Array>;
+        assert_eq!(
+            serde_json::to_string(&encode_json_dyn(&values, 0).unwrap()).unwrap(),
+            "17014118346046923173168730371.5884105727"
+        );
+        assert_eq!(
+            to_value(encode_json_verbose_dyn(&values, 0)),
+            Value::String("17014118346046923173168730371.5884105727".to_string())
+        );
+
+        let values =
+            Arc::new(Float32Array::from(vec![0.1, f32::NAN, f32::NEG_INFINITY])) as Arc<dyn Array>;
+        assert_eq!(
+            serde_json::to_string(&encode_json_dyn(&values, 0).unwrap()).unwrap(),
+            "0.1"
+        );
+        assert_eq!(
+            to_value(encode_json_dyn(&values, 1)),
+            Value::String("NaN".to_string())
+        );
+        assert_eq!(
+            to_value(encode_json_verbose_dyn(&values, 2)),
+            Value::String("-INF".to_string())
+        );
+
+        let values = Arc::new(Float64Array::from(vec![f64::INFINITY])) as Arc<dyn Array>;
+        assert_eq!(
+            to_value(encode_json_dyn(&values, 0)),
+            Value::String("INF".to_string())
+        );
+
+        let values = Arc::new(BinaryArray::from(vec![&[0xfbu8, 0xff][..]])) as Arc<dyn Array>;
+        assert_eq!(
+            to_value(encode_json_dyn(&values, 0)),
+            Value::String("-_8=".to_string())
+        );
+        assert_eq!(
+            to_value(encode_json_verbose_dyn(&values, 0)),
+            Value::String("+/8=".to_string())
+        );
+    }
+}
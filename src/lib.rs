@@ -1,8 +1,13 @@
 pub mod atom;
 pub mod collection;
 pub mod context;
+pub mod encoder;
 pub mod error;
 pub mod filter;
+pub mod geo;
 pub mod handlers;
+pub mod json;
 pub mod metadata;
+pub mod navigation;
+pub mod negotiation;
 pub mod service;
@@ -7,9 +7,12 @@
 //         </Key>
 //         <Property Name="LastName" Type="Edm.String" Nullable="false" MaxLength="20" FixedLength="false" Unicode="true"/>
 
-use datafusion::arrow::datatypes::DataType;
+use datafusion::arrow::datatypes::{DataType, Field, FieldRef};
 
-use crate::error::UnsupportedDataType;
+use crate::{
+    error::{ODataError, UnsupportedDataType},
+    geo::SpatialType,
+};
 
 #[derive(Debug, serde::Serialize)]
 pub struct Edmx {
@@ -60,6 +63,12 @@ pub struct Schema {
     pub namespace: String,
     #[serde(rename = "EntityType")]
     pub entity_types: Vec<EntityType>,
+    #[serde(rename = "ComplexType")]
+    pub complex_types: Vec<ComplexType>,
+    #[serde(rename = "EnumType")]
+    pub enum_types: Vec<EnumType>,
+    #[serde(rename = "Association")]
+    pub associations: Vec<Association>,
     #[serde(rename = "EntityContainer")]
     pub entity_containers: Vec<EntityContainer>,
     #[serde(rename = "@xmlns")]
@@ -70,11 +79,17 @@ impl Schema {
     pub fn new(
         namespace: String,
         entity_types: Vec<EntityType>,
+        complex_types: Vec<ComplexType>,
+        enum_types: Vec<EnumType>,
+        associations: Vec<Association>,
         entity_containers: Vec<EntityContainer>,
     ) -> Self {
         Self {
             namespace,
             entity_types,
+            complex_types,
+            enum_types,
+            associations,
             entity_containers,
             ns: "http://schemas.microsoft.com/ado/2009/11/edm".to_string(),
         }
@@ -89,6 +104,8 @@ pub struct EntityType {
     pub key: EntityKey,
     #[serde(rename = "Property")]
     pub properties: Vec<Property>,
+    #[serde(rename = "NavigationProperty")]
+    pub navigation_properties: Vec<NavigationProperty>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -118,12 +135,21 @@ pub struct Property {
     pub typ: String,
     #[serde(rename = "@Nullable")]
     pub nullable: bool,
+    #[serde(rename = "@MaxLength")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<i32>,
     #[serde(rename = "@FixedLength")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fixed_length: Option<bool>,
     #[serde(rename = "@Unicode")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub unicode: Option<bool>,
+    #[serde(rename = "@Precision")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub precision: Option<u8>,
+    #[serde(rename = "@Scale")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<u8>,
 }
 
 impl Property {
@@ -132,8 +158,11 @@ impl Property {
             name: name.into(),
             typ: typ.into(),
             nullable,
+            max_length: None,
             fixed_length: None,
             unicode: None,
+            precision: None,
+            scale: None,
         }
     }
 
@@ -142,14 +171,164 @@ impl Property {
             name: name.into(),
             typ: typ.into(),
             nullable,
+            max_length: None,
             fixed_length: Some(false),
             unicode: Some(true),
+            precision: None,
+            scale: None,
+        }
+    }
+
+    /// Sets the facets implied by the Arrow type, e.g. `Precision` and `Scale`
+    /// of decimals or `MaxLength` of fixed-size binaries
+    pub fn with_facets(mut self, dt: &DataType) -> Self {
+        match value_type(dt) {
+            DataType::Decimal32(precision, scale)
+            | DataType::Decimal64(precision, scale)
+            | DataType::Decimal128(precision, scale)
+            | DataType::Decimal256(precision, scale) => {
+                // Negative scale is expressed as extra integer digits
+                let (precision, scale) = if *scale < 0 {
+                    (precision.saturating_add(scale.unsigned_abs()), 0)
+                } else {
+                    (*precision, *scale as u8)
+                };
+                self.precision = Some(precision);
+                self.scale = Some(scale);
+            }
+            // Enough digits for `u64::MAX`
+            DataType::UInt64 => {
+                self.precision = Some(20);
+                self.scale = Some(0);
+            }
+            DataType::FixedSizeBinary(size) => {
+                self.max_length = Some(*size);
+                self.fixed_length = Some(true);
+            }
+            _ => {}
+        }
+        self
+    }
+}
+
+// <ComplexType Name="Address">
+//   <Property Name="Street" Type="Edm.String" Nullable="true"/>
+//   <Property Name="City" Type="Edm.String" Nullable="true"/>
+// </ComplexType>
+
+#[derive(Debug, serde::Serialize)]
+pub struct ComplexType {
+    #[serde(rename = "@Name")]
+    pub name: String,
+    #[serde(rename = "Property")]
+    pub properties: Vec<Property>,
+}
+
+// <EnumType Name="Status" UnderlyingType="Edm.Int32">
+//   <Member Name="Active" Value="0"/>
+//   <Member Name="Closed" Value="1"/>
+// </EnumType>
+
+#[derive(Debug, serde::Serialize)]
+pub struct EnumType {
+    #[serde(rename = "@Name")]
+    pub name: String,
+    #[serde(rename = "@UnderlyingType")]
+    pub underlying_type: String,
+    #[serde(rename = "Member")]
+    pub members: Vec<EnumMember>,
+}
+
+impl EnumType {
+    /// Enum type with the members valued by their positions. Member names must
+    /// be CSDL simple identifiers.
+    pub fn new(name: impl Into<String>, members: Vec<String>) -> Result<Self, ODataError> {
+        let name = name.into();
+        if let Some(member) = members.iter().find(|m| !is_simple_identifier(m)) {
+            return Err(ODataError::internal(format!(
+                "Member '{member}' of enum type {name} is not a valid identifier"
+            )));
         }
+        Ok(Self {
+            name,
+            underlying_type: "Edm.Int32".to_string(),
+            members: members
+                .into_iter()
+                .zip(0..)
+                .map(|(name, value)| EnumMember { name, value })
+                .collect(),
+        })
     }
 }
 
+/// Checks that the name is a `SimpleIdentifier`: a letter or an underscore
+/// followed by up to 479 letters, digits or underscores
+/// See: https://www.odata.org/documentation/odata-version-3-0/common-schema-definition-language-csdl/
+pub fn is_simple_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    name.chars().count() <= 480
+        && chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct EnumMember {
+    #[serde(rename = "@Name")]
+    pub name: String,
+    #[serde(rename = "@Value")]
+    pub value: i32,
+}
+
+/// Name of the complex type of a struct property, e.g. `instruments_address`
+/// for the `address` property of `instruments` entity type. Types of nested
+/// structs are named after the complex type that contains them.
+pub fn complex_type_name(parent_type: &str, property: &str) -> String {
+    format!("{parent_type}_{property}")
+}
+
+// <NavigationProperty Name="Orders" Relationship="NorthwindModel.FK_Orders_Customers" FromRole="Customers" ToRole="Orders"/>
+
+#[derive(Debug, serde::Serialize)]
+pub struct NavigationProperty {
+    #[serde(rename = "@Name")]
+    pub name: String,
+    #[serde(rename = "@Relationship")]
+    pub relationship: String,
+    #[serde(rename = "@FromRole")]
+    pub from_role: String,
+    #[serde(rename = "@ToRole")]
+    pub to_role: String,
+}
+
+// <Association Name="FK_Orders_Customers">
+//   <End Type="NorthwindModel.Customer" Role="Customers" Multiplicity="0..1"/>
+//   <End Type="NorthwindModel.Order" Role="Orders" Multiplicity="*"/>
+// </Association>
+
+#[derive(Debug, serde::Serialize)]
+pub struct Association {
+    #[serde(rename = "@Name")]
+    pub name: String,
+    #[serde(rename = "End")]
+    pub ends: Vec<AssociationEnd>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct AssociationEnd {
+    #[serde(rename = "@Type")]
+    pub typ: String,
+    #[serde(rename = "@Role")]
+    pub role: String,
+    #[serde(rename = "@Multiplicity")]
+    pub multiplicity: String,
+}
+
 // <EntityContainer Name="DemoService" m:IsDefaultEntityContainer="true">
 //   <EntitySet Name="Products" EntityType="ODataDemo.Product"/>
+//   <AssociationSet Name="FK_Orders_Customers" Association="NorthwindModel.FK_Orders_Customers">
+//     <End Role="Customers" EntitySet="Customers"/>
+//     <End Role="Orders" EntitySet="Orders"/>
+//   </AssociationSet>
 
 #[derive(Debug, serde::Serialize)]
 pub struct EntityContainer {
@@ -159,6 +338,8 @@ pub struct EntityContainer {
     pub is_default: bool,
     #[serde(rename = "EntitySet")]
     pub entity_set: Vec<EntitySet>,
+    #[serde(rename = "AssociationSet")]
+    pub association_set: Vec<AssociationSet>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -169,22 +350,150 @@ pub struct EntitySet {
     pub entity_type: String,
 }
 
+#[derive(Debug, serde::Serialize)]
+pub struct AssociationSet {
+    #[serde(rename = "@Name")]
+    pub name: String,
+    #[serde(rename = "@Association")]
+    pub association: String,
+    #[serde(rename = "End")]
+    pub ends: Vec<AssociationSetEnd>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct AssociationSetEnd {
+    #[serde(rename = "@Role")]
+    pub role: String,
+    #[serde(rename = "@EntitySet")]
+    pub entity_set: String,
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
+/// Converts a field into a property, declaring complex types of struct fields
+/// and of their nested structs in `complex_types`. A struct is unsupported if
+/// any of its nested fields is. Lists become `Collection(...)` properties,
+/// collections of collections are unsupported.
+pub fn to_property(
+    field: &Field,
+    parent_type: &str,
+    namespace: &str,
+    complex_types: &mut Vec<ComplexType>,
+) -> std::result::Result<Property, UnsupportedDataType> {
+    // Collection of primitive or complex values, the item is typed as if it
+    // was the property itself
+    if let Some(item) = collection_item(field.data_type()) {
+        if collection_item(item.data_type()).is_some() {
+            return Err(UnsupportedDataType::new(field.data_type().clone()));
+        }
+        let item = to_property(
+            &item.as_ref().clone().with_name(field.name()),
+            parent_type,
+            namespace,
+            complex_types,
+        )?;
+        return Ok(Property {
+            typ: format!("Collection({})", item.typ),
+            nullable: field.is_nullable(),
+            ..item
+        });
+    }
+
+    let DataType::Struct(fields) = field.data_type() else {
+        let typ = to_edm_type(field.data_type())?;
+        return Ok(Property::primitive(field.name(), typ, field.is_nullable())
+            .with_facets(field.data_type()));
+    };
+
+    let name = complex_type_name(parent_type, field.name());
+    let mut nested_types = Vec::new();
+    let properties = fields
+        .iter()
+        .map(|f| to_property(f, &name, namespace, &mut nested_types))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    complex_types.push(ComplexType {
+        name: name.clone(),
+        properties,
+    });
+    complex_types.extend(nested_types);
+
+    Ok(Property::primitive(
+        field.name(),
+        format!("{namespace}.{name}"),
+        field.is_nullable(),
+    ))
+}
+
+/// Converts a string field into a property of the enum type. Other types are
+/// unsupported as their values cannot name the members.
+pub fn to_enum_property(
+    field: &Field,
+    namespace: &str,
+    type_name: &str,
+) -> std::result::Result<Property, UnsupportedDataType> {
+    if to_edm_type(field.data_type())? != "Edm.String" {
+        return Err(UnsupportedDataType::new(field.data_type().clone()));
+    }
+    Ok(Property::primitive(
+        field.name(),
+        format!("{namespace}.{type_name}"),
+        field.is_nullable(),
+    ))
+}
+
+/// Converts a binary (WKB) or string (WKT) field into a property of the
+/// spatial type
+pub fn to_spatial_property(
+    field: &Field,
+    spatial_type: SpatialType,
+) -> std::result::Result<Property, UnsupportedDataType> {
+    if !matches!(to_edm_type(field.data_type())?, "Edm.String" | "Edm.Binary") {
+        return Err(UnsupportedDataType::new(field.data_type().clone()));
+    }
+    Ok(Property::primitive(
+        field.name(),
+        spatial_type.edm_type(),
+        field.is_nullable(),
+    ))
+}
+
+/// Type of the values of dictionary and run-end encoded types, other types are
+/// returned as is
+pub fn value_type(dt: &DataType) -> &DataType {
+    match dt {
+        DataType::Dictionary(_, values) => value_type(values),
+        DataType::RunEndEncoded(_, values) => value_type(values.data_type()),
+        dt => dt,
+    }
+}
+
+/// Field of the items of a list type. Maps are collections of their entries.
+pub fn collection_item(dt: &DataType) -> Option<&FieldRef> {
+    match dt {
+        DataType::Map(entries, _) => Some(entries),
+        DataType::List(item)
+        | DataType::LargeList(item)
+        | DataType::FixedSizeList(item, _)
+        | DataType::ListView(item)
+        | DataType::LargeListView(item) => Some(item),
+        _ => None,
+    }
+}
+
 // See: https://www.odata.org/documentation/odata-version-3-0/common-schema-definition-language-csdl/
 pub fn to_edm_type(dt: &DataType) -> std::result::Result<&'static str, UnsupportedDataType> {
     match dt {
         DataType::Boolean => Ok("Edm.Boolean"),
-        // TODO: Use Edm.Byte / Edm.SByte?
-        DataType::Int8 => Ok("Edm.Int16"),
+        DataType::Int8 => Ok("Edm.SByte"),
         DataType::Int16 => Ok("Edm.Int16"),
         DataType::Int32 => Ok("Edm.Int32"),
         DataType::Int64 => Ok("Edm.Int64"),
-        // TODO: Use Edm.Byte / Edm.SByte?
-        DataType::UInt8 => Ok("Edm.Int16"),
-        DataType::UInt16 => Ok("Edm.Int16"),
-        DataType::UInt32 => Ok("Edm.Int32"),
-        DataType::UInt64 => Ok("Edm.Int64"),
+        // Unsigned types are widened to the signed type covering all their values
+        DataType::UInt8 => Ok("Edm.Byte"),
+        DataType::UInt16 => Ok("Edm.Int32"),
+        DataType::UInt32 => Ok("Edm.Int64"),
+        DataType::UInt64 => Ok("Edm.Decimal"),
         DataType::Utf8 | DataType::Utf8View => Ok("Edm.String"),
         DataType::LargeUtf8 => Ok("Edm.String"),
         DataType::Float16 => Ok("Edm.Single"),
@@ -194,15 +503,22 @@ pub fn to_edm_type(dt: &DataType) -> std::result::Result<&'static str, Unsupport
         DataType::Timestamp(_, Some(_)) => Ok("Edm.DateTimeOffset"),
         DataType::Date32 => Ok("Edm.DateTime"),
         DataType::Date64 => Ok("Edm.DateTime"),
-        DataType::Null
-        | DataType::Time32(_)
+        DataType::Decimal32(_, _)
+        | DataType::Decimal64(_, _)
+        | DataType::Decimal128(_, _)
+        | DataType::Decimal256(_, _) => Ok("Edm.Decimal"),
+        DataType::Binary
+        | DataType::LargeBinary
+        | DataType::BinaryView
+        | DataType::FixedSizeBinary(_) => Ok("Edm.Binary"),
+        // Edm.Time covers both time of day and durations in v3
+        DataType::Time32(_)
         | DataType::Time64(_)
         | DataType::Duration(_)
-        | DataType::Interval(_)
-        | DataType::Binary
-        | DataType::BinaryView
-        | DataType::FixedSizeBinary(_)
-        | DataType::LargeBinary
+        | DataType::Interval(_) => Ok("Edm.Time"),
+        // Encoded values are exposed as is
+        DataType::Dictionary(_, _) | DataType::RunEndEncoded(_, _) => to_edm_type(value_type(dt)),
+        DataType::Null
         | DataType::List(_)
         | DataType::FixedSizeList(_, _)
         | DataType::LargeList(_)
@@ -210,12 +526,6 @@ pub fn to_edm_type(dt: &DataType) -> std::result::Result<&'static str, Unsupport
         | DataType::LargeListView(_)
         | DataType::Struct(_)
         | DataType::Union(_, _)
-        | DataType::Dictionary(_, _)
-        | DataType::Decimal32(_, _)
-        | DataType::Decimal64(_, _)
-        | DataType::Decimal128(_, _)
-        | DataType::Decimal256(_, _)
-        | DataType::Map(_, _)
-        | DataType::RunEndEncoded(_, _) => Err(UnsupportedDataType::new(dt.clone())),
+        | DataType::Map(_, _) => Err(UnsupportedDataType::new(dt.clone())),
     }
 }
@@ -0,0 +1,331 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+    sync::Arc,
+};
+
+use datafusion::{
+    arrow::{
+        array::RecordBatch,
+        compute::{cast, concat_batches},
+    },
+    prelude::*,
+    scalar::ScalarValue,
+};
+
+use crate::{
+    collection::{QueryParams, SkipToken},
+    context::{CollectionContext, Relationship},
+    encoder::{CollectionInfo, EntryLayout, ResponseEncoder},
+    error::{ODataError, PropertyNotFound, UnsupportedFeature},
+};
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Navigation property requested via `$expand` along with the context of the
+/// collection it targets
+#[derive(Clone)]
+pub struct Expansion {
+    pub relationship: Relationship,
+    pub ctx: Arc<dyn CollectionContext>,
+}
+
+impl Expansion {
+    /// Resolves `$expand` items against the navigation properties of the collection
+    pub async fn resolve(
+        ctx: &dyn CollectionContext,
+        navigation: &[Relationship],
+        expand: &[String],
+    ) -> Result<Vec<Self>, ODataError> {
+        let mut expansions = Vec::new();
+
+        for name in expand {
+            if name.contains('/') {
+                return Err(UnsupportedFeature::new("Nested $expand is not supported").into());
+            }
+
+            let Some(relationship) = navigation.iter().find(|rel| rel.name == *name) else {
+                return Err(ODataError::bad_request(format!(
+                    "Unknown navigation property in $expand: {name}"
+                )));
+            };
+
+            expansions.push(Self {
+                relationship: relationship.clone(),
+                ctx: ctx.related_collection(&relationship.to_collection).await?,
+            });
+        }
+
+        Ok(expansions)
+    }
+}
+
+/// Removes navigation properties from `$select`, which may list the expanded
+/// ones, and makes sure that columns needed to resolve the expanded properties
+/// are selected. Returns the columns that were not requested by the client.
+pub fn adjust_select(
+    select: &mut Vec<String>,
+    navigation: &[Relationship],
+    expansions: &[Expansion],
+) -> Vec<String> {
+    select.retain(|c| !navigation.iter().any(|rel| rel.name == *c));
+
+    let mut hidden_columns = Vec::new();
+    if select.is_empty() {
+        return hidden_columns;
+    }
+
+    for expansion in expansions {
+        let column = &expansion.relationship.from_column;
+        if !select.contains(column) {
+            select.push(column.clone());
+            hidden_columns.push(column.clone());
+        }
+    }
+    hidden_columns
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Collection addressed by a navigation property of an entry in the resource
+/// path, e.g. `customers(1)/orders`
+pub struct NavigationTarget {
+    pub relationship: Relationship,
+    pub ctx: Arc<dyn CollectionContext>,
+    /// Restricts the target collection to the entries related to the source entry
+    pub filter: Expr,
+    /// URL of the navigation resource without a trailing slash
+    pub url: String,
+}
+
+impl NavigationTarget {
+    /// Resolves the navigation property of the entry addressed by the source
+    /// context. Returns `None` when the source entry does not exist.
+    pub async fn resolve(
+        ctx: &dyn CollectionContext,
+        name: &str,
+    ) -> Result<Option<Self>, ODataError> {
+        let Some(relationship) = ctx.relationships().into_iter().find(|rel| rel.name == name)
+        else {
+            return Err(PropertyNotFound::new(ctx.collection_name()?, name).into());
+        };
+
+        let df = ctx
+            .query(QueryParams {
+                select: vec![relationship.from_column.clone()],
+                ..Default::default()
+            })
+            .await?;
+
+        let batches = df.collect().await.map_err(ODataError::internal)?;
+        let Some(batch) = batches.iter().find(|b| b.num_rows() != 0) else {
+            return Ok(None);
+        };
+
+        let from_col = batch.column(
+            batch
+                .schema()
+                .index_of(&relationship.from_column)
+                .map_err(ODataError::internal)?,
+        );
+        let value = ScalarValue::try_from_array(from_col, 0).map_err(ODataError::internal)?;
+
+        let target = ctx.related_collection(&relationship.to_collection).await?;
+        let to_type = target
+            .schema()
+            .await?
+            .field_with_name(&relationship.to_column)
+            .map_err(ODataError::internal)?
+            .data_type()
+            .clone();
+
+        // Null reference has no related entries
+        let filter = if value.is_null() {
+            lit(false)
+        } else {
+            let value = value.cast_to(&to_type).map_err(ODataError::internal)?;
+            col(&relationship.to_column).eq(lit(value))
+        };
+
+        let url = format!(
+            "{}({})/{}",
+            ctx.collection_base_url()?.trim_end_matches('/'),
+            ctx.addr()?
+                .key
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+            relationship.name
+        );
+
+        Ok(Some(Self {
+            relationship,
+            ctx: target,
+            filter,
+            url,
+        }))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Entries related to the records of a batch via an expanded navigation property
+pub struct RelatedEntries {
+    relationship: Relationship,
+    layout: EntryLayout,
+    batch: RecordBatch,
+    /// Index of the source column in the batch of the source entries
+    from_index: usize,
+    /// Rows of the related batch by the values of the target column
+    rows: HashMap<ScalarValue, Vec<usize>>,
+    /// Maximum number of entries related to a single source entry
+    page_size: Option<usize>,
+    /// Indices of the key columns in the related batch
+    key_indices: Vec<usize>,
+}
+
+impl RelatedEntries {
+    /// Queries entries related to all records of the batch at once. When the
+    /// related collection has a page size, at most one page of entries plus
+    /// one to detect the next page is fetched per source entry, otherwise the
+    /// related entries are not bounded. Note that the related set is also
+    /// subject to the overall limit applied by the related context.
+    pub async fn fetch(expansion: &Expansion, batch: &RecordBatch) -> Result<Self, ODataError> {
+        let rel = &expansion.relationship;
+        let ctx = expansion.ctx.as_ref();
+
+        let from_index = batch
+            .schema()
+            .index_of(&rel.from_column)
+            .map_err(ODataError::internal)?;
+        let from_col = batch.column(from_index);
+
+        let to_type = ctx
+            .schema()
+            .await?
+            .field_with_name(&rel.to_column)
+            .map_err(ODataError::internal)?
+            .data_type()
+            .clone();
+
+        let mut values = Vec::new();
+        let mut seen = HashSet::new();
+        for row in 0..batch.num_rows() {
+            let value = ScalarValue::try_from_array(from_col, row).map_err(ODataError::internal)?;
+            if !value.is_null() && seen.insert(value.clone()) {
+                values.push(value);
+            }
+        }
+        let values = values
+            .into_iter()
+            .map(|v| v.cast_to(&to_type).map(lit))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(ODataError::internal)?;
+
+        let filter = if values.is_empty() {
+            lit(false)
+        } else {
+            col(&rel.to_column).in_list(values, false)
+        };
+
+        let info = CollectionInfo::new(ctx).await?;
+        let page_size = ctx.max_page_size();
+        let df = ctx
+            .query(QueryParams {
+                order_by: info
+                    .key_column_aliases()
+                    .into_iter()
+                    .map(|c| (c, true))
+                    .collect(),
+                top: Some(usize::MAX),
+                filter: Some(filter),
+                limit_per_value: page_size.map(|n| (rel.to_column.clone(), n + 1)),
+                ..Default::default()
+            })
+            .await?;
+
+        let schema = df.schema().inner().clone();
+        let batches = df.collect().await.map_err(ODataError::internal)?;
+        ctx.validate(&batches).await?;
+        let related = concat_batches(&schema, &batches).map_err(ODataError::internal)?;
+
+        // Target values are compared in the type of the source column
+        let to_col = related.column(
+            schema
+                .index_of(&rel.to_column)
+                .map_err(ODataError::internal)?,
+        );
+        let to_col = cast(to_col, from_col.data_type()).map_err(ODataError::internal)?;
+
+        let mut rows: HashMap<ScalarValue, Vec<usize>> = HashMap::new();
+        for row in 0..related.num_rows() {
+            let value = ScalarValue::try_from_array(&to_col, row).map_err(ODataError::internal)?;
+            if !value.is_null() {
+                rows.entry(value).or_default().push(row);
+            }
+        }
+
+        let key_indices = info
+            .key_column_aliases()
+            .iter()
+            .map(|c| schema.index_of(c))
+            .collect::<Result<_, _>>()
+            .map_err(ODataError::internal)?;
+
+        let layout = EntryLayout::new(info, &schema)?.with_navigation(ctx.relationships(), &[]);
+
+        Ok(Self {
+            relationship: rel.clone(),
+            layout,
+            batch: related,
+            from_index,
+            rows,
+            page_size,
+            key_indices,
+        })
+    }
+
+    pub fn relationship(&self) -> &Relationship {
+        &self.relationship
+    }
+
+    /// Writes entries related to the record of the source batch as a nested
+    /// feed, or as a single entry for to-one relationships. Nested feed holds
+    /// a page of entries and links the rest via the navigation property `url`.
+    pub(crate) fn write(
+        &self,
+        encoder: &mut dyn ResponseEncoder,
+        writer: &mut dyn Write,
+        source: &RecordBatch,
+        row: usize,
+        url: &str,
+    ) -> Result<(), ODataError> {
+        let value = ScalarValue::try_from_array(source.column(self.from_index), row)
+            .map_err(ODataError::internal)?;
+        let rows = self.rows.get(&value).map(Vec::as_slice).unwrap_or_default();
+
+        if self.relationship.to_many() {
+            let (rows, rest) = rows.split_at(self.page_size.unwrap_or(rows.len()).min(rows.len()));
+            let next_link = match rows.last() {
+                Some(last) if !rest.is_empty() => {
+                    let skip_token = SkipToken::from_row(&self.batch, &self.key_indices, *last)
+                        .map_err(ODataError::internal)?;
+                    Some(format!("{url}?$skiptoken={}", skip_token.encode()))
+                }
+                _ => None,
+            };
+
+            encoder.start_feed(writer, self.layout.info(), None)?;
+            for row in rows {
+                self.layout
+                    .write_row(encoder, writer, &self.batch, *row, &[])?;
+            }
+            encoder.end_feed(writer, next_link.as_deref())
+        } else if let Some(row) = rows.first() {
+            self.layout
+                .write_row(encoder, writer, &self.batch, *row, &[])
+        } else {
+            Ok(())
+        }
+    }
+}
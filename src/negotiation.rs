@@ -0,0 +1,389 @@
+use std::sync::Arc;
+
+use axum::http::HeaderMap;
+
+use crate::{
+    encoder::{ResponseEncoder, ResponseEncoderFactory},
+    error::{NotAcceptable, ODataError},
+    handlers::{
+        MEDIA_TYPE_ATOM, MEDIA_TYPE_JSON_METADATA_FULL, MEDIA_TYPE_JSON_METADATA_MINIMAL,
+        MEDIA_TYPE_JSON_METADATA_NONE, MEDIA_TYPE_JSON_VERBOSE, MEDIA_TYPE_XML,
+    },
+    json::{JsonFormat, JsonMetadata},
+};
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Query options that affect only the representation of a resource
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct FormatQueryParam {
+    #[serde(rename = "$format")]
+    pub format: Option<String>,
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Representation of a resource that handlers can produce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    /// Atom feeds and entries
+    Atom,
+    /// Plain XML documents, e.g. service document and `$metadata`
+    Xml,
+    /// JSON Verbose or v4 JSON
+    Json(JsonFormat),
+}
+
+impl ResponseFormat {
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Self::Atom => MEDIA_TYPE_ATOM,
+            Self::Xml => MEDIA_TYPE_XML,
+            Self::Json(JsonFormat::Verbose) => MEDIA_TYPE_JSON_VERBOSE,
+            Self::Json(JsonFormat::V4(JsonMetadata::None)) => MEDIA_TYPE_JSON_METADATA_NONE,
+            Self::Json(JsonFormat::V4(JsonMetadata::Minimal)) => MEDIA_TYPE_JSON_METADATA_MINIMAL,
+            Self::Json(JsonFormat::V4(JsonMetadata::Full)) => MEDIA_TYPE_JSON_METADATA_FULL,
+        }
+    }
+
+    /// Returns the format this candidate resolves to when it satisfies the
+    /// media range, e.g. v4 JSON candidate picks up `odata.metadata` level
+    pub(crate) fn resolve(&self, range: &MediaRange) -> Option<Self> {
+        if range.is_any() {
+            return Some(*self);
+        }
+
+        match self {
+            Self::Atom => (range.matches("application", "atom+xml")
+                || range.matches("application", "xml"))
+            .then_some(*self),
+            Self::Xml => (range.matches("application", "xml")
+                || range.matches("application", "atomsvc+xml")
+                || range.matches("text", "xml"))
+            .then_some(*self),
+            Self::Json(JsonFormat::Verbose) => (range.matches("application", "json")
+                && range.param("odata") == Some("verbose"))
+            .then_some(*self),
+            Self::Json(JsonFormat::V4(_)) => {
+                if !range.matches("application", "json") || range.param("odata").is_some() {
+                    return None;
+                }
+                match range
+                    .param("odata.metadata")
+                    .or_else(|| range.param("metadata"))
+                {
+                    None => Some(*self),
+                    Some(level) => level.parse().ok().map(|l| Self::Json(JsonFormat::V4(l))),
+                }
+            }
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Picks the representation of a resource among the `supported` formats.
+///
+/// The `$format` query option takes precedence over the `Accept` header. When
+/// neither is specified the first supported format is used as the default.
+///
+/// See: https://www.odata.org/documentation/odata-version-3-0/odata-version-3-0-core-protocol/#formatsystemqueryoption
+pub fn negotiate_format(
+    format: Option<&str>,
+    headers: &HeaderMap,
+    supported: &[ResponseFormat],
+) -> Result<ResponseFormat, ODataError> {
+    negotiate(
+        format,
+        headers,
+        || Some(supported[0]),
+        |range| supported.iter().filter_map(|f| f.resolve(range)).collect(),
+        |f| f.media_type().to_string(),
+    )
+}
+
+/// Same as [`negotiate_format`] but picks among the encoder factories, with
+/// the first factory acting as the default
+pub fn negotiate_encoder(
+    format: Option<&str>,
+    headers: &HeaderMap,
+    factories: &[Arc<dyn ResponseEncoderFactory>],
+) -> Result<Box<dyn ResponseEncoder>, ODataError> {
+    let any = MediaRange {
+        typ: "*".to_string(),
+        subtype: "*".to_string(),
+        params: Vec::new(),
+        q: 1.0,
+    };
+
+    negotiate(
+        format,
+        headers,
+        || factories.first().and_then(|f| f.resolve(&any)),
+        |range| factories.iter().filter_map(|f| f.resolve(range)).collect(),
+        |encoder| encoder.media_type().to_string(),
+    )
+}
+
+fn negotiate<T>(
+    format: Option<&str>,
+    headers: &HeaderMap,
+    default: impl FnOnce() -> Option<T>,
+    resolve: impl Fn(&MediaRange) -> Vec<T>,
+    media_type: impl Fn(&T) -> String,
+) -> Result<T, ODataError> {
+    let requested = match format {
+        Some("atom") => "application/atom+xml".to_string(),
+        Some("xml") => "application/xml".to_string(),
+        Some("json") => "application/json;odata=verbose".to_string(),
+        Some(media_type) => media_type.to_string(),
+        None => headers
+            .get_all(http::header::ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(","),
+    };
+
+    let ranges = parse_accept(&requested);
+    if ranges.is_empty()
+        && format.is_none()
+        && let Some(default) = default()
+    {
+        return Ok(default);
+    }
+
+    // Ranges with zero quality mark the media types they include as not
+    // acceptable, so that e.g. `*/*` does not resolve to them
+    let (excluded, mut ranges): (Vec<_>, Vec<_>) = ranges.into_iter().partition(|r| r.q <= 0.0);
+
+    // Stable sort keeps the client's order for ranges of equal preference
+    ranges.sort_by(|a, b| {
+        b.q.total_cmp(&a.q)
+            .then_with(|| b.specificity().cmp(&a.specificity()))
+    });
+
+    for range in &ranges {
+        let found = resolve(range).into_iter().find(|candidate| {
+            let media_type = media_type(candidate);
+            !excluded.iter().any(|e| e.includes(&media_type))
+        });
+        if let Some(found) = found {
+            return Ok(found);
+        }
+    }
+
+    Err(NotAcceptable::new(requested).into())
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Single element of the `Accept` header
+///
+/// See: https://httpwg.org/specs/rfc9110.html#field.accept
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaRange {
+    pub typ: String,
+    pub subtype: String,
+    pub params: Vec<(String, String)>,
+    pub q: f32,
+}
+
+impl MediaRange {
+    /// Whether the range is `*/*`
+    pub fn is_any(&self) -> bool {
+        self.typ == "*" && self.subtype == "*"
+    }
+
+    /// Whether the range includes the specified media type, e.g. `text/*`
+    /// matches `text/csv`
+    pub fn matches(&self, typ: &str, subtype: &str) -> bool {
+        self.typ.eq_ignore_ascii_case(typ)
+            && (self.subtype == "*" || self.subtype.eq_ignore_ascii_case(subtype))
+    }
+
+    /// Whether the media type falls within the range, i.e. matches its type
+    /// and carries all of its parameters, e.g. `application/json` includes
+    /// `application/json;odata.metadata=full`
+    pub fn includes(&self, media_type: &str) -> bool {
+        let Ok(other) = media_type.parse::<MediaRange>() else {
+            return false;
+        };
+        (self.is_any() || self.matches(&other.typ, &other.subtype))
+            && self.params.iter().all(|(name, value)| {
+                other
+                    .param(name)
+                    .is_some_and(|v| v.eq_ignore_ascii_case(value))
+            })
+    }
+
+    /// Value of a media type parameter, e.g. `odata.metadata`
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn specificity(&self) -> usize {
+        match (self.typ.as_str(), self.subtype.as_str()) {
+            ("*", _) => 0,
+            (_, "*") => 1,
+            _ => 2 + self.params.len(),
+        }
+    }
+}
+
+impl std::str::FromStr for MediaRange {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(';').map(str::trim);
+
+        let (typ, subtype) = parts.next().and_then(|mt| mt.split_once('/')).ok_or(())?;
+        if typ.is_empty() || subtype.is_empty() {
+            return Err(());
+        }
+
+        let mut params = Vec::new();
+        let mut q = 1.0;
+
+        for part in parts.filter(|p| !p.is_empty()) {
+            let (name, value) = part.split_once('=').ok_or(())?;
+            let (name, value) = (name.trim(), value.trim().trim_matches('"'));
+            if name.eq_ignore_ascii_case("q") {
+                q = value.parse().map_err(|_| ())?;
+            } else {
+                params.push((name.to_string(), value.to_string()));
+            }
+        }
+
+        Ok(Self {
+            typ: typ.to_string(),
+            subtype: subtype.to_string(),
+            params,
+            q,
+        })
+    }
+}
+
+/// Parses the value of the `Accept` header skipping malformed media ranges
+pub fn parse_accept(accept: &str) -> Vec<MediaRange> {
+    accept
+        .split(',')
+        .filter_map(|r| r.trim().parse().ok())
+        .collect()
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLLECTION_FORMATS: [ResponseFormat; 3] = [
+        ResponseFormat::Atom,
+        ResponseFormat::Json(JsonFormat::V4(JsonMetadata::Minimal)),
+        ResponseFormat::Json(JsonFormat::Verbose),
+    ];
+
+    fn negotiate(format: Option<&str>, accept: Option<&str>) -> Result<ResponseFormat, ODataError> {
+        let mut headers = HeaderMap::new();
+        if let Some(accept) = accept {
+            headers.insert(http::header::ACCEPT, accept.parse().unwrap());
+        }
+        negotiate_format(format, &headers, &COLLECTION_FORMATS)
+    }
+
+    #[test]
+    fn test_parse_accept() {
+        assert_eq!(
+            parse_accept("application/json;odata.metadata=full;q=0.5, text/*, bad"),
+            vec![
+                MediaRange {
+                    typ: "application".to_string(),
+                    subtype: "json".to_string(),
+                    params: vec![("odata.metadata".to_string(), "full".to_string())],
+                    q: 0.5,
+                },
+                MediaRange {
+                    typ: "text".to_string(),
+                    subtype: "*".to_string(),
+                    params: Vec::new(),
+                    q: 1.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_negotiate_format() {
+        assert_eq!(negotiate(None, None).unwrap(), ResponseFormat::Atom);
+        assert_eq!(negotiate(None, Some("*/*")).unwrap(), ResponseFormat::Atom);
+        assert_eq!(
+            negotiate(
+                None,
+                Some("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
+            )
+            .unwrap(),
+            ResponseFormat::Atom
+        );
+        assert_eq!(
+            negotiate(None, Some("application/atom+xml;q=0.5, application/json")).unwrap(),
+            ResponseFormat::Json(JsonFormat::V4(JsonMetadata::Minimal))
+        );
+        assert_eq!(
+            negotiate(None, Some("application/json;odata.metadata=none")).unwrap(),
+            ResponseFormat::Json(JsonFormat::V4(JsonMetadata::None))
+        );
+        assert_eq!(
+            negotiate(None, Some("application/json;odata=verbose")).unwrap(),
+            ResponseFormat::Json(JsonFormat::Verbose)
+        );
+        assert_eq!(
+            negotiate(Some("json"), Some("application/atom+xml")).unwrap(),
+            ResponseFormat::Json(JsonFormat::Verbose)
+        );
+        assert_eq!(
+            negotiate(Some("application/json;odata.metadata=full"), None).unwrap(),
+            ResponseFormat::Json(JsonFormat::V4(JsonMetadata::Full))
+        );
+        assert_eq!(
+            negotiate(Some("atom"), Some("application/json")).unwrap(),
+            ResponseFormat::Atom
+        );
+        assert_eq!(
+            negotiate(None, Some("application/atom+xml;q=0, */*")).unwrap(),
+            ResponseFormat::Json(JsonFormat::V4(JsonMetadata::Minimal))
+        );
+        assert_eq!(
+            negotiate(
+                None,
+                Some("application/json;odata=verbose;q=0, application/json;q=0.5, */*")
+            )
+            .unwrap(),
+            ResponseFormat::Atom
+        );
+
+        assert!(matches!(
+            negotiate(None, Some("text/csv")),
+            Err(ODataError::NotAcceptable(_))
+        ));
+        assert!(matches!(
+            negotiate(None, Some("application/json;odata.metadata=bogus")),
+            Err(ODataError::NotAcceptable(_))
+        ));
+        assert!(matches!(
+            negotiate(None, Some("application/atom+xml;q=0")),
+            Err(ODataError::NotAcceptable(_))
+        ));
+        assert!(matches!(
+            negotiate(Some("csv"), None),
+            Err(ODataError::NotAcceptable(_))
+        ));
+        assert!(matches!(
+            negotiate(None, Some("*/*;q=0, application/json")),
+            Err(ODataError::NotAcceptable(_))
+        ));
+    }
+}
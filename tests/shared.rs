@@ -1,7 +1,24 @@
 use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
-use datafusion::{arrow::datatypes::SchemaRef, prelude::*, sql::TableReference};
+use datafusion::{
+    arrow::{
+        array::{
+            ArrayRef, BinaryArray, BooleanArray, Date32Array, Date64Array, Decimal128Array,
+            DictionaryArray, DurationMillisecondArray, DurationSecondArray, FixedSizeBinaryArray,
+            Float32Array, Float64Array, Int8Array, Int16Array, Int32Array, Int64Array,
+            IntervalYearMonthArray, LargeStringArray, ListArray, ListBuilder, MapArray, MapBuilder,
+            MapFieldNames, NullArray, RecordBatch, RunArray, StringArray, StringBuilder,
+            StringViewArray, StructArray, Time32SecondArray, Time64MicrosecondArray,
+            TimestampMillisecondArray, UInt8Array, UInt16Array, UInt32Array, UInt64Array,
+            make_array,
+        },
+        buffer::{NullBuffer, OffsetBuffer},
+        datatypes::{DataType, Field, Fields, Int32Type, Int64Type, SchemaRef},
+    },
+    prelude::*,
+    sql::TableReference,
+};
 use datafusion_odata::{
     collection::{CollectionAddr, QueryParams},
     context::*,
@@ -39,12 +56,422 @@ pub async fn fixture(collection_elem: &str) -> Arc<ODataContext> {
     ))
 }
 
+/// Customers and their orders related in both directions
+pub async fn fixture_related(collection_elem: &str) -> Arc<ODataContext> {
+    let ctx = SessionContext::new();
+
+    let customers = RecordBatch::try_from_iter([
+        ("offset", Arc::new(Int64Array::from(vec![0, 1, 2])) as _),
+        (
+            "name",
+            Arc::new(StringArray::from(vec!["alfki", "anatr", "anton"])) as _,
+        ),
+    ])
+    .unwrap();
+    ctx.register_batch("customers", customers).unwrap();
+
+    let orders = RecordBatch::try_from_iter([
+        (
+            "offset",
+            Arc::new(Int64Array::from(vec![10, 11, 12, 13])) as _,
+        ),
+        (
+            "customer_id",
+            Arc::new(Int64Array::from(vec![Some(0), Some(0), Some(1), None])) as _,
+        ),
+    ])
+    .unwrap();
+    ctx.register_batch("orders", orders).unwrap();
+
+    let mut odata_ctx = ODataContext::new(
+        ctx,
+        "http://example.com/odata".to_string(),
+        Some(CollectionAddr::decode(collection_elem).unwrap()),
+    );
+    odata_ctx.relationships = vec![
+        Relationship {
+            name: "orders".to_string(),
+            from_collection: "customers".to_string(),
+            from_column: "offset".to_string(),
+            to_collection: "orders".to_string(),
+            to_column: "customer_id".to_string(),
+            multiplicity: Multiplicity::Many,
+        },
+        Relationship {
+            name: "customer".to_string(),
+            from_collection: "orders".to_string(),
+            from_column: "customer_id".to_string(),
+            to_collection: "customers".to_string(),
+            to_column: "offset".to_string(),
+            multiplicity: Multiplicity::ZeroOrOne,
+        },
+    ];
+    Arc::new(odata_ctx)
+}
+
+/// Order lines keyed by the order and the line number
+pub async fn fixture_composite(collection_elem: &str) -> Arc<ODataContext> {
+    let ctx = SessionContext::new();
+
+    let order_lines = RecordBatch::try_from_iter([
+        (
+            "order_id",
+            Arc::new(Int64Array::from(vec![10, 10, 11])) as _,
+        ),
+        ("line", Arc::new(Int64Array::from(vec![1, 2, 1])) as _),
+        ("quantity", Arc::new(Int64Array::from(vec![5, 1, 2])) as _),
+    ])
+    .unwrap();
+    ctx.register_batch("order_lines", order_lines).unwrap();
+
+    let mut odata_ctx = ODataContext::new(
+        ctx,
+        "http://example.com/odata".to_string(),
+        Some(CollectionAddr::decode(collection_elem).unwrap()),
+    );
+    odata_ctx.key_columns = vec!["order_id".to_string(), "line".to_string()];
+    Arc::new(odata_ctx)
+}
+
+/// Instruments with columns of types not present in the parquet fixtures
+pub async fn fixture_types(collection_elem: &str) -> Arc<ODataContext> {
+    let ctx = SessionContext::new();
+
+    let instruments = RecordBatch::try_from_iter([
+        ("offset", Arc::new(Int64Array::from(vec![0, 1, 2])) as _),
+        (
+            "price",
+            Arc::new(
+                Decimal128Array::from(vec![Some(12345), Some(-5), None])
+                    .with_precision_and_scale(10, 2)
+                    .unwrap(),
+            ) as _,
+        ),
+        (
+            "hash",
+            Arc::new(
+                FixedSizeBinaryArray::try_from_iter(
+                    [[0x0a, 0xff], [0x00, 0x00], [0xfb, 0xff]].into_iter(),
+                )
+                .unwrap(),
+            ) as _,
+        ),
+        (
+            "payload",
+            Arc::new(BinaryArray::from(vec![
+                Some(&b"odata"[..]),
+                Some(&b""[..]),
+                None,
+            ])) as _,
+        ),
+        (
+            "opens",
+            Arc::new(Time32SecondArray::from(vec![
+                Some(34_200),
+                Some(57_600),
+                None,
+            ])) as _,
+        ),
+        (
+            "settlement",
+            Arc::new(DurationMillisecondArray::from(vec![172_800_000, -1_500, 0])) as _,
+        ),
+        ("address", Arc::new(address_array()) as _),
+        ("tags", Arc::new(tags_array()) as _),
+        ("fills", Arc::new(fills_array()) as _),
+        (
+            "exchange",
+            Arc::new(DictionaryArray::<Int32Type>::from_iter([
+                Some("nyse"),
+                Some("nyse"),
+                None,
+            ])) as _,
+        ),
+        (
+            "currency",
+            Arc::new(
+                RunArray::try_new(
+                    &Int32Array::from(vec![2, 3]),
+                    &StringArray::from(vec!["usd", "eur"]),
+                )
+                .unwrap(),
+            ) as _,
+        ),
+        ("attributes", Arc::new(attributes_array()) as _),
+        (
+            "location",
+            Arc::new(BinaryArray::from(vec![
+                Some(&wkb_point(-123.1207, 49.2827)[..]),
+                Some(&wkb_point(-79.3832, 43.6532)[..]),
+                None,
+            ])) as _,
+        ),
+        (
+            "zone",
+            Arc::new(StringArray::from(vec![
+                Some("POLYGON((0 0, 4 0, 4 4, 0 4, 0 0))"),
+                Some("LINESTRING(5 0, 5 4)"),
+                None,
+            ])) as _,
+        ),
+    ])
+    .unwrap();
+    ctx.register_batch("instruments", instruments).unwrap();
+
+    Arc::new(ODataContext::new(
+        ctx,
+        "http://example.com/odata".to_string(),
+        Some(CollectionAddr::decode(collection_elem).unwrap()),
+    ))
+}
+
+/// Entries with nullable columns of every supported type: the first one holds
+/// values and the second one nulls. Slots of the nulls hold the same values
+/// as the first entry, so encoders must not read them.
+pub async fn fixture_nulls(collection_elem: &str) -> Arc<ODataContext> {
+    let ctx = SessionContext::new();
+
+    let columns: Vec<(&str, ArrayRef)> = vec![
+        ("boolean", Arc::new(BooleanArray::from(vec![true, true]))),
+        ("int8", Arc::new(Int8Array::from(vec![-8, -8]))),
+        ("int16", Arc::new(Int16Array::from(vec![-16, -16]))),
+        ("int32", Arc::new(Int32Array::from(vec![-32, -32]))),
+        ("int64", Arc::new(Int64Array::from(vec![-64, -64]))),
+        ("uint8", Arc::new(UInt8Array::from(vec![8, 8]))),
+        ("uint16", Arc::new(UInt16Array::from(vec![16, 16]))),
+        ("uint32", Arc::new(UInt32Array::from(vec![32, 32]))),
+        ("uint64", Arc::new(UInt64Array::from(vec![64, 64]))),
+        ("float32", Arc::new(Float32Array::from(vec![1.5, 1.5]))),
+        ("float64", Arc::new(Float64Array::from(vec![2.5, 2.5]))),
+        ("utf8", Arc::new(StringArray::from(vec!["a", "a"]))),
+        (
+            "large_utf8",
+            Arc::new(LargeStringArray::from(vec!["b", "b"])),
+        ),
+        ("utf8_view", Arc::new(StringViewArray::from(vec!["c", "c"]))),
+        (
+            "timestamp",
+            Arc::new(TimestampMillisecondArray::from(vec![
+                1_700_000_000_000,
+                1_700_000_000_000,
+            ])),
+        ),
+        (
+            "timestamp_tz",
+            Arc::new(
+                TimestampMillisecondArray::from(vec![1_700_000_000_000, 1_700_000_000_000])
+                    .with_timezone("UTC"),
+            ),
+        ),
+        ("date32", Arc::new(Date32Array::from(vec![19_000, 19_000]))),
+        (
+            "date64",
+            Arc::new(Date64Array::from(vec![
+                1_641_600_000_000,
+                1_641_600_000_000,
+            ])),
+        ),
+        (
+            "decimal",
+            Arc::new(
+                Decimal128Array::from(vec![12345, 12345])
+                    .with_precision_and_scale(10, 2)
+                    .unwrap(),
+            ),
+        ),
+        (
+            "binary",
+            Arc::new(BinaryArray::from(vec![&b"odata"[..], &b"odata"[..]])),
+        ),
+        (
+            "fixed_binary",
+            Arc::new(
+                FixedSizeBinaryArray::try_from_iter([[0x0a, 0xff], [0x0a, 0xff]].into_iter())
+                    .unwrap(),
+            ),
+        ),
+        (
+            "time",
+            Arc::new(Time64MicrosecondArray::from(vec![
+                34_200_000_000,
+                34_200_000_000,
+            ])),
+        ),
+        (
+            "duration",
+            Arc::new(DurationSecondArray::from(vec![90, 90])),
+        ),
+        (
+            "interval",
+            Arc::new(IntervalYearMonthArray::from(vec![14, 14])),
+        ),
+        (
+            "dictionary",
+            Arc::new(DictionaryArray::<Int32Type>::from_iter([
+                Some("d"),
+                Some("d"),
+            ])),
+        ),
+        (
+            "complex",
+            Arc::new(StructArray::from(vec![(
+                Arc::new(Field::new("a", DataType::Int64, false)),
+                Arc::new(Int64Array::from(vec![1, 1])) as ArrayRef,
+            )])),
+        ),
+        (
+            "collection",
+            Arc::new(ListArray::from_iter_primitive::<Int64Type, _, _>([
+                Some([Some(1)]),
+                Some([Some(1)]),
+            ])),
+        ),
+    ];
+
+    let nulls = NullBuffer::from(vec![true, false]);
+    let mut nullables: Vec<(&str, ArrayRef)> =
+        vec![("offset", Arc::new(Int64Array::from(vec![0, 1])))];
+    for (name, array) in columns {
+        let data = array
+            .to_data()
+            .into_builder()
+            .nulls(Some(nulls.clone()))
+            .build()
+            .unwrap();
+        nullables.push((name, make_array(data)));
+    }
+    ctx.register_batch("nullables", RecordBatch::try_from_iter(nullables).unwrap())
+        .unwrap();
+
+    Arc::new(ODataContext::new(
+        ctx,
+        "http://example.com/odata".to_string(),
+        Some(CollectionAddr::decode(collection_elem).unwrap()),
+    ))
+}
+
+/// Entries with a complex column holding a member of a type that cannot be
+/// exposed in the EDM
+pub async fn fixture_unsupported(collection_elem: &str) -> Arc<ODataContext> {
+    let ctx = SessionContext::new();
+
+    let complex = StructArray::from(vec![
+        (
+            Arc::new(Field::new("a", DataType::Int64, false)),
+            Arc::new(Int64Array::from(vec![1])) as ArrayRef,
+        ),
+        (
+            Arc::new(Field::new("b", DataType::Null, true)),
+            Arc::new(NullArray::new(1)) as ArrayRef,
+        ),
+    ]);
+    let batch = RecordBatch::try_from_iter([
+        ("offset", Arc::new(Int64Array::from(vec![0])) as ArrayRef),
+        ("complex", Arc::new(complex) as ArrayRef),
+    ])
+    .unwrap();
+    ctx.register_batch("unsupported", batch).unwrap();
+
+    Arc::new(ODataContext::new(
+        ctx,
+        "http://example.com/odata".to_string(),
+        Some(CollectionAddr::decode(collection_elem).unwrap()),
+    ))
+}
+
+// Little-endian WKB of a point
+fn wkb_point(x: f64, y: f64) -> Vec<u8> {
+    let mut wkb = vec![1];
+    wkb.extend(1u32.to_le_bytes());
+    wkb.extend(x.to_le_bytes());
+    wkb.extend(y.to_le_bytes());
+    wkb
+}
+
+// Rows: {city: Vancouver, geo: {lat: 49.25}}, null, {city: Toronto, geo: null}
+fn address_array() -> StructArray {
+    let geo_fields = Fields::from(vec![Field::new("lat", DataType::Float64, false)]);
+    let geo = StructArray::new(
+        geo_fields.clone(),
+        vec![Arc::new(Float64Array::from(vec![49.25, 0.0, 0.0])) as _],
+        Some(NullBuffer::from(vec![true, false, false])),
+    );
+
+    StructArray::new(
+        Fields::from(vec![
+            Field::new("city", DataType::Utf8, true),
+            Field::new("geo", DataType::Struct(geo_fields), true),
+        ]),
+        vec![
+            Arc::new(StringArray::from(vec![
+                Some("Vancouver"),
+                None,
+                Some("Toronto"),
+            ])) as _,
+            Arc::new(geo) as _,
+        ],
+        Some(NullBuffer::from(vec![true, false, true])),
+    )
+}
+
+// Rows: [etf, usd], [], null
+fn tags_array() -> ListArray {
+    let mut builder = ListBuilder::new(StringBuilder::new());
+    builder.append_value([Some("etf"), Some("usd")]);
+    builder.append_value(Vec::<Option<&str>>::new());
+    builder.append_null();
+    builder.finish()
+}
+
+// Rows: [{qty: 10}, {qty: 5}], [{qty: 1}], []
+fn fills_array() -> ListArray {
+    let fill_fields = Fields::from(vec![Field::new("qty", DataType::Int64, false)]);
+    let fills = StructArray::new(
+        fill_fields.clone(),
+        vec![Arc::new(Int64Array::from(vec![10, 5, 1])) as _],
+        None,
+    );
+
+    ListArray::new(
+        Arc::new(Field::new("item", DataType::Struct(fill_fields), false)),
+        OffsetBuffer::from_lengths([2, 1, 0]),
+        Arc::new(fills),
+        None,
+    )
+}
+
+// Rows: {sector: etf, region: null}, {}, null
+fn attributes_array() -> MapArray {
+    let mut builder = MapBuilder::new(
+        Some(MapFieldNames {
+            entry: "entries".to_string(),
+            key: "key".to_string(),
+            value: "value".to_string(),
+        }),
+        StringBuilder::new(),
+        StringBuilder::new(),
+    );
+    builder.keys().append_value("sector");
+    builder.values().append_value("etf");
+    builder.keys().append_value("region");
+    builder.values().append_null();
+    builder.append(true).unwrap();
+    builder.append(true).unwrap();
+    builder.append(false).unwrap();
+    builder.finish()
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
 pub struct ODataContext {
     query_ctx: SessionContext,
     service_base_url: String,
     addr: Option<CollectionAddr>,
+    pub max_page_size: Option<usize>,
+    pub relationships: Vec<Relationship>,
+    pub key_columns: Vec<String>,
+    pub enum_columns: Vec<EnumColumn>,
+    pub spatial_columns: Vec<SpatialColumn>,
 }
 
 impl ODataContext {
@@ -57,6 +484,11 @@ impl ODataContext {
             query_ctx,
             service_base_url,
             addr,
+            max_page_size: None,
+            relationships: Vec::new(),
+            key_columns: vec!["offset".to_string()],
+            enum_columns: Vec::new(),
+            spatial_columns: Vec::new(),
         }
     }
 }
@@ -85,13 +517,25 @@ impl ServiceContext for ODataContext {
                 addr: Some(CollectionAddr {
                     name: table_name,
                     key: None,
+                    property: None,
+                    value: false,
+                    count: false,
                 }),
+                max_page_size: self.max_page_size,
+                relationships: self.relationships.clone(),
+                key_columns: self.key_columns.clone(),
+                enum_columns: self.enum_columns.clone(),
+                spatial_columns: self.spatial_columns.clone(),
             }));
         }
 
         Ok(collections)
     }
 
+    fn relationships(&self) -> Vec<Relationship> {
+        self.relationships.clone()
+    }
+
     fn on_unsupported_feature(&self) -> OnUnsupported {
         OnUnsupported::Error
     }
@@ -149,11 +593,13 @@ impl CollectionContext for ODataContext {
                 )
             })?;
 
+        let key_columns: Vec<_> = self.key_columns.iter().map(String::as_str).collect();
+
         query
             .apply(
                 df,
                 self.addr()?,
-                "offset",
+                &key_columns,
                 &self.key_column_alias(),
                 100,
                 usize::MAX,
@@ -161,6 +607,53 @@ impl CollectionContext for ODataContext {
             .map_err(ODataError::internal)
     }
 
+    fn key_columns(&self) -> Result<Vec<String>, ODataError> {
+        Ok(self.key_columns.clone())
+    }
+
+    fn max_page_size(&self) -> Option<usize> {
+        self.max_page_size
+    }
+
+    fn enum_columns(&self) -> Vec<EnumColumn> {
+        self.enum_columns.clone()
+    }
+
+    fn spatial_columns(&self) -> Vec<SpatialColumn> {
+        self.spatial_columns.clone()
+    }
+
+    fn relationships(&self) -> Vec<Relationship> {
+        let collection_name = self.addr.as_ref().unwrap().name.as_str();
+        self.relationships
+            .iter()
+            .filter(|rel| rel.from_collection == collection_name)
+            .cloned()
+            .collect()
+    }
+
+    async fn related_collection(
+        &self,
+        collection_name: &str,
+    ) -> Result<Arc<dyn CollectionContext>, ODataError> {
+        Ok(Arc::new(ODataContext {
+            query_ctx: self.query_ctx.clone(),
+            service_base_url: self.service_base_url.clone(),
+            addr: Some(CollectionAddr {
+                name: collection_name.to_string(),
+                key: None,
+                property: None,
+                value: false,
+                count: false,
+            }),
+            max_page_size: self.max_page_size,
+            relationships: self.relationships.clone(),
+            key_columns: self.key_columns.clone(),
+            enum_columns: self.enum_columns.clone(),
+            spatial_columns: self.spatial_columns.clone(),
+        }))
+    }
+
     fn on_unsupported_feature(&self) -> OnUnsupported {
         OnUnsupported::Error
     }
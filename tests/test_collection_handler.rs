@@ -1,9 +1,22 @@
 mod shared;
 
-use datafusion_odata::collection::QueryParamsRaw;
+use datafusion_odata::{
+    collection::{QueryParamsRaw, SkipToken},
+    context::{EnumColumn, SpatialColumn},
+    geo::{SpatialShape, SpatialType},
+};
 use indoc::indoc;
 
-use shared::fixture;
+use shared::{
+    fixture, fixture_composite, fixture_nulls, fixture_related, fixture_types, fixture_unsupported,
+};
+
+async fn body_string(resp: axum::response::Response) -> String {
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
 
 #[tokio::test]
 async fn test_collection() {
@@ -13,16 +26,15 @@ async fn test_collection() {
         axum::extract::Query(QueryParamsRaw {
             select: Some("offset,from_symbol,to_symbol,close".to_string()),
             order_by: Some("offset asc".to_string()),
-            skip: None,
             top: Some(2),
-            filter: None,
+            ..Default::default()
         }),
         axum::http::HeaderMap::new(),
     )
     .await
     .unwrap();
     pretty_assertions::assert_eq!(
-        *resp.body(),
+        body_string(resp).await,
         indoc!(
             r#"
             <?xml version="1.0" encoding="utf-8"?>
@@ -83,17 +95,14 @@ async fn test_collection_entity_by_id() {
         axum::Extension(ctx),
         axum::extract::Query(QueryParamsRaw {
             select: Some("offset,close".to_string()),
-            order_by: None,
-            skip: None,
-            top: None,
-            filter: None,
+            ..Default::default()
         }),
         axum::http::HeaderMap::new(),
     )
     .await
     .unwrap();
     pretty_assertions::assert_eq!(
-        *resp.body(),
+        body_string(resp).await,
         indoc!(
             r#"
             <?xml version="1.0" encoding="utf-8"?>
@@ -130,10 +139,7 @@ async fn test_collection_entity_by_id_not_found() {
         axum::Extension(ctx),
         axum::extract::Query(QueryParamsRaw {
             select: Some("offset,close".to_string()),
-            order_by: None,
-            skip: None,
-            top: None,
-            filter: None,
+            ..Default::default()
         }),
         axum::http::HeaderMap::new(),
     )
@@ -152,16 +158,15 @@ async fn test_collection_with_filter() {
         axum::extract::Query(QueryParamsRaw {
             select: Some("offset,close".to_string()),
             order_by: Some("offset asc".to_string()),
-            skip: None,
-            top: None,
             filter: Some("offset eq 0".parse().unwrap()),
+            ..Default::default()
         }),
         axum::http::HeaderMap::new(),
     )
     .await
     .unwrap();
     pretty_assertions::assert_eq!(
-        *resp.body(),
+        body_string(resp).await,
         indoc!(
             r#"
             <?xml version="1.0" encoding="utf-8"?>
@@ -204,17 +209,14 @@ async fn test_collection_with_null_values() {
         axum::Extension(ctx),
         axum::extract::Query(QueryParamsRaw {
             select: Some("offset,close".to_string()),
-            order_by: None,
-            skip: None,
-            top: None,
-            filter: None,
+            ..Default::default()
         }),
         axum::http::HeaderMap::new(),
     )
     .await
     .unwrap();
     pretty_assertions::assert_eq!(
-        *resp.body(),
+        body_string(resp).await,
         indoc!(
             r#"
             <?xml version="1.0" encoding="utf-8"?>
@@ -241,3 +243,2612 @@ async fn test_collection_with_null_values() {
         .replace('\n', "")
     );
 }
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_json_verbose() {
+    let ctx = fixture("tickers.spy").await;
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("offset,from_symbol,close".to_string()),
+            order_by: Some("offset asc".to_string()),
+            top: Some(2),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::from_iter([(
+            http::header::ACCEPT,
+            "application/json;odata=verbose".parse().unwrap(),
+        )]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        resp.headers()[http::header::CONTENT_TYPE],
+        datafusion_odata::handlers::MEDIA_TYPE_JSON_VERBOSE
+    );
+    // Metadata precedes the properties, which follow the order of the columns
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        indoc!(
+            r#"
+            {"d":{"results":[
+            {"__metadata":{"id":"http://example.com/odatatickers.spy(0)","uri":"http://example.com/odatatickers.spy(0)","type":"default.tickers.spy"},
+            "offset":"0","from_symbol":"spy","close":135.5625},
+            {"__metadata":{"id":"http://example.com/odatatickers.spy(1)","uri":"http://example.com/odatatickers.spy(1)","type":"default.tickers.spy"},
+            "offset":"1","from_symbol":"spy","close":134.5937}
+            ]}}
+            "#
+        )
+        .replace('\n', "")
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_entity_by_id_json_verbose() {
+    let ctx = fixture("tickers.spy(2)").await;
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("offset,close".to_string()),
+            format: Some("json".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap(),
+        serde_json::json!({
+            "d": {
+                "__metadata": {
+                    "id": "http://example.com/odatatickers.spy(2)",
+                    "uri": "http://example.com/odatatickers.spy(2)",
+                    "type": "default.tickers.spy",
+                },
+                "offset": "2",
+                "close": null,
+            }
+        })
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_json() {
+    let ctx = fixture("tickers.spy").await;
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("offset,from_symbol,close".to_string()),
+            order_by: Some("offset asc".to_string()),
+            top: Some(2),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::from_iter([(
+            http::header::ACCEPT,
+            "application/json".parse().unwrap(),
+        )]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        resp.headers()[http::header::CONTENT_TYPE],
+        datafusion_odata::handlers::MEDIA_TYPE_JSON_METADATA_MINIMAL
+    );
+    assert_eq!(resp.headers()["OData-Version"], "4.0");
+    pretty_assertions::assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap(),
+        serde_json::json!({
+            "@odata.context": "http://example.com/odata/$metadata#tickers.spy",
+            "value": [
+                {
+                    "offset": 0,
+                    "from_symbol": "spy",
+                    "close": 135.5625,
+                },
+                {
+                    "offset": 1,
+                    "from_symbol": "spy",
+                    "close": 134.5937,
+                },
+            ]
+        })
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_json_metadata_none() {
+    let ctx = fixture("tickers.spy").await;
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("offset,close".to_string()),
+            order_by: Some("offset asc".to_string()),
+            top: Some(1),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::from_iter([(
+            http::header::ACCEPT,
+            "application/json;odata.metadata=none".parse().unwrap(),
+        )]),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap(),
+        serde_json::json!({
+            "value": [
+                {
+                    "offset": 0,
+                    "close": 135.5625,
+                },
+            ]
+        })
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_entity_by_id_json_metadata_full() {
+    let ctx = fixture("tickers.spy(1)").await;
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("offset,event_time,close".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::from_iter([(
+            http::header::ACCEPT,
+            "application/json;odata.metadata=full".parse().unwrap(),
+        )]),
+    )
+    .await
+    .unwrap();
+    // Annotations precede the values they describe
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        indoc!(
+            r##"
+            {"@odata.context":"http://example.com/odata/$metadata#tickers.spy/$entity",
+            "@odata.type":"#default.tickers.spy",
+            "@odata.id":"http://example.com/odatatickers.spy(1)",
+            "@odata.editLink":"tickers.spy(1)",
+            "offset@odata.type":"#Int64","offset":1,
+            "event_time@odata.type":"#DateTimeOffset","event_time":"1999-11-02T00:00:00Z",
+            "close":134.5937}
+            "##
+        )
+        .replace('\n', "")
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_not_acceptable() {
+    let ctx = fixture("tickers.spy").await;
+    let res = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw::default()),
+        axum::http::HeaderMap::from_iter([(http::header::ACCEPT, "text/csv".parse().unwrap())]),
+    )
+    .await;
+    let resp = axum::response::IntoResponse::into_response(res.unwrap_err());
+    assert_eq!(resp.status(), http::StatusCode::NOT_ACCEPTABLE);
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_unsupported() {
+    let res = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_unsupported("unsupported").await),
+        axum::extract::Query(QueryParamsRaw::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await;
+    assert!(
+        matches!(
+            &res,
+            Err(datafusion_odata::error::ODataError::UnsupportedDataType(err))
+                if err.data_type == datafusion::arrow::datatypes::DataType::Null
+        ),
+        "{:?}",
+        res.err()
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_excluded_media_type() {
+    // Zero quality excludes Atom that `*/*` would otherwise resolve to
+    let ctx = fixture("tickers.spy").await;
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw::default()),
+        axum::http::HeaderMap::from_iter([(
+            http::header::ACCEPT,
+            "application/atom+xml;q=0, */*".parse().unwrap(),
+        )]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        resp.headers()[http::header::CONTENT_TYPE],
+        datafusion_odata::handlers::MEDIA_TYPE_JSON_METADATA_MINIMAL
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_streamed() {
+    use futures::StreamExt;
+
+    let ctx = fixture("tickers.spy").await;
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("offset".to_string()),
+            order_by: Some("offset asc".to_string()),
+            top: Some(3),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::from_iter([(
+            http::header::ACCEPT,
+            "application/json;odata.metadata=none".parse().unwrap(),
+        )]),
+    )
+    .await
+    .unwrap();
+
+    let chunks: Vec<_> = resp
+        .into_body()
+        .into_data_stream()
+        .map(|chunk| chunk.unwrap())
+        .collect()
+        .await;
+
+    // Feed closing is flushed separately from the records
+    assert!(chunks.len() > 1);
+    assert_eq!(&chunks.last().unwrap()[..], b"]}");
+
+    let body: Vec<u8> = chunks.concat();
+    pretty_assertions::assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+        serde_json::json!({
+            "value": [
+                {"offset": 0},
+                {"offset": 1},
+                {"offset": 2},
+            ]
+        })
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_paging_next_link() {
+    let mut ctx = fixture("tickers.spy").await;
+    std::sync::Arc::get_mut(&mut ctx).unwrap().max_page_size = Some(2);
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("offset".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+
+    let skip_token = SkipToken::new(vec![Some("1".to_string())]).encode();
+    let body = body_string(resp).await;
+
+    assert_eq!(body.matches("<entry>").count(), 2);
+    assert!(
+        body.ends_with(&format!(
+            "<link rel=\"next\" href=\"http://example.com/odatatickers.spy?$select=offset&amp;$skiptoken={skip_token}\"/></feed>"
+        )),
+        "{body}"
+    );
+}
+
+/// Requests pages of the tickers feed following the next links, returns sizes
+/// of the pages and all their entries
+async fn follow_next_links(
+    mut query: QueryParamsRaw,
+    page_size: usize,
+) -> (Vec<usize>, Vec<serde_json::Value>) {
+    let mut pages = Vec::new();
+    let mut actual = Vec::new();
+
+    loop {
+        let mut ctx = fixture("tickers.spy").await;
+        std::sync::Arc::get_mut(&mut ctx).unwrap().max_page_size = Some(page_size);
+
+        let resp = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(ctx),
+            axum::extract::Query(query),
+            axum::http::HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+
+        let page = serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap();
+        let values = page["value"].as_array().unwrap();
+        pages.push(values.len());
+        actual.extend(values.iter().cloned());
+
+        let Some(next_link) = page["@odata.nextLink"].as_str() else {
+            break;
+        };
+        assert!(next_link.starts_with("http://example.com/odatatickers.spy?"));
+
+        let uri: axum::http::Uri = next_link.parse().unwrap();
+        query = axum::extract::Query::<QueryParamsRaw>::try_from_uri(&uri)
+            .unwrap()
+            .0;
+        assert!(query.skip_token.is_some());
+    }
+
+    (pages, actual)
+}
+
+#[tokio::test]
+async fn test_collection_paging_follow_next_links() {
+    let query = QueryParamsRaw {
+        select: Some("offset,close".to_string()),
+        order_by: Some("close desc".to_string()),
+        top: Some(5),
+        format: Some("application/json;odata.metadata=none".to_string()),
+        ..Default::default()
+    };
+
+    // Same query without paging
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture("tickers.spy").await),
+        axum::extract::Query(query.clone()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let expected = serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap();
+    let expected = expected["value"].as_array().unwrap().clone();
+    assert_eq!(expected.len(), 5);
+    // Nulls go first
+    assert_eq!(expected[0]["close"], serde_json::Value::Null);
+
+    let (pages, actual) = follow_next_links(query, 2).await;
+    assert_eq!(pages, vec![2, 2, 1]);
+    pretty_assertions::assert_eq!(actual, expected);
+}
+
+#[tokio::test]
+async fn test_collection_paging_order_by_unselected() {
+    let query = QueryParamsRaw {
+        select: Some("offset".to_string()),
+        order_by: Some("close desc".to_string()),
+        top: Some(5),
+        format: Some("application/json;odata.metadata=none".to_string()),
+        ..Default::default()
+    };
+
+    // Same query without paging
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture("tickers.spy").await),
+        axum::extract::Query(query.clone()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let expected = serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap();
+    let expected = expected["value"].as_array().unwrap().clone();
+    assert_eq!(expected.len(), 5);
+
+    // Ordering column is used for the skip token but is not returned
+    let (pages, actual) = follow_next_links(query, 2).await;
+    assert_eq!(pages, vec![2, 2, 1]);
+    assert!(actual.iter().all(|entry| entry.get("close").is_none()));
+    pretty_assertions::assert_eq!(actual, expected);
+}
+
+#[tokio::test]
+async fn test_collection_paging_invalid_skip_token() {
+    let res = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture("tickers.spy").await),
+        axum::extract::Query(QueryParamsRaw {
+            skip_token: Some("bogus!".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await;
+    let resp = axum::response::IntoResponse::into_response(res.unwrap_err());
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_inline_count() {
+    let ctx = fixture("tickers.spy").await;
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("offset".to_string()),
+            top: Some(1),
+            inline_count: Some("allpages".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <feed
+             xml:base="http://example.com/odata/"
+             xmlns="http://www.w3.org/2005/Atom"
+             xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+             xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata">
+            <id>http://example.com/odatatickers.spy</id>
+            <title type="text">tickers.spy</title>
+            <updated>2023-01-01T00:00:00.000Z</updated>
+            <link rel="self" title="tickers.spy" href="tickers.spy"/>
+            <m:count>6090</m:count>
+            <entry>
+            <id>http://example.com/odatatickers.spy(0)</id>
+            <category scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" term="default.tickers.spy"/>
+            <link rel="edit" title="tickers.spy" href="tickers.spy(0)"/>
+            <title/>
+            <updated>2023-01-01T00:00:00.000Z</updated>
+            <author><name/></author>
+            <content type="application/xml">
+            <m:properties>
+            <d:offset m:type="Edm.Int64">0</d:offset>
+            </m:properties>
+            </content>
+            </entry>
+            </feed>
+            "#
+        )
+        .replace('\n', "")
+    );
+}
+
+#[tokio::test]
+async fn test_collection_inline_count_json() {
+    let query = QueryParamsRaw {
+        select: Some("offset,close".to_string()),
+        top: Some(1),
+        filter: Some("close gt 135".parse().unwrap()),
+        inline_count: Some("allpages".to_string()),
+        ..Default::default()
+    };
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture("tickers.spy").await),
+        axum::extract::Query(query.clone()),
+        axum::http::HeaderMap::from_iter([(
+            http::header::ACCEPT,
+            "application/json;odata=verbose".parse().unwrap(),
+        )]),
+    )
+    .await
+    .unwrap();
+    let body = serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap();
+    assert_eq!(body["d"]["__count"], "3635");
+    assert_eq!(body["d"]["results"].as_array().unwrap().len(), 1);
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture("tickers.spy").await),
+        axum::extract::Query(query),
+        axum::http::HeaderMap::from_iter([(
+            http::header::ACCEPT,
+            "application/json;odata.metadata=none".parse().unwrap(),
+        )]),
+    )
+    .await
+    .unwrap();
+    let body = serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap();
+    assert_eq!(body["@odata.count"], 3635);
+}
+
+#[tokio::test]
+async fn test_collection_count() {
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture("tickers.spy/$count").await),
+        axum::extract::Query(QueryParamsRaw {
+            filter: Some("close gt 135".parse().unwrap()),
+            top: Some(1),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::from_iter([(
+            http::header::ACCEPT,
+            "application/json".parse().unwrap(),
+        )]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        resp.headers()[http::header::CONTENT_TYPE],
+        "text/plain;charset=utf-8"
+    );
+    assert_eq!(body_string(resp).await, "3635");
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_filter_functions() {
+    let filters = [
+        (
+            "startswith(from_symbol,'sp') and endswith(to_symbol,'usd')",
+            0,
+        ),
+        (
+            "substringof('SP',toupper(from_symbol)) and tolower(to_symbol) eq 'usd'",
+            0,
+        ),
+        ("length(to_symbol) eq 3 and indexof(to_symbol,'sd') eq 1", 0),
+        (
+            "year(event_time) eq 1999 and month(event_time) eq 11 and day(event_time) eq 2 and hour(event_time) eq 0",
+            1,
+        ),
+        (
+            "floor(close) eq 134 and ceiling(close) eq 135 and round(close) eq 135",
+            1,
+        ),
+    ];
+
+    for (filter, offset) in filters {
+        let resp = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(fixture("tickers.spy").await),
+            axum::extract::Query(QueryParamsRaw {
+                select: Some("offset,from_symbol,to_symbol,event_time,close".to_string()),
+                order_by: Some("offset asc".to_string()),
+                filter: Some(filter.parse().unwrap()),
+                top: Some(1),
+                format: Some("application/json;odata.metadata=none".to_string()),
+                ..Default::default()
+            }),
+            axum::http::HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+
+        let body = serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap();
+        assert_eq!(body["value"][0]["offset"], offset, "{filter}");
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_filter_arithmetic() {
+    let filters = [
+        // Left-to-right evaluation would yield `(offset - 4) / 2 = 0` i.e. offset 4
+        ("offset sub 4 div 2 eq 0", 2),
+        ("offset mod 3 eq 2 and offset mul 2 gt 10", 8),
+        ("(offset add 1) mul 2 eq 8", 3),
+        ("-offset add 7 eq 0", 7),
+    ];
+
+    for (filter, offset) in filters {
+        let resp = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(fixture("tickers.spy").await),
+            axum::extract::Query(QueryParamsRaw {
+                select: Some("offset".to_string()),
+                order_by: Some("offset asc".to_string()),
+                filter: Some(filter.parse().unwrap()),
+                top: Some(1),
+                format: Some("application/json;odata.metadata=none".to_string()),
+                ..Default::default()
+            }),
+            axum::http::HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+
+        let body = serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap();
+        assert_eq!(body["value"][0]["offset"], offset, "{filter}");
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_filter_literal_coercion() {
+    let filters = [
+        ("close eq 135.5625", 0),
+        ("close lt 134.6 and close gt 134.5", 1),
+        ("event_time eq datetime'1999-11-02T00:00:00'", 1),
+        ("event_time ge 1999-11-02", 1),
+        ("event_time gt 1999-11-02T00:00:00Z and offset lt 3", 2),
+    ];
+
+    for (filter, offset) in filters {
+        let resp = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(fixture("tickers.spy").await),
+            axum::extract::Query(QueryParamsRaw {
+                select: Some("offset,event_time,close".to_string()),
+                order_by: Some("offset asc".to_string()),
+                filter: Some(filter.parse().unwrap()),
+                top: Some(1),
+                format: Some("application/json;odata.metadata=none".to_string()),
+                ..Default::default()
+            }),
+            axum::http::HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+
+        let body = serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap();
+        assert_eq!(body["value"][0]["offset"], offset, "{filter}");
+    }
+
+    for filter in ["to_symbol eq 5", "close gt 'abc'", "unknown eq 1"] {
+        let res = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(fixture("tickers.spy").await),
+            axum::extract::Query(QueryParamsRaw {
+                filter: Some(filter.parse().unwrap()),
+                ..Default::default()
+            }),
+            axum::http::HeaderMap::new(),
+        )
+        .await;
+
+        assert!(
+            matches!(res, Err(datafusion_odata::error::ODataError::BadRequest(_))),
+            "{filter}"
+        );
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_expand() {
+    let ctx = fixture_related("customers(1)").await;
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            expand: Some("orders".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <entry xml:base="http://example.com/odata/"
+             xmlns="http://www.w3.org/2005/Atom"
+             xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+             xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata">
+            <id>http://example.com/odatacustomers(1)</id>
+            <category scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" term="default.customers"/>
+            <link rel="edit" title="customers" href="customers(1)"/>
+            <title/>
+            <updated>2023-01-01T00:00:00.000Z</updated>
+            <author><name/></author>
+            <link rel="http://schemas.microsoft.com/ado/2007/08/dataservices/related/orders" type="application/atom+xml;type=feed" title="orders" href="customers(1)/orders">
+            <m:inline>
+            <feed>
+            <id>http://example.com/odataorders</id>
+            <title type="text">orders</title>
+            <updated>2023-01-01T00:00:00.000Z</updated>
+            <link rel="self" title="orders" href="orders"/>
+            <entry>
+            <id>http://example.com/odataorders(12)</id>
+            <category scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" term="default.orders"/>
+            <link rel="edit" title="orders" href="orders(12)"/>
+            <title/>
+            <updated>2023-01-01T00:00:00.000Z</updated>
+            <author><name/></author>
+            <link rel="http://schemas.microsoft.com/ado/2007/08/dataservices/related/customer" type="application/atom+xml;type=entry" title="customer" href="orders(12)/customer"/>
+            <content type="application/xml">
+            <m:properties>
+            <d:offset m:type="Edm.Int64">12</d:offset>
+            <d:customer_id m:type="Edm.Int64">1</d:customer_id>
+            </m:properties>
+            </content>
+            </entry>
+            </feed>
+            </m:inline>
+            </link>
+            <content type="application/xml">
+            <m:properties>
+            <d:offset m:type="Edm.Int64">1</d:offset>
+            <d:name m:type="Edm.String">anatr</d:name>
+            </m:properties>
+            </content>
+            </entry>
+            "#
+        )
+        .replace('\n', "")
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_expand_json() {
+    let ctx = fixture_related("customers").await;
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("name".to_string()),
+            expand: Some("orders".to_string()),
+            format: Some("application/json;odata.metadata=none".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap(),
+        serde_json::json!({
+            "value": [
+                {"name": "alfki", "orders": [
+                    {"offset": 10, "customer_id": 0},
+                    {"offset": 11, "customer_id": 0},
+                ]},
+                {"name": "anatr", "orders": [
+                    {"offset": 12, "customer_id": 1},
+                ]},
+                {"name": "anton", "orders": []},
+            ]
+        })
+    );
+
+    let ctx = fixture_related("orders").await;
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("offset".to_string()),
+            expand: Some("customer".to_string()),
+            format: Some("json".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body = serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap();
+    let results = body["d"]["results"].as_array().unwrap();
+    assert_eq!(results.len(), 4);
+    assert_eq!(results[0]["customer"]["name"], "alfki");
+    assert_eq!(
+        results[0]["customer"]["orders"],
+        serde_json::json!({"__deferred": {"uri": "http://example.com/odatacustomers(0)/orders"}})
+    );
+    assert_eq!(results[2]["customer"]["name"], "anatr");
+    assert_eq!(results[3]["customer"], serde_json::Value::Null);
+    assert!(results[3].get("customer_id").is_none());
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_expand_paging() {
+    let next_link = format!(
+        "http://example.com/odatacustomers(0)/orders?$skiptoken={}",
+        SkipToken::new(vec![Some("10".to_string())]).encode()
+    );
+
+    // Related entries of each source entry are bounded by the page size
+    let mut ctx = fixture_related("customers").await;
+    std::sync::Arc::get_mut(&mut ctx).unwrap().max_page_size = Some(1);
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("name".to_string()),
+            expand: Some("orders".to_string()),
+            format: Some("application/json;odata.metadata=none".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body = serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap();
+    pretty_assertions::assert_eq!(
+        body["value"],
+        serde_json::json!([
+            {
+                "name": "alfki",
+                "orders@odata.nextLink": next_link,
+                "orders": [{"offset": 10, "customer_id": 0}],
+            },
+        ])
+    );
+
+    let mut ctx = fixture_related("customers(0)").await;
+    std::sync::Arc::get_mut(&mut ctx).unwrap().max_page_size = Some(1);
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            expand: Some("orders".to_string()),
+            format: Some("json".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body = serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap();
+    assert_eq!(body["d"]["orders"]["results"].as_array().unwrap().len(), 1);
+    assert_eq!(body["d"]["orders"]["__next"], next_link);
+
+    let mut ctx = fixture_related("customers(0)").await;
+    std::sync::Arc::get_mut(&mut ctx).unwrap().max_page_size = Some(1);
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            expand: Some("orders".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body = body_string(resp).await;
+    assert!(body.contains(&format!(
+        r#"</entry><link rel="next" href="{next_link}"/></feed></m:inline>"#
+    )));
+
+    // Next link continues with the rest of the related entries
+    let uri: axum::http::Uri = next_link.parse().unwrap();
+    let query = axum::extract::Query::<QueryParamsRaw>::try_from_uri(&uri)
+        .unwrap()
+        .0;
+    let mut ctx = fixture_related("customers(0)/orders").await;
+    std::sync::Arc::get_mut(&mut ctx).unwrap().max_page_size = Some(1);
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("application/json;odata.metadata=none".to_string()),
+            ..query
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap(),
+        serde_json::json!({"value": [{"offset": 11, "customer_id": 0}]})
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_expand_unknown_property() {
+    for expand in ["unknown", "name"] {
+        let res = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(fixture_related("customers").await),
+            axum::extract::Query(QueryParamsRaw {
+                expand: Some(expand.to_string()),
+                ..Default::default()
+            }),
+            axum::http::HeaderMap::new(),
+        )
+        .await;
+
+        assert!(
+            matches!(res, Err(datafusion_odata::error::ODataError::BadRequest(_))),
+            "{expand}"
+        );
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_navigation() {
+    let ctx = fixture_related("customers(0)/orders").await;
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("application/json;odata.metadata=none".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap(),
+        serde_json::json!({
+            "value": [
+                {"offset": 10, "customer_id": 0},
+                {"offset": 11, "customer_id": 0},
+            ]
+        })
+    );
+
+    let ctx = fixture_related("orders(12)/customer").await;
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("application/json;odata.metadata=minimal".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap(),
+        serde_json::json!({
+            "@odata.context": "http://example.com/odata/$metadata#customers/$entity",
+            "offset": 1,
+            "name": "anatr",
+        })
+    );
+
+    let ctx = fixture_related("customers(0)/orders/$count").await;
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            filter: Some("offset gt 10".parse().unwrap()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(body_string(resp).await, "1");
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_navigation_paging() {
+    let mut ctx = fixture_related("customers(0)/orders").await;
+    std::sync::Arc::get_mut(&mut ctx).unwrap().max_page_size = Some(1);
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("json".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body = serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap();
+    assert_eq!(body["d"]["results"].as_array().unwrap().len(), 1);
+    assert_eq!(
+        body["d"]["__next"],
+        format!(
+            "http://example.com/odatacustomers(0)/orders?$format=json&$skiptoken={}",
+            SkipToken::new(vec![Some("10".to_string())]).encode()
+        )
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_navigation_not_found() {
+    for collection_elem in ["customers(99)/orders", "orders(13)/customer"] {
+        let ctx = fixture_related(collection_elem).await;
+        let resp = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(ctx),
+            axum::extract::Query(QueryParamsRaw::default()),
+            axum::http::HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            resp.status(),
+            http::StatusCode::NOT_FOUND,
+            "{collection_elem}"
+        );
+    }
+
+    let res = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_related("customers(0)/unknown").await),
+        axum::extract::Query(QueryParamsRaw::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await;
+    assert!(matches!(
+        res,
+        Err(datafusion_odata::error::ODataError::PropertyNotFound(_))
+    ));
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_composite_key() {
+    let ctx = fixture_composite("order_lines(line=2,order_id=10)").await;
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("quantity".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <entry xml:base="http://example.com/odata/"
+             xmlns="http://www.w3.org/2005/Atom"
+             xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+             xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata">
+            <id>http://example.com/odataorder_lines(order_id=10,line=2)</id>
+            <category scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" term="default.order_lines"/>
+            <link rel="edit" title="order_lines" href="order_lines(order_id=10,line=2)"/>
+            <title/>
+            <updated>2023-01-01T00:00:00.000Z</updated>
+            <author><name/></author>
+            <content type="application/xml">
+            <m:properties>
+            <d:quantity m:type="Edm.Int64">1</d:quantity>
+            </m:properties>
+            </content>
+            </entry>
+            "#
+        )
+        .replace('\n', "")
+    );
+
+    for collection_elem in [
+        "order_lines(10)",
+        "order_lines(order_id=10)",
+        "order_lines(order_id=10,item=1)",
+    ] {
+        let res = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(fixture_composite(collection_elem).await),
+            axum::extract::Query(QueryParamsRaw::default()),
+            axum::http::HeaderMap::new(),
+        )
+        .await;
+        assert!(
+            matches!(res, Err(datafusion_odata::error::ODataError::BadRequest(_))),
+            "{collection_elem}"
+        );
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_composite_key_paging() {
+    let mut ctx = fixture_composite("order_lines").await;
+    std::sync::Arc::get_mut(&mut ctx).unwrap().max_page_size = Some(2);
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("application/json;odata.metadata=minimal".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let skip_token = SkipToken::new(vec![Some("10".to_string()), Some("2".to_string())]);
+    pretty_assertions::assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap(),
+        serde_json::json!({
+            "@odata.context": "http://example.com/odata/$metadata#order_lines",
+            "value": [
+                {"order_id": 10, "line": 1, "quantity": 5},
+                {"order_id": 10, "line": 2, "quantity": 1},
+            ],
+            "@odata.nextLink": format!(
+                "http://example.com/odataorder_lines?$format=application%2Fjson%3Bodata.metadata%3Dminimal&$skiptoken={}",
+                skip_token.encode()
+            ),
+        })
+    );
+
+    let mut ctx = fixture_composite("order_lines").await;
+    std::sync::Arc::get_mut(&mut ctx).unwrap().max_page_size = Some(2);
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("application/json;odata.metadata=none".to_string()),
+            skip_token: Some(skip_token.encode()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap(),
+        serde_json::json!({
+            "value": [
+                {"order_id": 11, "line": 1, "quantity": 2},
+            ],
+        })
+    );
+
+    // Token must carry values of both key columns
+    let res = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_composite("order_lines").await),
+        axum::extract::Query(QueryParamsRaw {
+            skip_token: Some(SkipToken::new(vec![Some("10".to_string())]).encode()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await;
+    assert!(matches!(
+        res,
+        Err(datafusion_odata::error::ODataError::BadRequest(_))
+    ));
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_entity_by_typed_key() {
+    let mut ctx =
+        fixture("tickers.spy(from_symbol='spy',event_time=datetimeoffset'1999-11-02T00:00:00Z')")
+            .await;
+    std::sync::Arc::get_mut(&mut ctx).unwrap().key_columns =
+        vec!["from_symbol".to_string(), "event_time".to_string()];
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("offset,close".to_string()),
+            format: Some("json".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap(),
+        serde_json::json!({
+            "d": {
+                "__metadata": {
+                    "id": "http://example.com/odatatickers.spy(from_symbol='spy',event_time=datetimeoffset'1999-11-02T00:00:00.000Z')",
+                    "uri": "http://example.com/odatatickers.spy(from_symbol='spy',event_time=datetimeoffset'1999-11-02T00:00:00.000Z')",
+                    "type": "default.tickers.spy",
+                },
+                "offset": "1",
+                "close": 134.5937,
+            }
+        })
+    );
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture("tickers.spy(1L)").await),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("close".to_string()),
+            format: Some("application/json;odata.metadata=none".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap(),
+        serde_json::json!({"close": 134.5937})
+    );
+
+    for collection_elem in ["tickers.spy('1')", "tickers.spy(one)", "tickers.spy(null)"] {
+        let res = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(fixture(collection_elem).await),
+            axum::extract::Query(QueryParamsRaw::default()),
+            axum::http::HeaderMap::new(),
+        )
+        .await;
+        assert!(
+            matches!(res, Err(datafusion_odata::error::ODataError::BadRequest(_))),
+            "{collection_elem}"
+        );
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_property() {
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture("tickers.spy(1)/close").await),
+        axum::extract::Query(QueryParamsRaw::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        resp.headers()[http::header::CONTENT_TYPE],
+        datafusion_odata::handlers::MEDIA_TYPE_XML
+    );
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <d:close
+             xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+             xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata"
+             m:type="Edm.Double">134.5937</d:close>
+            "#
+        )
+        .replace('\n', "")
+    );
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture("tickers.spy(2)/close").await),
+        axum::extract::Query(QueryParamsRaw::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <d:close
+             xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+             xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata"
+             m:type="Edm.Double" m:null="true"/>
+            "#
+        )
+        .replace('\n', "")
+    );
+
+    for (format, expected) in [
+        (
+            "application/json;odata.metadata=minimal",
+            serde_json::json!({
+                "@odata.context": "http://example.com/odata/$metadata#tickers.spy(1)/offset",
+                "value": 1,
+            }),
+        ),
+        ("json", serde_json::json!({"d": {"offset": "1"}})),
+    ] {
+        let resp = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(fixture("tickers.spy(1)/offset").await),
+            axum::extract::Query(QueryParamsRaw {
+                format: Some(format.to_string()),
+                ..Default::default()
+            }),
+            axum::http::HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        pretty_assertions::assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body_string(resp).await).unwrap(),
+            expected
+        );
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_property_value() {
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture("tickers.spy(1)/from_symbol/$value").await),
+        axum::extract::Query(QueryParamsRaw::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        resp.headers()[http::header::CONTENT_TYPE],
+        datafusion_odata::handlers::MEDIA_TYPE_TEXT
+    );
+    assert_eq!(body_string(resp).await, "spy");
+
+    for collection_elem in ["tickers.spy(2)/close/$value", "tickers.spy(999999)/close"] {
+        let resp = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(fixture(collection_elem).await),
+            axum::extract::Query(QueryParamsRaw::default()),
+            axum::http::HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            resp.status(),
+            http::StatusCode::NOT_FOUND,
+            "{collection_elem}"
+        );
+    }
+
+    let res = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture("tickers.spy(1)/unknown").await),
+        axum::extract::Query(QueryParamsRaw::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await;
+    assert!(matches!(
+        res,
+        Err(datafusion_odata::error::ODataError::PropertyNotFound(_))
+    ));
+
+    let res = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_related("customers(0)/orders/$value").await),
+        axum::extract::Query(QueryParamsRaw::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await;
+    assert!(matches!(
+        res,
+        Err(datafusion_odata::error::ODataError::BadRequest(_))
+    ));
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_decimal() {
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments(0)").await),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("price".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <entry xml:base="http://example.com/odata/"
+             xmlns="http://www.w3.org/2005/Atom"
+             xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+             xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata">
+            <id>http://example.com/odatainstruments(0)</id>
+            <category scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" term="default.instruments"/>
+            <link rel="edit" title="instruments" href="instruments(0)"/>
+            <title/>
+            <updated>2023-01-01T00:00:00.000Z</updated>
+            <author><name/></author>
+            <content type="application/xml">
+            <m:properties>
+            <d:price m:type="Edm.Decimal">123.45</d:price>
+            </m:properties>
+            </content>
+            </entry>
+            "#
+        )
+        .replace('\n', "")
+    );
+
+    // Decimals are numbers in v4 and strings in verbose JSON
+    for (format, pointer, expected) in [
+        (
+            "application/json;odata.metadata=none",
+            "/value",
+            serde_json::json!([123.45, -0.05]),
+        ),
+        ("json", "/d/results", serde_json::json!(["123.45", "-0.05"])),
+    ] {
+        let resp = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(fixture_types("instruments").await),
+            axum::extract::Query(QueryParamsRaw {
+                format: Some(format.to_string()),
+                filter: Some("price lt 1000.5M".parse().unwrap()),
+                ..Default::default()
+            }),
+            axum::http::HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+        let prices: Vec<_> = body
+            .pointer(pointer)
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["price"].clone())
+            .collect();
+        pretty_assertions::assert_eq!(serde_json::Value::from(prices), expected, "{format}");
+    }
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments(1)/price/$value").await),
+        axum::extract::Query(QueryParamsRaw::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(body_string(resp).await, "-0.05");
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_binary() {
+    let mut ctx = fixture_types("instruments(binary'FBFF')").await;
+    std::sync::Arc::get_mut(&mut ctx).unwrap().key_columns = vec!["hash".to_string()];
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("hash,payload".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <entry xml:base="http://example.com/odata/"
+             xmlns="http://www.w3.org/2005/Atom"
+             xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+             xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata">
+            <id>http://example.com/odatainstruments(binary'FBFF')</id>
+            <category scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" term="default.instruments"/>
+            <link rel="edit" title="instruments" href="instruments(binary&apos;FBFF&apos;)"/>
+            <title/>
+            <updated>2023-01-01T00:00:00.000Z</updated>
+            <author><name/></author>
+            <content type="application/xml">
+            <m:properties>
+            <d:hash m:type="Edm.Binary">+/8=</d:hash>
+            <d:payload m:type="Edm.Binary" m:null="true"/>
+            </m:properties>
+            </content>
+            </entry>
+            "#
+        )
+        .replace('\n', "")
+    );
+
+    // Base64 in JSON Verbose and base64url in v4
+    for (format, pointer, expected) in [
+        (
+            "application/json;odata.metadata=none",
+            "/value",
+            serde_json::json!([{"hash": "-_8=", "payload": null}]),
+        ),
+        (
+            "json",
+            "/d/results",
+            serde_json::json!([{"hash": "+/8=", "payload": null}]),
+        ),
+    ] {
+        let resp = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(fixture_types("instruments").await),
+            axum::extract::Query(QueryParamsRaw {
+                format: Some(format.to_string()),
+                filter: Some("hash eq X'fbff'".parse().unwrap()),
+                ..Default::default()
+            }),
+            axum::http::HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+        let values: Vec<_> = body
+            .pointer(pointer)
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| serde_json::json!({"hash": entry["hash"], "payload": entry["payload"]}))
+            .collect();
+        pretty_assertions::assert_eq!(serde_json::Value::from(values), expected, "{format}");
+    }
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments(0)/payload/$value").await),
+        axum::extract::Query(QueryParamsRaw::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        resp.headers()[http::header::CONTENT_TYPE],
+        datafusion_odata::handlers::MEDIA_TYPE_BINARY
+    );
+    assert_eq!(body_string(resp).await, "odata");
+
+    assert!(matches!(
+        "hash eq X'fbf'".parse::<datafusion_odata::filter::ODataFilter>(),
+        Err(datafusion_odata::error::ODataError::BadRequest(_))
+    ));
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_time() {
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments(1)").await),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("opens,settlement".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <entry xml:base="http://example.com/odata/"
+             xmlns="http://www.w3.org/2005/Atom"
+             xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+             xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata">
+            <id>http://example.com/odatainstruments(1)</id>
+            <category scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" term="default.instruments"/>
+            <link rel="edit" title="instruments" href="instruments(1)"/>
+            <title/>
+            <updated>2023-01-01T00:00:00.000Z</updated>
+            <author><name/></author>
+            <content type="application/xml">
+            <m:properties>
+            <d:opens m:type="Edm.Time">PT16H</d:opens>
+            <d:settlement m:type="Edm.Time">-PT1.5S</d:settlement>
+            </m:properties>
+            </content>
+            </entry>
+            "#
+        )
+        .replace('\n', "")
+    );
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments").await),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("application/json;odata.metadata=full".to_string()),
+            select: Some("offset,opens,settlement".to_string()),
+            filter: Some(
+                "opens lt time'PT10H' or settlement ge duration'P1D'"
+                    .parse()
+                    .unwrap(),
+            ),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    pretty_assertions::assert_eq!(
+        body["value"][0],
+        serde_json::json!({
+            "@odata.id": "http://example.com/odatainstruments(0)",
+            "@odata.editLink": "instruments(0)",
+            "@odata.type": "#default.instruments",
+            "offset@odata.type": "#Int64",
+            "offset": 0,
+            "opens@odata.type": "#TimeOfDay",
+            "opens": "09:30:00",
+            "settlement@odata.type": "#Duration",
+            "settlement": "P2D",
+        })
+    );
+    assert_eq!(body["value"].as_array().unwrap().len(), 1);
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments(time'PT0S')").await),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("json".to_string()),
+            select: Some("opens".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await;
+    assert!(matches!(
+        resp,
+        Err(datafusion_odata::error::ODataError::BadRequest(_))
+    ));
+
+    let mut ctx = fixture_types("instruments(time'PT0S')").await;
+    std::sync::Arc::get_mut(&mut ctx).unwrap().key_columns = vec!["settlement".to_string()];
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("json".to_string()),
+            select: Some("opens".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    pretty_assertions::assert_eq!(
+        body["d"],
+        serde_json::json!({
+            "__metadata": {
+                "id": "http://example.com/odatainstruments(time'PT0S')",
+                "uri": "http://example.com/odatainstruments(time'PT0S')",
+                "type": "default.instruments",
+            },
+            "opens": null,
+        })
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_complex() {
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments(0)").await),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("address".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <entry xml:base="http://example.com/odata/"
+             xmlns="http://www.w3.org/2005/Atom"
+             xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+             xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata">
+            <id>http://example.com/odatainstruments(0)</id>
+            <category scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" term="default.instruments"/>
+            <link rel="edit" title="instruments" href="instruments(0)"/>
+            <title/>
+            <updated>2023-01-01T00:00:00.000Z</updated>
+            <author><name/></author>
+            <content type="application/xml">
+            <m:properties>
+            <d:address m:type="default.instruments_address">
+            <d:city m:type="Edm.String">Vancouver</d:city>
+            <d:geo m:type="default.instruments_address_geo">
+            <d:lat m:type="Edm.Double">49.25</d:lat>
+            </d:geo>
+            </d:address>
+            </m:properties>
+            </content>
+            </entry>
+            "#
+        )
+        .replace('\n', "")
+    );
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments").await),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("application/json;odata.metadata=full".to_string()),
+            select: Some("offset,address/city".to_string()),
+            filter: Some("address/city eq 'Toronto'".parse().unwrap()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    pretty_assertions::assert_eq!(
+        body["value"],
+        serde_json::json!([{
+            "@odata.id": "http://example.com/odatainstruments(2)",
+            "@odata.editLink": "instruments(2)",
+            "@odata.type": "#default.instruments",
+            "offset@odata.type": "#Int64",
+            "offset": 2,
+            "address@odata.type": "#default.instruments_address",
+            "address": {"city": "Toronto"},
+        }])
+    );
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments").await),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("json".to_string()),
+            select: Some("address/geo/lat".to_string()),
+            order_by: Some("offset".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    let addresses: Vec<_> = body["d"]["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["address"].clone())
+        .collect();
+    pretty_assertions::assert_eq!(
+        serde_json::Value::from(addresses),
+        serde_json::json!([
+            {
+                "__metadata": {"type": "default.instruments_address"},
+                "geo": {
+                    "__metadata": {"type": "default.instruments_address_geo"},
+                    "lat": 49.25,
+                },
+            },
+            null,
+            {
+                "__metadata": {"type": "default.instruments_address"},
+                "geo": null,
+            },
+        ])
+    );
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments").await),
+        axum::extract::Query(QueryParamsRaw {
+            filter: Some("address/country eq 'Canada'".parse().unwrap()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await;
+    assert!(matches!(
+        resp,
+        Err(datafusion_odata::error::ODataError::BadRequest(_))
+    ));
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_collections() {
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments(0)").await),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("tags,fills".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <entry xml:base="http://example.com/odata/"
+             xmlns="http://www.w3.org/2005/Atom"
+             xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+             xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata">
+            <id>http://example.com/odatainstruments(0)</id>
+            <category scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" term="default.instruments"/>
+            <link rel="edit" title="instruments" href="instruments(0)"/>
+            <title/>
+            <updated>2023-01-01T00:00:00.000Z</updated>
+            <author><name/></author>
+            <content type="application/xml">
+            <m:properties>
+            <d:tags m:type="Collection(Edm.String)">
+            <d:element m:type="Edm.String">etf</d:element>
+            <d:element m:type="Edm.String">usd</d:element>
+            </d:tags>
+            <d:fills m:type="Collection(default.instruments_fills)">
+            <d:element m:type="default.instruments_fills">
+            <d:qty m:type="Edm.Int64">10</d:qty>
+            </d:element>
+            <d:element m:type="default.instruments_fills">
+            <d:qty m:type="Edm.Int64">5</d:qty>
+            </d:element>
+            </d:fills>
+            </m:properties>
+            </content>
+            </entry>
+            "#
+        )
+        .replace('\n', "")
+    );
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments").await),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("application/json;odata.metadata=full".to_string()),
+            select: Some("offset,tags,fills".to_string()),
+            filter: Some("tags/any(t: t eq 'usd')".parse().unwrap()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    pretty_assertions::assert_eq!(
+        body["value"],
+        serde_json::json!([{
+            "@odata.id": "http://example.com/odatainstruments(0)",
+            "@odata.editLink": "instruments(0)",
+            "@odata.type": "#default.instruments",
+            "offset@odata.type": "#Int64",
+            "offset": 0,
+            "tags": ["etf", "usd"],
+            "fills@odata.type": "#Collection(default.instruments_fills)",
+            "fills": [
+                {"qty@odata.type": "#Int64", "qty": 10},
+                {"qty@odata.type": "#Int64", "qty": 5},
+            ],
+        }])
+    );
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments(1)").await),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("json".to_string()),
+            select: Some("tags,fills".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    pretty_assertions::assert_eq!(
+        body["d"],
+        serde_json::json!({
+            "__metadata": {
+                "id": "http://example.com/odatainstruments(1)",
+                "uri": "http://example.com/odatainstruments(1)",
+                "type": "default.instruments",
+            },
+            "tags": {
+                "__metadata": {"type": "Collection(Edm.String)"},
+                "results": [],
+            },
+            "fills": {
+                "__metadata": {"type": "Collection(default.instruments_fills)"},
+                "results": [{
+                    "__metadata": {"type": "default.instruments_fills"},
+                    "qty": "1",
+                }],
+            },
+        })
+    );
+
+    for (filter, expected) in [
+        ("tags/any()", vec![0]),
+        ("not tags/any()", vec![1, 2]),
+        ("fills/any(f: f/qty lt 5)", vec![1]),
+        ("fills/all(f: f/qty ge 5)", vec![0, 2]),
+        ("fills/all(f: f/qty gt offset mul 2)", vec![0, 2]),
+        ("tags/all(t: t ne 'usd') and fills/any()", vec![1]),
+        (
+            "fills/any(f: tags/any(t: length(t) eq f/qty sub 7))",
+            vec![0],
+        ),
+    ] {
+        let resp = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(fixture_types("instruments").await),
+            axum::extract::Query(QueryParamsRaw {
+                format: Some("application/json;odata.metadata=none".to_string()),
+                select: Some("offset,tags,fills".to_string()),
+                filter: Some(filter.parse().unwrap()),
+                ..Default::default()
+            }),
+            axum::http::HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+        let offsets: Vec<_> = body["value"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["offset"].as_i64().unwrap())
+            .collect();
+        assert_eq!(offsets, expected, "{filter}");
+    }
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments").await),
+        axum::extract::Query(QueryParamsRaw {
+            filter: Some("offset/any(o: o eq 1)".parse().unwrap()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await;
+    assert!(matches!(
+        resp,
+        Err(datafusion_odata::error::ODataError::BadRequest(_))
+    ));
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_encoded() {
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments(0)").await),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("exchange,currency,attributes".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <entry xml:base="http://example.com/odata/"
+             xmlns="http://www.w3.org/2005/Atom"
+             xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+             xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata">
+            <id>http://example.com/odatainstruments(0)</id>
+            <category scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" term="default.instruments"/>
+            <link rel="edit" title="instruments" href="instruments(0)"/>
+            <title/>
+            <updated>2023-01-01T00:00:00.000Z</updated>
+            <author><name/></author>
+            <content type="application/xml">
+            <m:properties>
+            <d:exchange m:type="Edm.String">nyse</d:exchange>
+            <d:currency m:type="Edm.String">usd</d:currency>
+            <d:attributes m:type="Collection(default.instruments_attributes)">
+            <d:element m:type="default.instruments_attributes">
+            <d:key m:type="Edm.String">sector</d:key>
+            <d:value m:type="Edm.String">etf</d:value>
+            </d:element>
+            <d:element m:type="default.instruments_attributes">
+            <d:key m:type="Edm.String">region</d:key>
+            <d:value m:type="Edm.String" m:null="true"/>
+            </d:element>
+            </d:attributes>
+            </m:properties>
+            </content>
+            </entry>
+            "#
+        )
+        .replace('\n', "")
+    );
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments").await),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("application/json;odata.metadata=none".to_string()),
+            select: Some("exchange,currency,attributes".to_string()),
+            order_by: Some("offset".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    pretty_assertions::assert_eq!(
+        body["value"],
+        serde_json::json!([
+            {
+                "exchange": "nyse",
+                "currency": "usd",
+                "attributes": [
+                    {"key": "sector", "value": "etf"},
+                    {"key": "region", "value": null},
+                ],
+            },
+            {"exchange": "nyse", "currency": "usd", "attributes": []},
+            {"exchange": null, "currency": "eur", "attributes": null},
+        ])
+    );
+
+    for (filter, expected) in [
+        ("exchange eq 'nyse'", vec![0, 1]),
+        ("currency eq 'eur'", vec![2]),
+        (
+            "attributes/any(a: a/key eq 'sector' and a/value eq 'etf')",
+            vec![0],
+        ),
+    ] {
+        let resp = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(fixture_types("instruments").await),
+            axum::extract::Query(QueryParamsRaw {
+                format: Some("application/json;odata.metadata=none".to_string()),
+                select: Some("offset,exchange,currency,attributes".to_string()),
+                filter: Some(filter.parse().unwrap()),
+                ..Default::default()
+            }),
+            axum::http::HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+        let offsets: Vec<_> = body["value"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["offset"].as_i64().unwrap())
+            .collect();
+        assert_eq!(offsets, expected, "{filter}");
+    }
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments(2)/currency/$value").await),
+        axum::extract::Query(QueryParamsRaw::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(body_string(resp).await, "eur");
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_types("instruments(2)/exchange/$value").await),
+        axum::extract::Query(QueryParamsRaw::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_enum() {
+    let enum_ctx = |collection_elem: &'static str| async move {
+        let mut ctx = fixture_types(collection_elem).await;
+        std::sync::Arc::get_mut(&mut ctx).unwrap().enum_columns = vec![EnumColumn {
+            column: "exchange".to_string(),
+            type_name: "Exchange".to_string(),
+            members: vec!["nyse".to_string(), "nasdaq".to_string()],
+        }];
+        ctx
+    };
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(enum_ctx("instruments(0)").await),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("exchange,currency".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <entry xml:base="http://example.com/odata/"
+             xmlns="http://www.w3.org/2005/Atom"
+             xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+             xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata">
+            <id>http://example.com/odatainstruments(0)</id>
+            <category scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" term="default.instruments"/>
+            <link rel="edit" title="instruments" href="instruments(0)"/>
+            <title/>
+            <updated>2023-01-01T00:00:00.000Z</updated>
+            <author><name/></author>
+            <content type="application/xml">
+            <m:properties>
+            <d:exchange m:type="default.Exchange">nyse</d:exchange>
+            <d:currency m:type="Edm.String">usd</d:currency>
+            </m:properties>
+            </content>
+            </entry>
+            "#
+        )
+        .replace('\n', "")
+    );
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(enum_ctx("instruments").await),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("application/json;odata.metadata=full".to_string()),
+            select: Some("offset,exchange".to_string()),
+            filter: Some("exchange eq default.Exchange'nyse'".parse().unwrap()),
+            order_by: Some("offset".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    let entries: Vec<_> = body["value"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| {
+            (
+                entry["offset"].clone(),
+                entry["exchange"].clone(),
+                entry["exchange@odata.type"].clone(),
+            )
+        })
+        .collect();
+    pretty_assertions::assert_eq!(
+        entries,
+        vec![
+            (0.into(), "nyse".into(), "#default.Exchange".into()),
+            (1.into(), "nyse".into(), "#default.Exchange".into()),
+        ]
+    );
+
+    for filter in [
+        "exchange eq default.Exchange'lse'",
+        "exchange eq default.Currency'nyse'",
+        "exchange eq Foo.Bar'nyse'",
+        "currency eq default.Exchange'nyse'",
+    ] {
+        let res = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(enum_ctx("instruments").await),
+            axum::extract::Query(QueryParamsRaw {
+                filter: Some(filter.parse().unwrap()),
+                ..Default::default()
+            }),
+            axum::http::HeaderMap::new(),
+        )
+        .await;
+
+        assert!(
+            matches!(res, Err(datafusion_odata::error::ODataError::BadRequest(_))),
+            "{filter}"
+        );
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_spatial() {
+    let spatial_ctx = |collection_elem: &'static str| async move {
+        let mut ctx = fixture_types(collection_elem).await;
+        std::sync::Arc::get_mut(&mut ctx).unwrap().spatial_columns = vec![
+            SpatialColumn {
+                column: "location".to_string(),
+                spatial_type: SpatialType::Geography(SpatialShape::Point),
+            },
+            SpatialColumn {
+                column: "zone".to_string(),
+                spatial_type: SpatialType::Geometry(SpatialShape::Any),
+            },
+        ];
+        ctx
+    };
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(spatial_ctx("instruments(0)").await),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("location,zone".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <entry xml:base="http://example.com/odata/"
+             xmlns="http://www.w3.org/2005/Atom"
+             xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+             xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata">
+            <id>http://example.com/odatainstruments(0)</id>
+            <category scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" term="default.instruments"/>
+            <link rel="edit" title="instruments" href="instruments(0)"/>
+            <title/>
+            <updated>2023-01-01T00:00:00.000Z</updated>
+            <author><name/></author>
+            <content type="application/xml">
+            <m:properties>
+            <d:location m:type="Edm.GeographyPoint">
+            <gml:Point xmlns:gml="http://www.opengis.net/gml" gml:srsName="http://www.opengis.net/def/crs/EPSG/0/4326">
+            <gml:pos>49.2827 -123.1207</gml:pos>
+            </gml:Point>
+            </d:location>
+            <d:zone m:type="Edm.Geometry">
+            <gml:Polygon xmlns:gml="http://www.opengis.net/gml" gml:srsName="http://www.opengis.net/def/crs/EPSG/0/0">
+            <gml:exterior>
+            <gml:LinearRing>
+            <gml:pos>0 0</gml:pos>
+            <gml:pos>4 0</gml:pos>
+            <gml:pos>4 4</gml:pos>
+            <gml:pos>0 4</gml:pos>
+            <gml:pos>0 0</gml:pos>
+            </gml:LinearRing>
+            </gml:exterior>
+            </gml:Polygon>
+            </d:zone>
+            </m:properties>
+            </content>
+            </entry>
+            "#
+        )
+        .replace('\n', "")
+    );
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(spatial_ctx("instruments").await),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("application/json;odata.metadata=full".to_string()),
+            select: Some("offset,location,zone".to_string()),
+            order_by: Some("offset".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    pretty_assertions::assert_eq!(
+        body["value"][1],
+        serde_json::json!({
+            "@odata.id": "http://example.com/odatainstruments(1)",
+            "@odata.editLink": "instruments(1)",
+            "@odata.type": "#default.instruments",
+            "offset@odata.type": "#Int64",
+            "offset": 1,
+            "location@odata.type": "#GeographyPoint",
+            "location": {
+                "type": "Point",
+                "coordinates": [-79.3832, 43.6532],
+                "crs": {"type": "name", "properties": {"name": "EPSG:4326"}},
+            },
+            "zone@odata.type": "#Geometry",
+            "zone": {
+                "type": "LineString",
+                "coordinates": [[5.0, 0.0], [5.0, 4.0]],
+                "crs": {"type": "name", "properties": {"name": "EPSG:0"}},
+            },
+        })
+    );
+    pretty_assertions::assert_eq!(body["value"][2]["location"], serde_json::Value::Null);
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(spatial_ctx("instruments(1)/location").await),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("json".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    pretty_assertions::assert_eq!(
+        body["d"]["location"],
+        serde_json::json!({
+            "__metadata": {"type": "Edm.GeographyPoint"},
+            "type": "Point",
+            "coordinates": [-79.3832, 43.6532],
+            "crs": {"type": "name", "properties": {"name": "EPSG:4326"}},
+        })
+    );
+
+    for (filter, expected) in [
+        (
+            "geo.distance(location, geography'POINT(-122.3321 47.6062)') lt 200000",
+            vec![0],
+        ),
+        (
+            "geo.distance(location, geography'SRID=4326;POINT(-122.3321 47.6062)') gt 200000",
+            vec![1],
+        ),
+        ("geo.intersects(zone, geometry'POINT(2 2)')", vec![0]),
+        (
+            "geo.intersects(zone, geometry'LINESTRING(3 2, 6 2)')",
+            vec![0, 1],
+        ),
+        ("not geo.intersects(zone, geometry'POINT(5 5)')", vec![0, 1]),
+    ] {
+        let resp = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(spatial_ctx("instruments").await),
+            axum::extract::Query(QueryParamsRaw {
+                format: Some("application/json;odata.metadata=none".to_string()),
+                select: Some("offset,location,zone".to_string()),
+                filter: Some(filter.parse().unwrap()),
+                ..Default::default()
+            }),
+            axum::http::HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+        let offsets: Vec<_> = body["value"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["offset"].as_i64().unwrap())
+            .collect();
+        assert_eq!(offsets, expected, "{filter}");
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_collection_nulls() {
+    let properties = indoc!(
+        r#"
+        <m:properties>
+        <d:offset m:type="Edm.Int64">1</d:offset>
+        <d:boolean m:type="Edm.Boolean" m:null="true"/>
+        <d:int8 m:type="Edm.SByte" m:null="true"/>
+        <d:int16 m:type="Edm.Int16" m:null="true"/>
+        <d:int32 m:type="Edm.Int32" m:null="true"/>
+        <d:int64 m:type="Edm.Int64" m:null="true"/>
+        <d:uint8 m:type="Edm.Byte" m:null="true"/>
+        <d:uint16 m:type="Edm.Int32" m:null="true"/>
+        <d:uint32 m:type="Edm.Int64" m:null="true"/>
+        <d:uint64 m:type="Edm.Decimal" m:null="true"/>
+        <d:float32 m:type="Edm.Single" m:null="true"/>
+        <d:float64 m:type="Edm.Double" m:null="true"/>
+        <d:utf8 m:type="Edm.String" m:null="true"/>
+        <d:large_utf8 m:type="Edm.String" m:null="true"/>
+        <d:utf8_view m:type="Edm.String" m:null="true"/>
+        <d:timestamp m:type="Edm.DateTime" m:null="true"/>
+        <d:timestamp_tz m:type="Edm.DateTimeOffset" m:null="true"/>
+        <d:date32 m:type="Edm.DateTime" m:null="true"/>
+        <d:date64 m:type="Edm.DateTime" m:null="true"/>
+        <d:decimal m:type="Edm.Decimal" m:null="true"/>
+        <d:binary m:type="Edm.Binary" m:null="true"/>
+        <d:fixed_binary m:type="Edm.Binary" m:null="true"/>
+        <d:time m:type="Edm.Time" m:null="true"/>
+        <d:duration m:type="Edm.Time" m:null="true"/>
+        <d:interval m:type="Edm.Time" m:null="true"/>
+        <d:dictionary m:type="Edm.String" m:null="true"/>
+        <d:complex m:type="default.nullables_complex" m:null="true"/>
+        <d:collection m:type="Collection(Edm.Int64)" m:null="true"/>
+        </m:properties>
+        "#
+    );
+
+    // Feed and entry resources share the entry writer
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_nulls("nullables").await),
+        axum::extract::Query(QueryParamsRaw {
+            filter: Some("offset eq 1".parse().unwrap()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        format!(
+            indoc!(
+                r#"
+                <?xml version="1.0" encoding="utf-8"?>
+                <feed xml:base="http://example.com/odata/"
+                 xmlns="http://www.w3.org/2005/Atom"
+                 xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+                 xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata">
+                <id>http://example.com/odatanullables</id>
+                <title type="text">nullables</title>
+                <updated>2023-01-01T00:00:00.000Z</updated>
+                <link rel="self" title="nullables" href="nullables"/>
+                <entry>
+                <id>http://example.com/odatanullables(1)</id>
+                <category scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" term="default.nullables"/>
+                <link rel="edit" title="nullables" href="nullables(1)"/>
+                <title/>
+                <updated>2023-01-01T00:00:00.000Z</updated>
+                <author><name/></author>
+                <content type="application/xml">
+                {}
+                </content>
+                </entry>
+                </feed>
+                "#
+            ),
+            properties
+        )
+        .replace('\n', "")
+    );
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(fixture_nulls("nullables(1)").await),
+        axum::extract::Query(QueryParamsRaw::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        format!(
+            indoc!(
+                r#"
+                <?xml version="1.0" encoding="utf-8"?>
+                <entry xml:base="http://example.com/odata/"
+                 xmlns="http://www.w3.org/2005/Atom"
+                 xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+                 xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata">
+                <id>http://example.com/odatanullables(1)</id>
+                <category scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" term="default.nullables"/>
+                <link rel="edit" title="nullables" href="nullables(1)"/>
+                <title/>
+                <updated>2023-01-01T00:00:00.000Z</updated>
+                <author><name/></author>
+                <content type="application/xml">
+                {}
+                </content>
+                </entry>
+                "#
+            ),
+            properties
+        )
+        .replace('\n', "")
+    );
+
+    for (format, values) in [
+        ("application/json;odata.metadata=minimal", "/value"),
+        ("json", "/d/results"),
+    ] {
+        let resp = datafusion_odata::handlers::odata_collection_handler(
+            axum::Extension(fixture_nulls("nullables").await),
+            axum::extract::Query(QueryParamsRaw {
+                format: Some(format.to_string()),
+                filter: Some("offset eq 1".parse().unwrap()),
+                ..Default::default()
+            }),
+            axum::http::HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+        let entry = body.pointer(values).unwrap()[0].as_object().unwrap();
+        let mut non_null: Vec<_> = entry
+            .iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(name, _)| name.as_str())
+            .collect();
+        non_null.sort();
+        match format {
+            "json" => assert_eq!(non_null, ["__metadata", "offset"], "{format}"),
+            _ => assert_eq!(non_null, ["offset"], "{format}"),
+        }
+        assert_eq!(entry.len(), 27 + non_null.len(), "{format}");
+    }
+
+    // Entries with null key values are not addressable, so they have no id
+    // and edit link
+    let null_key_ctx = || async {
+        let mut ctx = fixture_nulls("nullables").await;
+        std::sync::Arc::get_mut(&mut ctx).unwrap().key_columns = vec!["int32".to_string()];
+        ctx
+    };
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(null_key_ctx().await),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("json".to_string()),
+            select: Some("offset".to_string()),
+            order_by: Some("offset".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    let metadata: Vec<_> = body["d"]["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["__metadata"].clone())
+        .collect();
+    assert_eq!(
+        metadata,
+        [
+            serde_json::json!({
+                "id": "http://example.com/odatanullables(-32)",
+                "uri": "http://example.com/odatanullables(-32)",
+                "type": "default.nullables",
+            }),
+            serde_json::json!({"type": "default.nullables"}),
+        ]
+    );
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(null_key_ctx().await),
+        axum::extract::Query(QueryParamsRaw {
+            format: Some("application/json;odata.metadata=full".to_string()),
+            select: Some("offset".to_string()),
+            order_by: Some("offset".to_string()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    let links: Vec<_> = body["value"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| (entry.get("@odata.id"), entry.get("@odata.editLink")))
+        .collect();
+    assert_eq!(
+        links,
+        [
+            (
+                Some(&"http://example.com/odatanullables(-32)".into()),
+                Some(&"nullables(-32)".into())
+            ),
+            (None, None),
+        ]
+    );
+
+    let resp = datafusion_odata::handlers::odata_collection_handler(
+        axum::Extension(null_key_ctx().await),
+        axum::extract::Query(QueryParamsRaw {
+            select: Some("offset".to_string()),
+            filter: Some("offset eq 1".parse().unwrap()),
+            ..Default::default()
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        body_string(resp).await,
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <feed xml:base="http://example.com/odata/"
+             xmlns="http://www.w3.org/2005/Atom"
+             xmlns:d="http://schemas.microsoft.com/ado/2007/08/dataservices"
+             xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata">
+            <id>http://example.com/odatanullables</id>
+            <title type="text">nullables</title>
+            <updated>2023-01-01T00:00:00.000Z</updated>
+            <link rel="self" title="nullables" href="nullables"/>
+            <entry>
+            <category scheme="http://schemas.microsoft.com/ado/2007/08/dataservices/scheme" term="default.nullables"/>
+            <title/>
+            <updated>2023-01-01T00:00:00.000Z</updated>
+            <author><name/></author>
+            <content type="application/xml">
+            <m:properties>
+            <d:offset m:type="Edm.Int64">1</d:offset>
+            </m:properties>
+            </content>
+            </entry>
+            </feed>
+            "#
+        )
+        .replace('\n', "")
+    );
+}
@@ -1,17 +1,28 @@
 mod shared;
 
+use datafusion_odata::{
+    context::{EnumColumn, SpatialColumn},
+    geo::{SpatialShape, SpatialType},
+    negotiation::FormatQueryParam,
+};
 use indoc::indoc;
 
-use shared::fixture;
+use shared::{
+    fixture, fixture_composite, fixture_nulls, fixture_related, fixture_types, fixture_unsupported,
+};
 
 ///////////////////////////////////////////////////////////////////////////////
 
 #[tokio::test]
 async fn test_service() {
     let ctx = fixture("tickers.spy").await;
-    let resp = datafusion_odata::handlers::odata_service_handler(axum::Extension(ctx))
-        .await
-        .unwrap();
+    let resp = datafusion_odata::handlers::odata_service_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(FormatQueryParam::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
     pretty_assertions::assert_eq!(
         *resp.body(),
         indoc!(
@@ -41,9 +52,13 @@ async fn test_service() {
 #[tokio::test]
 async fn test_metadata() {
     let ctx = fixture("tickers.spy").await;
-    let resp = datafusion_odata::handlers::odata_metadata_handler(axum::Extension(ctx))
-        .await
-        .unwrap();
+    let resp = datafusion_odata::handlers::odata_metadata_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(FormatQueryParam::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
     pretty_assertions::assert_eq!(
         *resp.body(),
         indoc!(
@@ -87,3 +102,491 @@ async fn test_metadata() {
         .replace('\n', "")
     );
 }
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_metadata_navigation() {
+    let ctx = fixture_related("customers").await;
+    let resp = datafusion_odata::handlers::odata_metadata_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(FormatQueryParam::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        *resp.body(),
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <edmx:Edmx xmlns:edmx="http://schemas.microsoft.com/ado/2007/06/edmx" Version="1.0">
+            <edmx:DataServices xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata" m:DataServiceVersion="3.0" m:MaxDataServiceVersion="3.0">
+            <Schema Namespace="default" xmlns="http://schemas.microsoft.com/ado/2009/11/edm">
+            <EntityType Name="customers">
+            <Key><PropertyRef Name="offset"/></Key>
+            <Property Name="offset" Type="Edm.Int64" Nullable="false"/>
+            <Property Name="name" Type="Edm.String" Nullable="false"/>
+            <NavigationProperty Name="orders" Relationship="default.customers_orders" FromRole="customers" ToRole="orders"/>
+            </EntityType>
+            <EntityType Name="orders">
+            <Key><PropertyRef Name="offset"/></Key>
+            <Property Name="offset" Type="Edm.Int64" Nullable="false"/>
+            <Property Name="customer_id" Type="Edm.Int64" Nullable="true"/>
+            <NavigationProperty Name="customer" Relationship="default.orders_customer" FromRole="orders" ToRole="customers"/>
+            </EntityType>
+            <Association Name="customers_orders">
+            <End Type="default.customers" Role="customers" Multiplicity="0..1"/>
+            <End Type="default.orders" Role="orders" Multiplicity="*"/>
+            </Association>
+            <Association Name="orders_customer">
+            <End Type="default.orders" Role="orders" Multiplicity="*"/>
+            <End Type="default.customers" Role="customers" Multiplicity="0..1"/>
+            </Association>
+            <EntityContainer Name="default" m:IsDefaultEntityContainer="true">
+            <EntitySet Name="customers" EntityType="default.customers"/>
+            <EntitySet Name="orders" EntityType="default.orders"/>
+            <AssociationSet Name="customers_orders" Association="default.customers_orders">
+            <End Role="customers" EntitySet="customers"/>
+            <End Role="orders" EntitySet="orders"/>
+            </AssociationSet>
+            <AssociationSet Name="orders_customer" Association="default.orders_customer">
+            <End Role="orders" EntitySet="orders"/>
+            <End Role="customers" EntitySet="customers"/>
+            </AssociationSet>
+            </EntityContainer>
+            </Schema>
+            </edmx:DataServices>
+            </edmx:Edmx>
+            "#
+        )
+        .replace('\n', "")
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_metadata_composite_key() {
+    let ctx = fixture_composite("order_lines").await;
+    let resp = datafusion_odata::handlers::odata_metadata_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(FormatQueryParam::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        *resp.body(),
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <edmx:Edmx xmlns:edmx="http://schemas.microsoft.com/ado/2007/06/edmx" Version="1.0">
+            <edmx:DataServices xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata" m:DataServiceVersion="3.0" m:MaxDataServiceVersion="3.0">
+            <Schema Namespace="default" xmlns="http://schemas.microsoft.com/ado/2009/11/edm">
+            <EntityType Name="order_lines">
+            <Key><PropertyRef Name="order_id"/><PropertyRef Name="line"/></Key>
+            <Property Name="order_id" Type="Edm.Int64" Nullable="false"/>
+            <Property Name="line" Type="Edm.Int64" Nullable="false"/>
+            <Property Name="quantity" Type="Edm.Int64" Nullable="false"/>
+            </EntityType>
+            <EntityContainer Name="default" m:IsDefaultEntityContainer="true">
+            <EntitySet Name="order_lines" EntityType="default.order_lines"/>
+            </EntityContainer>
+            </Schema>
+            </edmx:DataServices>
+            </edmx:Edmx>
+            "#
+        )
+        .replace('\n', "")
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_metadata_types() {
+    let ctx = fixture_types("instruments").await;
+    let resp = datafusion_odata::handlers::odata_metadata_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(FormatQueryParam::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        *resp.body(),
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <edmx:Edmx xmlns:edmx="http://schemas.microsoft.com/ado/2007/06/edmx" Version="1.0">
+            <edmx:DataServices xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata" m:DataServiceVersion="3.0" m:MaxDataServiceVersion="3.0">
+            <Schema Namespace="default" xmlns="http://schemas.microsoft.com/ado/2009/11/edm">
+            <EntityType Name="instruments">
+            <Key><PropertyRef Name="offset"/></Key>
+            <Property Name="offset" Type="Edm.Int64" Nullable="false"/>
+            <Property Name="price" Type="Edm.Decimal" Nullable="true" Precision="10" Scale="2"/>
+            <Property Name="hash" Type="Edm.Binary" Nullable="false" MaxLength="2" FixedLength="true"/>
+            <Property Name="payload" Type="Edm.Binary" Nullable="true"/>
+            <Property Name="opens" Type="Edm.Time" Nullable="true"/>
+            <Property Name="settlement" Type="Edm.Time" Nullable="false"/>
+            <Property Name="address" Type="default.instruments_address" Nullable="true"/>
+            <Property Name="tags" Type="Collection(Edm.String)" Nullable="true"/>
+            <Property Name="fills" Type="Collection(default.instruments_fills)" Nullable="false"/>
+            <Property Name="exchange" Type="Edm.String" Nullable="true"/>
+            <Property Name="currency" Type="Edm.String" Nullable="false"/>
+            <Property Name="attributes" Type="Collection(default.instruments_attributes)" Nullable="true"/>
+            <Property Name="location" Type="Edm.Binary" Nullable="true"/>
+            <Property Name="zone" Type="Edm.String" Nullable="true"/>
+            </EntityType>
+            <ComplexType Name="instruments_address">
+            <Property Name="city" Type="Edm.String" Nullable="true"/>
+            <Property Name="geo" Type="default.instruments_address_geo" Nullable="true"/>
+            </ComplexType>
+            <ComplexType Name="instruments_address_geo">
+            <Property Name="lat" Type="Edm.Double" Nullable="false"/>
+            </ComplexType>
+            <ComplexType Name="instruments_fills">
+            <Property Name="qty" Type="Edm.Int64" Nullable="false"/>
+            </ComplexType>
+            <ComplexType Name="instruments_attributes">
+            <Property Name="key" Type="Edm.String" Nullable="false"/>
+            <Property Name="value" Type="Edm.String" Nullable="true"/>
+            </ComplexType>
+            <EntityContainer Name="default" m:IsDefaultEntityContainer="true">
+            <EntitySet Name="instruments" EntityType="default.instruments"/>
+            </EntityContainer>
+            </Schema>
+            </edmx:DataServices>
+            </edmx:Edmx>
+            "#
+        )
+        .replace('\n', "")
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_metadata_nulls() {
+    let ctx = fixture_nulls("nullables").await;
+    let resp = datafusion_odata::handlers::odata_metadata_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(FormatQueryParam::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        *resp.body(),
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <edmx:Edmx xmlns:edmx="http://schemas.microsoft.com/ado/2007/06/edmx" Version="1.0">
+            <edmx:DataServices xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata" m:DataServiceVersion="3.0" m:MaxDataServiceVersion="3.0">
+            <Schema Namespace="default" xmlns="http://schemas.microsoft.com/ado/2009/11/edm">
+            <EntityType Name="nullables">
+            <Key><PropertyRef Name="offset"/></Key>
+            <Property Name="offset" Type="Edm.Int64" Nullable="false"/>
+            <Property Name="boolean" Type="Edm.Boolean" Nullable="true"/>
+            <Property Name="int8" Type="Edm.SByte" Nullable="true"/>
+            <Property Name="int16" Type="Edm.Int16" Nullable="true"/>
+            <Property Name="int32" Type="Edm.Int32" Nullable="true"/>
+            <Property Name="int64" Type="Edm.Int64" Nullable="true"/>
+            <Property Name="uint8" Type="Edm.Byte" Nullable="true"/>
+            <Property Name="uint16" Type="Edm.Int32" Nullable="true"/>
+            <Property Name="uint32" Type="Edm.Int64" Nullable="true"/>
+            <Property Name="uint64" Type="Edm.Decimal" Nullable="true" Precision="20" Scale="0"/>
+            <Property Name="float32" Type="Edm.Single" Nullable="true"/>
+            <Property Name="float64" Type="Edm.Double" Nullable="true"/>
+            <Property Name="utf8" Type="Edm.String" Nullable="true"/>
+            <Property Name="large_utf8" Type="Edm.String" Nullable="true"/>
+            <Property Name="utf8_view" Type="Edm.String" Nullable="true"/>
+            <Property Name="timestamp" Type="Edm.DateTime" Nullable="true"/>
+            <Property Name="timestamp_tz" Type="Edm.DateTimeOffset" Nullable="true"/>
+            <Property Name="date32" Type="Edm.DateTime" Nullable="true"/>
+            <Property Name="date64" Type="Edm.DateTime" Nullable="true"/>
+            <Property Name="decimal" Type="Edm.Decimal" Nullable="true" Precision="10" Scale="2"/>
+            <Property Name="binary" Type="Edm.Binary" Nullable="true"/>
+            <Property Name="fixed_binary" Type="Edm.Binary" Nullable="true" MaxLength="2" FixedLength="true"/>
+            <Property Name="time" Type="Edm.Time" Nullable="true"/>
+            <Property Name="duration" Type="Edm.Time" Nullable="true"/>
+            <Property Name="interval" Type="Edm.Time" Nullable="true"/>
+            <Property Name="dictionary" Type="Edm.String" Nullable="true"/>
+            <Property Name="complex" Type="default.nullables_complex" Nullable="true"/>
+            <Property Name="collection" Type="Collection(Edm.Int64)" Nullable="true"/>
+            </EntityType>
+            <ComplexType Name="nullables_complex">
+            <Property Name="a" Type="Edm.Int64" Nullable="false"/>
+            </ComplexType>
+            <EntityContainer Name="default" m:IsDefaultEntityContainer="true">
+            <EntitySet Name="nullables" EntityType="default.nullables"/>
+            </EntityContainer>
+            </Schema>
+            </edmx:DataServices>
+            </edmx:Edmx>
+            "#
+        )
+        .replace('\n', "")
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_metadata_unsupported() {
+    // Error names the nested field type that is unsupported, not the struct
+    let res = datafusion_odata::handlers::odata_metadata_handler(
+        axum::Extension(fixture_unsupported("unsupported").await),
+        axum::extract::Query(FormatQueryParam::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await;
+    assert!(
+        matches!(
+            &res,
+            Err(datafusion_odata::error::ODataError::UnsupportedDataType(err))
+                if err.data_type == datafusion::arrow::datatypes::DataType::Null
+        ),
+        "{res:?}"
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_metadata_enums() {
+    let mut ctx = fixture_types("instruments").await;
+    std::sync::Arc::get_mut(&mut ctx).unwrap().enum_columns = vec![
+        EnumColumn {
+            column: "exchange".to_string(),
+            type_name: "Exchange".to_string(),
+            members: vec!["nyse".to_string()],
+        },
+        EnumColumn {
+            column: "currency".to_string(),
+            type_name: "Currency".to_string(),
+            members: vec!["usd".to_string(), "eur".to_string(), "cad".to_string()],
+        },
+    ];
+    let resp = datafusion_odata::handlers::odata_metadata_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(FormatQueryParam::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        *resp.body(),
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <edmx:Edmx xmlns:edmx="http://schemas.microsoft.com/ado/2007/06/edmx" Version="1.0">
+            <edmx:DataServices xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata" m:DataServiceVersion="3.0" m:MaxDataServiceVersion="3.0">
+            <Schema Namespace="default" xmlns="http://schemas.microsoft.com/ado/2009/11/edm">
+            <EntityType Name="instruments">
+            <Key><PropertyRef Name="offset"/></Key>
+            <Property Name="offset" Type="Edm.Int64" Nullable="false"/>
+            <Property Name="price" Type="Edm.Decimal" Nullable="true" Precision="10" Scale="2"/>
+            <Property Name="hash" Type="Edm.Binary" Nullable="false" MaxLength="2" FixedLength="true"/>
+            <Property Name="payload" Type="Edm.Binary" Nullable="true"/>
+            <Property Name="opens" Type="Edm.Time" Nullable="true"/>
+            <Property Name="settlement" Type="Edm.Time" Nullable="false"/>
+            <Property Name="address" Type="default.instruments_address" Nullable="true"/>
+            <Property Name="tags" Type="Collection(Edm.String)" Nullable="true"/>
+            <Property Name="fills" Type="Collection(default.instruments_fills)" Nullable="false"/>
+            <Property Name="exchange" Type="default.Exchange" Nullable="true"/>
+            <Property Name="currency" Type="default.Currency" Nullable="false"/>
+            <Property Name="attributes" Type="Collection(default.instruments_attributes)" Nullable="true"/>
+            <Property Name="location" Type="Edm.Binary" Nullable="true"/>
+            <Property Name="zone" Type="Edm.String" Nullable="true"/>
+            </EntityType>
+            <ComplexType Name="instruments_address">
+            <Property Name="city" Type="Edm.String" Nullable="true"/>
+            <Property Name="geo" Type="default.instruments_address_geo" Nullable="true"/>
+            </ComplexType>
+            <ComplexType Name="instruments_address_geo">
+            <Property Name="lat" Type="Edm.Double" Nullable="false"/>
+            </ComplexType>
+            <ComplexType Name="instruments_fills">
+            <Property Name="qty" Type="Edm.Int64" Nullable="false"/>
+            </ComplexType>
+            <ComplexType Name="instruments_attributes">
+            <Property Name="key" Type="Edm.String" Nullable="false"/>
+            <Property Name="value" Type="Edm.String" Nullable="true"/>
+            </ComplexType>
+            <EnumType Name="Exchange" UnderlyingType="Edm.Int32">
+            <Member Name="nyse" Value="0"/>
+            </EnumType>
+            <EnumType Name="Currency" UnderlyingType="Edm.Int32">
+            <Member Name="usd" Value="0"/>
+            <Member Name="eur" Value="1"/>
+            <Member Name="cad" Value="2"/>
+            </EnumType>
+            <EntityContainer Name="default" m:IsDefaultEntityContainer="true">
+            <EntitySet Name="instruments" EntityType="default.instruments"/>
+            </EntityContainer>
+            </Schema>
+            </edmx:DataServices>
+            </edmx:Edmx>
+            "#
+        )
+        .replace('\n', "")
+    );
+
+    // Members are published as identifiers, so the values cannot be arbitrary
+    let mut ctx = fixture_types("instruments").await;
+    std::sync::Arc::get_mut(&mut ctx).unwrap().enum_columns = vec![EnumColumn {
+        column: "exchange".to_string(),
+        type_name: "Exchange".to_string(),
+        members: vec!["nyse".to_string(), "on hold".to_string()],
+    }];
+    let res = datafusion_odata::handlers::odata_metadata_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(FormatQueryParam::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await;
+    assert!(matches!(
+        res,
+        Err(datafusion_odata::error::ODataError::Internal(_))
+    ));
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_metadata_spatial() {
+    let mut ctx = fixture_types("instruments").await;
+    std::sync::Arc::get_mut(&mut ctx).unwrap().spatial_columns = vec![
+        SpatialColumn {
+            column: "location".to_string(),
+            spatial_type: SpatialType::Geography(SpatialShape::Point),
+        },
+        SpatialColumn {
+            column: "zone".to_string(),
+            spatial_type: SpatialType::Geometry(SpatialShape::Any),
+        },
+    ];
+    let resp = datafusion_odata::handlers::odata_metadata_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(FormatQueryParam::default()),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        *resp.body(),
+        indoc!(
+            r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <edmx:Edmx xmlns:edmx="http://schemas.microsoft.com/ado/2007/06/edmx" Version="1.0">
+            <edmx:DataServices xmlns:m="http://schemas.microsoft.com/ado/2007/08/dataservices/metadata" m:DataServiceVersion="3.0" m:MaxDataServiceVersion="3.0">
+            <Schema Namespace="default" xmlns="http://schemas.microsoft.com/ado/2009/11/edm">
+            <EntityType Name="instruments">
+            <Key><PropertyRef Name="offset"/></Key>
+            <Property Name="offset" Type="Edm.Int64" Nullable="false"/>
+            <Property Name="price" Type="Edm.Decimal" Nullable="true" Precision="10" Scale="2"/>
+            <Property Name="hash" Type="Edm.Binary" Nullable="false" MaxLength="2" FixedLength="true"/>
+            <Property Name="payload" Type="Edm.Binary" Nullable="true"/>
+            <Property Name="opens" Type="Edm.Time" Nullable="true"/>
+            <Property Name="settlement" Type="Edm.Time" Nullable="false"/>
+            <Property Name="address" Type="default.instruments_address" Nullable="true"/>
+            <Property Name="tags" Type="Collection(Edm.String)" Nullable="true"/>
+            <Property Name="fills" Type="Collection(default.instruments_fills)" Nullable="false"/>
+            <Property Name="exchange" Type="Edm.String" Nullable="true"/>
+            <Property Name="currency" Type="Edm.String" Nullable="false"/>
+            <Property Name="attributes" Type="Collection(default.instruments_attributes)" Nullable="true"/>
+            <Property Name="location" Type="Edm.GeographyPoint" Nullable="true"/>
+            <Property Name="zone" Type="Edm.Geometry" Nullable="true"/>
+            </EntityType>
+            <ComplexType Name="instruments_address">
+            <Property Name="city" Type="Edm.String" Nullable="true"/>
+            <Property Name="geo" Type="default.instruments_address_geo" Nullable="true"/>
+            </ComplexType>
+            <ComplexType Name="instruments_address_geo">
+            <Property Name="lat" Type="Edm.Double" Nullable="false"/>
+            </ComplexType>
+            <ComplexType Name="instruments_fills">
+            <Property Name="qty" Type="Edm.Int64" Nullable="false"/>
+            </ComplexType>
+            <ComplexType Name="instruments_attributes">
+            <Property Name="key" Type="Edm.String" Nullable="false"/>
+            <Property Name="value" Type="Edm.String" Nullable="true"/>
+            </ComplexType>
+            <EntityContainer Name="default" m:IsDefaultEntityContainer="true">
+            <EntitySet Name="instruments" EntityType="default.instruments"/>
+            </EntityContainer>
+            </Schema>
+            </edmx:DataServices>
+            </edmx:Edmx>
+            "#
+        )
+        .replace('\n', "")
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_service_json() {
+    let ctx = fixture("tickers.spy").await;
+    let resp = datafusion_odata::handlers::odata_service_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(FormatQueryParam::default()),
+        axum::http::HeaderMap::from_iter([(
+            http::header::ACCEPT,
+            "application/json".parse().unwrap(),
+        )]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.headers()["OData-Version"], "4.0");
+    pretty_assertions::assert_eq!(
+        serde_json::from_str::<serde_json::Value>(resp.body()).unwrap(),
+        serde_json::json!({
+            "@odata.context": "http://example.com/odata/$metadata",
+            "value": [
+                {"name": "covid19.canada", "kind": "EntitySet", "url": "covid19.canada"},
+                {"name": "tickers.spy", "kind": "EntitySet", "url": "tickers.spy"},
+            ]
+        })
+    );
+
+    let ctx = fixture("tickers.spy").await;
+    let resp = datafusion_odata::handlers::odata_service_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(FormatQueryParam {
+            format: Some("json".to_string()),
+        }),
+        axum::http::HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        serde_json::from_str::<serde_json::Value>(resp.body()).unwrap(),
+        serde_json::json!({
+            "d": {
+                "EntitySets": ["covid19.canada", "tickers.spy"],
+            }
+        })
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[tokio::test]
+async fn test_metadata_not_acceptable() {
+    let ctx = fixture("tickers.spy").await;
+    let res = datafusion_odata::handlers::odata_metadata_handler(
+        axum::Extension(ctx),
+        axum::extract::Query(FormatQueryParam::default()),
+        axum::http::HeaderMap::from_iter([(
+            http::header::ACCEPT,
+            "application/json".parse().unwrap(),
+        )]),
+    )
+    .await;
+    let resp = axum::response::IntoResponse::into_response(res.unwrap_err());
+    assert_eq!(resp.status(), http::StatusCode::NOT_ACCEPTABLE);
+}